@@ -0,0 +1,11 @@
+/*
+clang add.c -arch arm64 -o add
+*/
+
+int add(int a, int b) { return (a + b); }
+
+int main(int ac, char **av) {
+  (void)av;
+
+  return (add(ac, 0));
+}
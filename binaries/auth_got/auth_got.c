@@ -0,0 +1,14 @@
+/*
+clang auth_got.c -arch arm64e -o auth_got
+*/
+
+#include <string.h>
+#include <unistd.h>
+
+// called through __auth_stubs, which authenticate the __auth_got slot
+size_t call_strlen(const char *s) { return (strlen(s)); }
+
+// a variable of libSystem, loaded through the GOT and never branched to
+int read_optind(void) { return (optind); }
+
+int main(void) { return ((int)call_strlen("auth") + read_optind()); }
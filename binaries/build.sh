@@ -0,0 +1,35 @@
+#!/bin/sh
+# Builds the fixtures that only ship as sources, with the command at the
+# top of each source. The tests that need them are ignored until they
+# are built, run them with `cargo test -- --ignored`.
+#
+# Needs macOS on arm64 with the command line tools, auth_got also needs
+# an SDK that can target arm64e.
+set -eu
+cd "$(dirname "$0")"
+
+(cd add && clang add.c -arch arm64 -o add)
+(cd auth_got && clang auth_got.c -arch arm64e -o auth_got)
+(cd common && clang common.c -arch arm64 -fcommon -o common)
+(
+    cd duplicate
+    clang foo_a.c -arch arm64 -dynamiclib -install_name @loader_path/libfoo_a.dylib -o libfoo_a.dylib
+    clang foo_b.c -arch arm64 -dynamiclib -install_name @loader_path/libfoo_b.dylib -o libfoo_b.dylib
+    clang duplicate.c -arch arm64 libfoo_b.dylib -o duplicate
+    clang duplicate.c -arch arm64 -Wl,-flat_namespace libfoo_b.dylib -o duplicate_flat
+)
+(cd flat && clang flat.c -arch arm64 -Wl,-flat_namespace -o flat)
+(cd libadd && clang libadd.c -arch arm64 -dynamiclib -o libadd.dylib)
+(cd local_got && clang local_got.c -arch arm64 -Wl,-interposable -o local_got)
+(cd multicall && clang multicall.c -arch arm64 -o multicall)
+(
+    cd preload
+    clang mymalloc.c -arch arm64 -dynamiclib -o libmymalloc.dylib
+    clang preload.c -arch arm64 -Wl,-flat_namespace -o preload
+)
+(
+    cd terminator
+    clang terminator.c -arch arm64 -dynamiclib ../libadd/libadd.dylib -o libterminator.dylib
+    install_name_tool -change libadd.dylib @loader_path/../libadd/libadd.dylib libterminator.dylib
+)
+(cd tlv && clang++ tlv.cpp -arch arm64 -std=c++17 -o tlv)
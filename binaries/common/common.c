@@ -0,0 +1,16 @@
+/*
+clang common.c -arch arm64 -fcommon -o common
+*/
+
+// a tentative definition, placed in __DATA,__common
+char buffer[1 << 20];
+
+unsigned long sum(void) {
+  unsigned long total = 0;
+
+  for (unsigned long i = 0; i < sizeof(buffer); i++)
+    total += buffer[i];
+  return (total);
+}
+
+int main(void) { return ((int)sum()); }
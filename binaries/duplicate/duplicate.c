@@ -0,0 +1,12 @@
+/*
+clang duplicate.c -arch arm64 libfoo_b.dylib -o duplicate
+clang duplicate.c -arch arm64 -Wl,-flat_namespace libfoo_b.dylib -o duplicate_flat
+*/
+
+// libfoo_a and libfoo_b both export foo, the image is linked against
+// libfoo_b
+int foo(void);
+
+int call_foo(void) { return (foo()); }
+
+int main(void) { return (call_foo()); }
@@ -0,0 +1,5 @@
+/*
+clang foo_a.c -arch arm64 -dynamiclib -install_name @loader_path/libfoo_a.dylib -o libfoo_a.dylib
+*/
+
+int foo(void) { return (1); }
@@ -0,0 +1,5 @@
+/*
+clang foo_b.c -arch arm64 -dynamiclib -install_name @loader_path/libfoo_b.dylib -o libfoo_b.dylib
+*/
+
+int foo(void) { return (2); }
@@ -0,0 +1,16 @@
+/*
+clang flat.c -arch arm64 -Wl,-flat_namespace -o flat
+*/
+
+#include <stdio.h>
+#include <string.h>
+
+// weak import missing from every image
+extern int flat_missing(void) __attribute__((weak_import));
+
+int main(void) {
+    if (flat_missing)
+        return (flat_missing());
+    printf("%zu\n", strlen("flat"));
+    return (0);
+}
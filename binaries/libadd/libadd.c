@@ -0,0 +1,11 @@
+/*
+clang libadd.c -arch arm64 -dynamiclib -o libadd.dylib
+*/
+
+static int initialized;
+
+__attribute__((constructor)) static void init(void) { initialized = 1; }
+
+int is_initialized(void) { return (initialized); }
+
+int add(int a, int b) { return (a + b); }
@@ -0,0 +1,13 @@
+/*
+clang local_got.c -arch arm64 -Wl,-interposable -o local_got
+*/
+
+#include <string.h>
+
+// with -interposable, the image reaches its own exported functions
+// through symbol pointers too, next to its imports
+int local_fn(void) { return (42); }
+
+int (*get_local_fn(void))(void) { return (local_fn); }
+
+int main(void) { return (local_fn() + (int)strlen("got")); }
@@ -0,0 +1,18 @@
+/*
+clang multicall.c -arch arm64 -o multicall
+*/
+
+#include <string.h>
+
+// dispatches on the name it was started as, like busybox
+int main(int ac, char **av) {
+  (void)ac;
+  const char *name = strrchr(av[0], '/');
+
+  name = name ? name + 1 : av[0];
+  if (strcmp(name, "true") == 0)
+    return (0);
+  if (strcmp(name, "false") == 0)
+    return (1);
+  return (2);
+}
@@ -0,0 +1,14 @@
+/*
+clang mymalloc.c -arch arm64 -dynamiclib -o libmymalloc.dylib
+*/
+
+#include <malloc/malloc.h>
+#include <stddef.h>
+
+// how many allocations went through this malloc
+size_t mymalloc_calls = 0;
+
+void *malloc(size_t size) {
+    mymalloc_calls++;
+    return (malloc_zone_malloc(malloc_default_zone(), size));
+}
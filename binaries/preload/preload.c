@@ -0,0 +1,14 @@
+/*
+clang preload.c -arch arm64 -Wl,-flat_namespace -o preload
+*/
+
+#include <stdlib.h>
+
+int main(void) {
+    char *buffer = malloc(16);
+
+    if (buffer == NULL)
+        return (1);
+    free(buffer);
+    return (0);
+}
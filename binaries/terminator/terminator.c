@@ -0,0 +1,19 @@
+/*
+clang terminator.c -arch arm64 -dynamiclib ../libadd/libadd.dylib -o libterminator.dylib
+install_name_tool -change libadd.dylib @loader_path/../libadd/libadd.dylib libterminator.dylib
+*/
+
+int add(int a, int b);
+
+static void (*hook)(int);
+
+void set_hook(void (*f)(int)) { hook = f; }
+
+// clang emits __cxa_atexit calls for destructors, an explicit
+// __mod_term_func entry is the only way to get one
+static void fini(void) {
+    if (hook)
+        hook(add(1, 2));
+}
+
+__attribute__((used, section("__DATA,__mod_term_func,mod_term_funcs"))) static void (*terminator)(void) = fini;
@@ -0,0 +1,19 @@
+/*
+clang++ tlv.cpp -arch arm64 -std=c++17 -o tlv
+*/
+
+// built on the first access of each thread
+struct Counter {
+    int value;
+
+    Counter() : value(100) {}
+};
+
+thread_local Counter counter;
+thread_local int plain = 7;
+
+extern "C" int counter_next(void) { return (++counter.value); }
+
+extern "C" int plain_next(void) { return (++plain); }
+
+int main(void) { return (counter_next() + plain_next() - 109); }
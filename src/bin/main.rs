@@ -1,27 +1,86 @@
 use std::env;
 use std::fs;
 
-use loader::{Task, jumper::jumper, linker::Linker};
+use loader::{
+    TargetArch, Task, TaskOptions,
+    error::{Error, LoadError},
+    jumper::ExecOptions,
+};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
+    // --dump only describes the image, it never jumps to it
+    let dump = args.iter().skip(1).any(|arg| arg == "--dump");
+
+    // --no-linkedit does not map __LINKEDIT
+    let skip_linkedit = args.iter().skip(1).any(|arg| arg == "--no-linkedit");
+
+    // --arch=NAME loads that slice of a fat binary, for --dump
+    let prefer_arch = args
+        .iter()
+        .skip(1)
+        .find_map(|arg| arg.strip_prefix("--arch="))
+        .map(|name| {
+            TargetArch::from_name(name).unwrap_or_else(|| panic!("unknown architecture {name}"))
+        });
+
+    let args = args
+        .into_iter()
+        .filter(|arg| arg != "--dump" && arg != "--no-linkedit" && !arg.starts_with("--arch="))
+        .collect::<Vec<_>>();
+
     if args.len() < 2 {
         panic!("invalid number of arguments");
     }
 
     let bin = &args[1];
 
-    let data = fs::read(bin).unwrap_or_else(|e| {
-        panic!("failed to read {bin}: {}", e);
-    });
+    let task_options = TaskOptions {
+        skip_linkedit,
+        prefer_arch,
+        image_path: Some(bin.into()),
+        ..Default::default()
+    };
+
+    if let Err(err) = run(bin, dump, task_options) {
+        // the messages do not repeat their causes, print the whole chain
+        eprint!("{bin}: {err}");
+        let mut source = std::error::Error::source(&err);
+        while let Some(err) = source {
+            eprint!(": {err}");
+            source = err.source();
+        }
+        eprintln!();
+        std::process::exit(1);
+    }
+}
+
+fn run(bin: &str, dump: bool, task_options: TaskOptions) -> Result<(), Error> {
+    let data = fs::read(bin).map_err(LoadError::Io)?;
+
+    let mut task = unsafe { Task::try_with_options(data.as_ptr(), data.len(), &task_options) }?;
+
+    if dump {
+        print!("{}", task.report());
+        return Ok(());
+    }
 
-    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    // relocate while writable, then seal and check, jumping with the
+    // wrong protections only crashes later
+    task.prepare()?;
 
-    let mut linker = Linker::new();
-    linker.link_raw(&mut task);
+    // static constructors run before main, as under dyld
+    unsafe { task.run_initializers() };
 
-    task.segments_protect();
+    // start the program with the signals a shell would give it, not
+    // those of the Rust runtime, and never with a w+x page
+    let options = ExecOptions {
+        executable_path: Some(bin.to_string()),
+        reset_signals: true,
+        check_wx: true,
+        ..Default::default()
+    };
 
-    jumper(task.memory, task.entry_point);
+    task.jump(options);
 }
@@ -0,0 +1,130 @@
+//! The GDB JIT interface, as understood by lldb.
+//!
+//! A debugger learns about code it did not see being loaded by setting a
+//! breakpoint on `__jit_debug_register_code` and walking the list held
+//! by `__jit_debug_descriptor` every time it is hit. Each entry points at
+//! an in-memory object file, for us the mapped Mach-O header, which lldb
+//! parses like any other image to get the symbols of the task.
+//!
+//! Only one copy of these two symbols can exist in a process, this
+//! feature can not be combined with another JIT (LLVM's ORC, ...) that
+//! defines them.
+
+use std::{ptr, sync::Mutex};
+
+use crate::Task;
+
+// actions, as defined in the GDB manual, "JIT Interface"
+const JIT_NOACTION: u32 = 0;
+const JIT_REGISTER_FN: u32 = 1;
+const JIT_UNREGISTER_FN: u32 = 2;
+
+/// A `jit_code_entry`, one per registered image
+#[repr(C)]
+pub(crate) struct JitCodeEntry {
+    next: *mut JitCodeEntry,
+    prev: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+/// The `jit_descriptor` the debugger reads
+#[repr(C)]
+pub struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+/// The list of registered images, looked up by name by the debugger.
+#[unsafe(no_mangle)]
+#[allow(non_upper_case_globals)]
+pub static mut __jit_debug_descriptor: JitDescriptor = JitDescriptor {
+    version: 1,
+    action_flag: JIT_NOACTION,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+/// The debugger breaks here to learn that the descriptor changed.
+#[unsafe(no_mangle)]
+#[inline(never)]
+pub extern "C" fn __jit_debug_register_code() {
+    // keep the call from being optimized out
+    std::hint::black_box(());
+}
+
+/// Serializes the updates of the descriptor
+static DESCRIPTOR_LOCK: Mutex<()> = Mutex::new(());
+
+impl Task {
+    /// Registers the loaded image with the debugger, if one is attached
+    /// or attaches later.
+    ///
+    /// The entry points at the mapped Mach-O header, the image is not
+    /// registered if it has no segment mapping it (`__TEXT` always does).
+    /// Symbols are read from the mapped `__LINKEDIT`, which
+    /// [`crate::TaskOptions::skip_linkedit`] leaves out.
+    pub(crate) fn debugger_register(&mut self) {
+        let Some(header) = self.header_address() else {
+            return;
+        };
+
+        let entry = Box::into_raw(Box::new(JitCodeEntry {
+            next: ptr::null_mut(),
+            prev: ptr::null_mut(),
+            symfile_addr: header as *const u8,
+            symfile_size: (self.memory.as_ptr().addr() + self.memory_size - header) as u64,
+        }));
+
+        let _lock = DESCRIPTOR_LOCK
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        unsafe {
+            let descriptor = &raw mut __jit_debug_descriptor;
+
+            (*entry).next = (*descriptor).first_entry;
+            if let Some(first) = (*descriptor).first_entry.as_mut() {
+                first.prev = entry;
+            }
+            (*descriptor).first_entry = entry;
+            (*descriptor).relevant_entry = entry;
+            (*descriptor).action_flag = JIT_REGISTER_FN;
+            __jit_debug_register_code();
+            (*descriptor).action_flag = JIT_NOACTION;
+        }
+
+        self.jit_entry = Some(entry);
+    }
+
+    /// Removes the image from the debugger, before its memory goes away.
+    pub(crate) fn debugger_unregister(&mut self) {
+        let Some(entry) = self.jit_entry.take() else {
+            return;
+        };
+
+        let _lock = DESCRIPTOR_LOCK
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        unsafe {
+            let descriptor = &raw mut __jit_debug_descriptor;
+
+            if let Some(prev) = (*entry).prev.as_mut() {
+                prev.next = (*entry).next;
+            } else {
+                (*descriptor).first_entry = (*entry).next;
+            }
+            if let Some(next) = (*entry).next.as_mut() {
+                next.prev = (*entry).prev;
+            }
+            (*descriptor).relevant_entry = entry;
+            (*descriptor).action_flag = JIT_UNREGISTER_FN;
+            __jit_debug_register_code();
+            (*descriptor).action_flag = JIT_NOACTION;
+            (*descriptor).relevant_entry = ptr::null_mut();
+
+            drop(Box::from_raw(entry));
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Readable symbol names for diagnostics.
+//!
+//! Errors keep the raw symbol name, the demangled form is only meant for
+//! people reading the message. Without the `demangle` feature names are
+//! printed as they are.
+
+/// Returns `symbol` demangled, or unchanged when it is not a C++ or
+/// Swift name or the `demangle` feature is off.
+///
+/// `symbol` is the C name, without the leading '_' the symbol table
+/// adds, so `_Znwm` for `operator new(unsigned long)`.
+pub fn demangle(symbol: &str) -> String {
+    #[cfg(feature = "demangle")]
+    if let Some(demangled) = demangle_cpp(symbol).or_else(|| demangle_swift(symbol)) {
+        return demangled;
+    }
+
+    symbol.to_string()
+}
+
+/// Demangles an Itanium C++ name (`_Z...`).
+#[cfg(feature = "demangle")]
+fn demangle_cpp(symbol: &str) -> Option<String> {
+    if !symbol.starts_with("_Z") {
+        return None;
+    }
+
+    let symbol = cpp_demangle::Symbol::new(symbol).ok()?;
+    symbol
+        .demangle(&cpp_demangle::DemangleOptions::default())
+        .ok()
+}
+
+/// `char *swift_demangle(const char *mangledName, size_t mangledNameLength,
+///                       char *outputBuffer, size_t *outputBufferSize,
+///                       uint32_t flags);`
+#[cfg(feature = "demangle")]
+type SwiftDemangle = extern "C" fn(
+    *const std::ffi::c_char,
+    usize,
+    *mut std::ffi::c_char,
+    *mut usize,
+    u32,
+) -> *mut std::ffi::c_char;
+
+/// Returns `swift_demangle` from the Swift runtime of the host, `None`
+/// if it is not there.
+///
+/// The runtime is opened on the first call only, the handle is never
+/// closed.
+#[cfg(feature = "demangle")]
+fn swift_demangle() -> Option<SwiftDemangle> {
+    static SWIFT_DEMANGLE: std::sync::OnceLock<Option<SwiftDemangle>> = std::sync::OnceLock::new();
+
+    *SWIFT_DEMANGLE.get_or_init(|| {
+        let runtime = unsafe {
+            libc::dlopen(
+                c"/usr/lib/swift/libswiftCore.dylib".as_ptr(),
+                libc::RTLD_LAZY | libc::RTLD_LOCAL,
+            )
+        };
+        if runtime.is_null() {
+            return None;
+        }
+
+        let swift_demangle = unsafe { libc::dlsym(runtime, c"swift_demangle".as_ptr()) };
+        if swift_demangle.is_null() {
+            return None;
+        }
+        Some(unsafe { std::mem::transmute::<*mut libc::c_void, SwiftDemangle>(swift_demangle) })
+    })
+}
+
+/// Demangles a Swift name (`$s...`, `$S...`, `_T0...`) with the Swift
+/// runtime of the host, `None` if it is not loaded or does not know the
+/// name.
+#[cfg(feature = "demangle")]
+fn demangle_swift(symbol: &str) -> Option<String> {
+    use std::ffi::CStr;
+
+    if !["$s", "$S", "_T0"]
+        .iter()
+        .any(|prefix| symbol.starts_with(prefix))
+    {
+        return None;
+    }
+
+    let swift_demangle = swift_demangle()?;
+
+    // with no output buffer, the result is malloc'ed
+    let demangled = swift_demangle(
+        symbol.as_ptr().cast(),
+        symbol.len(),
+        std::ptr::null_mut(),
+        std::ptr::null_mut(),
+        0,
+    );
+    if demangled.is_null() {
+        return None;
+    }
+
+    let name = unsafe { CStr::from_ptr(demangled) }
+        .to_string_lossy()
+        .into_owned();
+    unsafe { libc::free(demangled.cast()) };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::demangle::demangle;
+
+    #[test]
+    pub fn test_demangle() {
+        // C names are left alone, feature or not
+        assert_eq!(demangle("printf"), "printf");
+
+        #[cfg(feature = "demangle")]
+        {
+            assert_eq!(demangle("_Znwm"), "operator new(unsigned long)");
+            assert_eq!(demangle("_ZdlPv"), "operator delete(void*)");
+            assert_eq!(demangle("_ZNSt3__14coutE"), "std::__1::cout");
+            assert_eq!(
+                demangle("$sSS5countSivg"),
+                "Swift.String.count.getter : Swift.Int"
+            );
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Stand-ins for the dyld functions that list the loaded images.
+//!
+//! The loaded image is never registered with dyld, so dyld's image list
+//! only holds the images of the host. A program looking for its own
+//! image in there (to read its own load commands or sections, say)
+//! would never find it. Imports of these functions are bound to
+//! stand-ins instead, which list every loaded task first, in load order,
+//! then the images dyld knows about:
+//!
+//! - `_dyld_image_count`
+//! - `_dyld_get_image_header`
+//! - `_dyld_get_image_name`
+//! - `_dyld_get_image_vmaddr_slide`
+//! - `_NSGetMachExecuteHeader`, the header of the last executable task
+//!   loaded, the host's while there is none
+//!
+//! The rest of dyld still only knows about the host: `dladdr` and
+//! `dyld_all_image_infos` do not see the loaded images, and neither do
+//! the callbacks of `_dyld_register_func_for_add_image`.
+//! `__dyld_private` is left zeroed, only `dyld_stub_binder` reads it,
+//! from stub helpers that are never reached since lazy pointers are
+//! bound without them.
+
+use std::{
+    ffi::CString,
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::{Task, linker::SELF_IMAGE, mach::MH_EXECUTE};
+
+/// A loaded task, as the stand-ins list it
+struct DyldImage {
+    /// address of its mach_header
+    header: usize,
+
+    /// its slide
+    slide: isize,
+
+    /// its path, [`SELF_IMAGE`] when it is not known
+    name: CString,
+
+    /// whether it is an `MH_EXECUTE`
+    executable: bool,
+}
+
+/// The tasks the stand-ins list, in load order
+static DYLD_IMAGES: Mutex<Vec<DyldImage>> = Mutex::new(Vec::new());
+
+unsafe extern "C" {
+    fn _NSGetMachExecuteHeader() -> *const libc::mach_header;
+}
+
+fn dyld_images() -> MutexGuard<'static, Vec<DyldImage>> {
+    DYLD_IMAGES.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Returns the stand-in for the dyld function `symbol`, without the
+/// leading '_', `None` if it has none.
+pub(crate) fn dyld_stand_in(symbol: &str) -> Option<usize> {
+    let stand_in = match symbol {
+        "_dyld_image_count" => dyld_image_count as *const (),
+        "_dyld_get_image_header" => dyld_get_image_header as *const (),
+        "_dyld_get_image_name" => dyld_get_image_name as *const (),
+        "_dyld_get_image_vmaddr_slide" => dyld_get_image_vmaddr_slide as *const (),
+        "_NSGetMachExecuteHeader" => ns_get_mach_execute_header as *const (),
+        _ => return None,
+    };
+    Some(stand_in.addr())
+}
+
+extern "C" fn dyld_image_count() -> u32 {
+    dyld_images().len() as u32 + unsafe { libc::_dyld_image_count() }
+}
+
+extern "C" fn dyld_get_image_header(index: u32) -> *const libc::mach_header {
+    let images = dyld_images();
+    match images.get(index as usize) {
+        Some(image) => image.header as *const libc::mach_header,
+        None => unsafe { libc::_dyld_get_image_header(index - images.len() as u32) },
+    }
+}
+
+extern "C" fn dyld_get_image_name(index: u32) -> *const libc::c_char {
+    let images = dyld_images();
+    match images.get(index as usize) {
+        Some(image) => image.name.as_ptr(),
+        None => unsafe { libc::_dyld_get_image_name(index - images.len() as u32) },
+    }
+}
+
+extern "C" fn dyld_get_image_vmaddr_slide(index: u32) -> libc::intptr_t {
+    let images = dyld_images();
+    match images.get(index as usize) {
+        Some(image) => image.slide,
+        None => unsafe { libc::_dyld_get_image_vmaddr_slide(index - images.len() as u32) },
+    }
+}
+
+extern "C" fn ns_get_mach_execute_header() -> *const libc::mach_header {
+    match dyld_images().iter().rev().find(|image| image.executable) {
+        Some(image) => image.header as *const libc::mach_header,
+        None => unsafe { _NSGetMachExecuteHeader() },
+    }
+}
+
+impl Task {
+    /// Adds the task to the images the stand-ins list, under the path it
+    /// was read from.
+    pub(crate) fn dyld_register(&self, path: Option<&std::path::Path>) {
+        let Some(header) = self.header_address() else {
+            return;
+        };
+
+        let name = path
+            .and_then(|path| CString::new(path.display().to_string()).ok())
+            .unwrap_or_else(|| CString::new(SELF_IMAGE).unwrap());
+
+        dyld_images().push(DyldImage {
+            header,
+            slide: self.slide() as isize,
+            name,
+            executable: self.header.filetype == MH_EXECUTE,
+        });
+    }
+
+    /// Removes the task from the images the stand-ins list.
+    pub(crate) fn dyld_unregister(&self) {
+        let Some(header) = self.header_address() else {
+            return;
+        };
+
+        dyld_images().retain(|image| image.header != header);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dyld::{
+        DyldImage, dyld_get_image_header, dyld_get_image_name, dyld_get_image_vmaddr_slide,
+        dyld_image_count, dyld_images, dyld_stand_in, ns_get_mach_execute_header,
+    };
+
+    #[test]
+    pub fn test_dyld_stand_ins() {
+        assert_eq!(
+            dyld_stand_in("_dyld_image_count"),
+            Some(dyld_image_count as *const () as usize)
+        );
+        assert_eq!(dyld_stand_in("dlopen"), None);
+
+        let host = unsafe { libc::_dyld_image_count() };
+        let host_header = unsafe { libc::_dyld_get_image_header(0) };
+        dyld_images().push(DyldImage {
+            header: 0x10_0000_4000,
+            slide: 0x10_0000_0000,
+            name: c"/tmp/image".into(),
+            executable: true,
+        });
+
+        // the task comes first, then the images of the host
+        assert_eq!(dyld_image_count(), host + 1);
+        assert_eq!(dyld_get_image_header(0).addr(), 0x10_0000_4000);
+        assert_eq!(dyld_get_image_vmaddr_slide(0), 0x10_0000_0000);
+        let name = unsafe { std::ffi::CStr::from_ptr(dyld_get_image_name(0)) };
+        assert_eq!(name, c"/tmp/image");
+        assert_eq!(dyld_get_image_header(1), host_header);
+        assert_eq!(ns_get_mach_execute_header().addr(), 0x10_0000_4000);
+
+        dyld_images().retain(|image| image.header != 0x10_0000_4000);
+        assert_eq!(dyld_image_count(), host);
+    }
+}
@@ -0,0 +1,937 @@
+use std::fmt;
+
+use crate::{demangle::demangle, report::dylib_version_into_string};
+
+/// An error that happened while loading an image into a [`crate::Task`]
+#[derive(Debug)]
+pub enum LoadError {
+    /// Reading the image failed
+    Io(std::io::Error),
+
+    /// The image pointer is null
+    NullImage,
+
+    /// The image is empty
+    EmptyImage,
+
+    /// The image is larger than the loader accepts
+    ImageTooLarge {
+        /// its size
+        len: usize,
+    },
+
+    /// The image is not a Mach-O, or goblin could not parse it
+    Parse(goblin::error::Error),
+
+    /// The name of a dylib load command points outside of the command,
+    /// or is not nul terminated within it.
+    MalformedDylibName {
+        /// offset of the load command within the image
+        command_offset: usize,
+
+        /// offset of the name within the load command
+        name_offset: usize,
+    },
+
+    /// `dlopen` failed on a dylib of the image, and
+    /// [`crate::TaskOptions::on_missing_dylib`] had no substitute
+    DylibNotFound {
+        /// its install name
+        name: String,
+
+        /// the `dlerror()` message
+        error: DlError,
+    },
+
+    /// A dylib is older than the compatibility version the image was
+    /// linked against, with [`crate::TaskOptions::strict_dylib_versions`]
+    DylibTooOld {
+        /// its install name
+        name: String,
+
+        /// the compatibility version the image was linked against
+        required: u32,
+
+        /// the current version of the loaded dylib
+        current: u32,
+    },
+
+    /// The rebase opcode stream is truncated, or rebases outside
+    /// of a segment.
+    MalformedRebase {
+        /// offset of the faulty opcode within the image
+        offset: usize,
+    },
+
+    /// The `LC_DYLD_CHAINED_FIXUPS` data is truncated, uses an unknown
+    /// format, or chains pointers outside of the address space.
+    MalformedChainedFixups {
+        /// offset of the faulty structure within the image
+        offset: usize,
+    },
+
+    /// The indirect symbol table lies outside of the image
+    MalformedIndirectSymbols {
+        /// offset of the table within the image
+        offset: usize,
+    },
+
+    /// The symbol table or the string table lies outside of the image
+    MalformedSymbolTable {
+        /// offset of the table within the image
+        offset: usize,
+    },
+
+    /// The export trie lies outside of the image, or one of its nodes
+    /// is truncated
+    MalformedExportTrie {
+        /// offset of the faulty node within the image
+        offset: usize,
+    },
+
+    /// Allocating the address space or copying a segment into it failed
+    Vm(VmError),
+
+    /// Linking or sealing a [`crate::LoadMode::LinkOnly`] task failed
+    Prepare(ExecError),
+
+    /// A pointer in a read-only segment needs rebasing after the
+    /// segments were sealed
+    TextRelocation {
+        /// the read-only segment
+        segment: String,
+
+        /// offset of the rebase opcode within the image
+        offset: usize,
+    },
+
+    /// The image is an executable linked without `MH_PIE`, which has to
+    /// be loaded at its preferred base
+    NotPositionIndependent,
+
+    /// The image is an executable linked without `MH_PIE`, and the
+    /// address it has to be loaded at is not available
+    BaseAddressUnavailable {
+        /// its preferred base
+        required: u64,
+
+        /// why the range could not be allocated
+        reason: VmError,
+    },
+
+    /// The load commands do not match the count and size the header
+    /// declares for them
+    LoadCommandMismatch {
+        /// `ncmds` of the header
+        ncmds: usize,
+
+        /// `sizeofcmds` of the header
+        sizeofcmds: u32,
+
+        /// the sum of the `cmdsize` of the commands
+        parsed_size: u64,
+    },
+
+    /// The image is byte-swapped (big-endian)
+    UnsupportedEndianness {
+        /// the magic as read in little-endian
+        magic: u32,
+    },
+
+    /// A segment ends past the end of the address space
+    AddressOverflow {
+        /// the segment whose vmaddr + vmsize overflows
+        segment: String,
+    },
+
+    /// A segment read back after being copied differs from the file,
+    /// see [`crate::TaskOptions::verify_copy`]
+    CopyVerificationFailed {
+        /// the segment
+        segment: String,
+    },
+
+    /// A section lies outside of the vm range of its segment
+    SectionOutsideSegment {
+        /// the segment
+        segment: String,
+
+        /// the section
+        section: String,
+    },
+
+    /// A segment asks for an `initprot` its `maxprot` does not allow
+    ProtectionInconsistent {
+        /// the segment
+        segment: String,
+
+        /// its `initprot`
+        initprot: i32,
+
+        /// its `maxprot`
+        maxprot: i32,
+    },
+
+    /// A segment maps more of the file than it has memory for
+    FileSizeExceedsVmSize {
+        /// the segment
+        segment: String,
+
+        /// its `filesize`
+        filesize: u64,
+
+        /// its `vmsize`
+        vmsize: u64,
+    },
+
+    /// A segment maps bytes past the end of the image
+    SegmentOutsideImage {
+        /// the segment
+        segment: String,
+
+        /// its `fileoff`
+        fileoff: u64,
+
+        /// its `filesize`
+        filesize: u64,
+    },
+
+    /// The vm ranges of two segments overlap
+    OverlappingSegments {
+        /// the segment that comes first in memory
+        a: String,
+
+        /// the segment that overlaps it
+        b: String,
+    },
+
+    /// The entry point does not land in executable code
+    BadEntryPoint {
+        /// the entry point, as `LC_MAIN` or `LC_UNIXTHREAD` declare it
+        entry: u64,
+
+        /// the segment it lands in, `None` if it is outside of them all
+        segment: Option<String>,
+    },
+
+    /// The image is 32 bit, or is a fat binary with no 64 bit slice
+    No64BitArch {
+        /// the cputypes of the slices it holds
+        found: Vec<String>,
+    },
+
+    /// The image has no slice of the architecture
+    /// [`crate::TaskOptions::prefer_arch`] asks for, or no arm64 slice
+    ArchNotFound {
+        /// the requested architecture
+        requested: String,
+
+        /// the cputypes of the slices it holds
+        found: Vec<String>,
+    },
+
+    /// The task port of the target process could not be obtained
+    TaskPort {
+        /// the target process
+        pid: libc::pid_t,
+
+        /// the `task_for_pid` failure
+        err: VmError,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(_) => write!(f, "failed to read the image"),
+            LoadError::NullImage => write!(f, "image pointer is null"),
+            LoadError::EmptyImage => write!(f, "image is empty"),
+            LoadError::ImageTooLarge { len } => write!(
+                f,
+                "image is {len:#x} bytes, at most {:#x} are accepted",
+                crate::IMAGE_SIZE_MAX
+            ),
+            LoadError::Parse(_) => write!(f, "failed to parse the image"),
+            LoadError::MalformedDylibName {
+                command_offset,
+                name_offset,
+            } => write!(
+                f,
+                "malformed dylib name at {name_offset:#x} in load command @ {command_offset:#x}"
+            ),
+            LoadError::DylibNotFound { name, .. } => write!(f, "failed to load dylib {name}"),
+            LoadError::DylibTooOld {
+                name,
+                required,
+                current,
+            } => write!(
+                f,
+                "dylib {name} is version {}, the image needs {}",
+                dylib_version_into_string(*current),
+                dylib_version_into_string(*required)
+            ),
+            LoadError::MalformedRebase { offset } => {
+                write!(f, "malformed rebase opcode @ {offset:#x}")
+            }
+            LoadError::MalformedChainedFixups { offset } => {
+                write!(f, "malformed chained fixups @ {offset:#x}")
+            }
+            LoadError::MalformedIndirectSymbols { offset } => {
+                write!(f, "malformed indirect symbol table @ {offset:#x}")
+            }
+            LoadError::MalformedSymbolTable { offset } => {
+                write!(f, "malformed symbol table @ {offset:#x}")
+            }
+            LoadError::MalformedExportTrie { offset } => {
+                write!(f, "malformed export trie @ {offset:#x}")
+            }
+            LoadError::Vm(_) => write!(f, "failed to map the image"),
+            LoadError::Prepare(_) => write!(f, "failed to link the image"),
+            LoadError::TextRelocation { segment, offset } => write!(
+                f,
+                "rebase opcode @ {offset:#x} relocates read-only {segment} after sealing"
+            ),
+            LoadError::NotPositionIndependent => write!(
+                f,
+                "executable is not position independent (no MH_PIE), it can not be slid"
+            ),
+            LoadError::BaseAddressUnavailable { required, .. } => write!(
+                f,
+                "executable is not position independent (no MH_PIE) and {required:#x} is not available"
+            ),
+            LoadError::LoadCommandMismatch {
+                ncmds,
+                sizeofcmds,
+                parsed_size,
+            } => write!(
+                f,
+                "header declares {ncmds} load commands in {sizeofcmds:#x} bytes, \
+                 they take {parsed_size:#x}"
+            ),
+            LoadError::UnsupportedEndianness { magic } => {
+                write!(f, "unsupported byte-swapped image (magic {magic:#010x})")
+            }
+            LoadError::AddressOverflow { segment } => {
+                write!(
+                    f,
+                    "segment {segment} ends past the end of the address space"
+                )
+            }
+            LoadError::CopyVerificationFailed { segment } => {
+                write!(f, "segment {segment} does not match the file once copied")
+            }
+            LoadError::SectionOutsideSegment { segment, section } => {
+                write!(f, "section {segment},{section} lies outside of its segment")
+            }
+            LoadError::ProtectionInconsistent {
+                segment,
+                initprot,
+                maxprot,
+            } => write!(
+                f,
+                "segment {segment} is {} initially but at most {}",
+                crate::vm_prot_into_string(*initprot),
+                crate::vm_prot_into_string(*maxprot)
+            ),
+            LoadError::FileSizeExceedsVmSize {
+                segment,
+                filesize,
+                vmsize,
+            } => write!(
+                f,
+                "segment {segment} maps {filesize:#x} bytes of the file into {vmsize:#x} bytes"
+            ),
+            LoadError::SegmentOutsideImage {
+                segment,
+                fileoff,
+                filesize,
+            } => write!(
+                f,
+                "segment {segment} maps {filesize:#x} bytes at {fileoff:#x}, past the end of the image"
+            ),
+            LoadError::OverlappingSegments { a, b } => {
+                write!(f, "segments {a} and {b} overlap")
+            }
+            LoadError::BadEntryPoint {
+                entry,
+                segment: Some(segment),
+            } => write!(
+                f,
+                "entry point {entry:#x} is in a non executable segment: {segment}"
+            ),
+            LoadError::BadEntryPoint {
+                entry,
+                segment: None,
+            } => write!(f, "entry point {entry:#x} is outside of any segment"),
+            LoadError::No64BitArch { found } => {
+                write!(f, "image has no 64 bit slice, only {}", found.join(", "))
+            }
+            LoadError::ArchNotFound { requested, found } => write!(
+                f,
+                "image has no {requested} slice, only {}",
+                found.join(", ")
+            ),
+            LoadError::TaskPort { pid, .. } => write!(
+                f,
+                "no task port for pid {pid}, this needs root or the \
+                 com.apple.security.cs.debugger entitlement"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(err) => Some(err),
+            LoadError::Parse(err) => Some(err),
+            LoadError::DylibNotFound { error, .. } => Some(error),
+            LoadError::Vm(err) => Some(err),
+            LoadError::Prepare(err) => Some(err),
+            LoadError::BaseAddressUnavailable { reason, .. } => Some(reason),
+            LoadError::TaskPort { err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// A `dlopen` or `dlsym` failure, the `dlerror()` message
+#[derive(Debug, Clone)]
+pub struct DlError(pub String);
+
+impl fmt::Display for DlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for DlError {}
+
+/// An error that happened while binding the imports of a [`crate::Task`]
+#[derive(Debug)]
+pub enum LinkError {
+    /// An indirect symbol table entry does not name a symbol
+    BadIndirectIndex {
+        /// the symbol pointer section holding the entry
+        section: String,
+
+        /// index of the entry within the indirect symbol table
+        index: usize,
+    },
+
+    /// A symbol pointer section does not hold a whole number of pointers
+    MisalignedSymbolPointers {
+        /// the symbol pointer section
+        section: String,
+
+        /// its size
+        size: usize,
+    },
+
+    /// A symbol pointer section has more entries than the indirect
+    /// symbol table has left
+    IndirectSymbolsOverrun {
+        /// the symbol pointer section
+        section: String,
+
+        /// index of its first entry in the indirect symbol table
+        first: usize,
+
+        /// number of entries in the section
+        count: usize,
+    },
+
+    /// The name of a symbol lies outside of the string table
+    BadStringIndex {
+        /// index of the symbol in the symbol table
+        index: usize,
+
+        /// its `n_strx`
+        n_strx: u32,
+    },
+
+    /// A flat namespace import is defined by several loaded images,
+    /// with [`crate::FlatLookup::Strict`]
+    AmbiguousSymbol {
+        /// the imported symbol
+        symbol: String,
+
+        /// the images defining it
+        images: Vec<String>,
+    },
+
+    /// An import refers to a dylib that was not loaded
+    BadOrdinal {
+        /// the imported symbol
+        symbol: String,
+
+        /// its library ordinal
+        ordinal: u16,
+    },
+
+    /// The image does not import the symbol
+    UnknownImport {
+        /// the symbol that was looked for
+        symbol: String,
+    },
+
+    /// Changing the protection of a symbol pointer failed
+    Vm(VmError),
+
+    /// `dlsym` did not find an import
+    UnresolvedSymbol {
+        /// the imported symbol
+        symbol: String,
+
+        /// the dylib it was looked up in
+        dylib: String,
+
+        /// why it was not found
+        error: DlError,
+    },
+
+    /// Creating the pthread key of the thread local variables failed
+    ThreadLocalKey(std::io::Error),
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::BadIndirectIndex { section, index } => {
+                write!(f, "bad indirect symbol #{index} in {section}")
+            }
+            LinkError::MisalignedSymbolPointers { section, size } => {
+                write!(
+                    f,
+                    "{section} size {size:#x} is not a multiple of the pointer size"
+                )
+            }
+            LinkError::IndirectSymbolsOverrun {
+                section,
+                first,
+                count,
+            } => write!(
+                f,
+                "{section} entries {first}..{} run past the indirect symbol table",
+                first + count
+            ),
+            LinkError::BadStringIndex { index, n_strx } => {
+                write!(
+                    f,
+                    "symbol #{index} has its name @ {n_strx:#x}, outside of the string table"
+                )
+            }
+            LinkError::AmbiguousSymbol { symbol, images } => write!(
+                f,
+                "{} is defined by several images: {}",
+                demangle(symbol),
+                images.join(", ")
+            ),
+            LinkError::BadOrdinal { symbol, ordinal } => {
+                write!(
+                    f,
+                    "{} refers to unknown library ordinal {ordinal}",
+                    demangle(symbol)
+                )
+            }
+            LinkError::UnknownImport { symbol } => {
+                write!(f, "{} is not imported", demangle(symbol))
+            }
+            LinkError::Vm(_) => write!(f, "failed to rewrite a symbol pointer"),
+            LinkError::UnresolvedSymbol { symbol, dylib, .. } => {
+                write!(f, "symbol {} not found in {dylib}", demangle(symbol))
+            }
+            LinkError::ThreadLocalKey(_) => write!(f, "failed to create the thread local key"),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LinkError::Vm(err) => Some(err),
+            LinkError::UnresolvedSymbol { error, .. } => Some(error),
+            LinkError::ThreadLocalKey(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// An error that happened while running a loaded [`crate::Task`]
+#[derive(Debug)]
+pub enum ExecError {
+    /// `fork` failed
+    Fork(std::io::Error),
+
+    /// `waitpid` failed
+    Wait(std::io::Error),
+
+    /// Spawning the thread the program runs on failed
+    Thread(std::io::Error),
+
+    /// Making the segments writable for binding failed
+    Unprotect(VmError),
+
+    /// Binding the imports failed
+    Link(LinkError),
+
+    /// Sealing the segments to their `initprot` failed
+    Protect(VmError),
+
+    /// A segment does not have the protection it was sealed with
+    ProtectionCheck(VmError),
+
+    /// A segment is writable and executable, see
+    /// [`crate::jumper::ExecOptions::check_wx`]
+    Security(SecurityError),
+
+    /// The program ran past its timeout and was killed
+    Timeout,
+
+    /// The program was terminated by a signal
+    Signaled {
+        /// the signal number
+        signal: i32,
+    },
+
+    /// The program crashed
+    Crashed {
+        /// the signal number
+        signal: i32,
+
+        /// the pc of the faulting instruction
+        pc: u64,
+
+        /// the faulting address, for memory faults
+        address: u64,
+
+        /// the symbol of the loaded image the pc falls in, as `name+offset`
+        symbol: Option<String>,
+    },
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecError::Fork(_) => write!(f, "failed to fork"),
+            ExecError::Wait(_) => write!(f, "failed to wait for the child"),
+            ExecError::Thread(_) => write!(f, "failed to spawn the thread"),
+            ExecError::Unprotect(_) => write!(f, "failed to unprotect the segments"),
+            ExecError::Link(_) => write!(f, "failed to link"),
+            ExecError::Protect(_) => write!(f, "failed to protect the segments"),
+            ExecError::ProtectionCheck(_) => write!(f, "protection check failed"),
+            ExecError::Security(_) => write!(f, "refusing to run"),
+            ExecError::Timeout => write!(f, "the program timed out"),
+            ExecError::Signaled { signal } => {
+                write!(f, "the program was killed by signal {signal}")
+            }
+            ExecError::Crashed {
+                signal,
+                pc,
+                address,
+                symbol,
+            } => {
+                write!(
+                    f,
+                    "the program crashed with signal {signal} at pc {pc:#x} (address {address:#x})"
+                )?;
+                if let Some(symbol) = symbol {
+                    write!(f, " in {symbol}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExecError::Fork(err) | ExecError::Wait(err) | ExecError::Thread(err) => Some(err),
+            ExecError::Unprotect(err)
+            | ExecError::Protect(err)
+            | ExecError::ProtectionCheck(err) => Some(err),
+            ExecError::Link(err) => Some(err),
+            ExecError::Security(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// An error returned by the Mach VM layer
+#[derive(Debug)]
+pub enum VmError {
+    /// A `mach_vm_*` call failed
+    Kern {
+        /// the name of the failing call
+        call: &'static str,
+
+        /// the kern_return_t it returned
+        kern_return: i32,
+    },
+
+    /// A fixed allocation overlaps an existing mapping
+    RegionOccupied {
+        /// start of the requested range
+        address: u64,
+
+        /// size of the requested range
+        size: u64,
+
+        /// start of the conflicting mapping
+        region_start: u64,
+
+        /// end of the conflicting mapping
+        region_end: u64,
+    },
+
+    /// A region does not have the protection it was given
+    ProtectionMismatch {
+        /// address that was checked
+        address: u64,
+
+        /// the protection it should have
+        expected: i32,
+
+        /// the protection the kernel reports
+        actual: i32,
+    },
+
+    /// Nothing is mapped at the address
+    AddressUnmapped {
+        /// the address that was looked up
+        address: u64,
+    },
+
+    /// No mapped segment of the task has this name
+    UnknownSegment {
+        /// the name that was looked up
+        name: String,
+    },
+
+    /// The bytes written to a segment do not fit in it
+    SegmentOverflow {
+        /// the segment
+        segment: String,
+
+        /// size of the bytes
+        size: u64,
+
+        /// size of the segment in memory
+        vm_size: u64,
+    },
+
+    /// A copy hit pages that are not writable, they were protected
+    /// before being written to
+    WriteToProtected {
+        /// start of the destination
+        address: u64,
+
+        /// size of the copy
+        size: u64,
+    },
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::Kern { call, kern_return } => {
+                write!(
+                    f,
+                    "{call} failed: {}",
+                    crate::mach::kr_error_string(*kern_return)
+                )
+            }
+            VmError::RegionOccupied {
+                address,
+                size,
+                region_start,
+                region_end,
+            } => write!(
+                f,
+                "{address:#x}-{:#x} overlaps the existing mapping {region_start:#x}-{region_end:#x}",
+                address + size
+            ),
+            VmError::ProtectionMismatch {
+                address,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{address:#x} is {} instead of {}",
+                crate::vm_prot_into_string(*actual),
+                crate::vm_prot_into_string(*expected)
+            ),
+            VmError::AddressUnmapped { address } => write!(f, "{address:#x} is not mapped"),
+            VmError::UnknownSegment { name } => write!(f, "no segment named {name}"),
+            VmError::SegmentOverflow {
+                segment,
+                size,
+                vm_size,
+            } => write!(
+                f,
+                "{size:#x} bytes do not fit in {segment}, which is {vm_size:#x} bytes"
+            ),
+            VmError::WriteToProtected { address, size } => write!(
+                f,
+                "{address:#x}-{:#x} is not writable, it was protected before being copied to",
+                address + size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+/// A protection the loaded image should never have
+#[derive(Debug)]
+pub enum SecurityError {
+    /// Part of a segment is both writable and executable
+    WritableAndExecutable {
+        /// the segment
+        segment: String,
+
+        /// start of the offending region
+        address: u64,
+
+        /// the protection the kernel reports
+        protection: i32,
+    },
+
+    /// Looking up the protection of a segment failed
+    Vm(VmError),
+}
+
+impl fmt::Display for SecurityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecurityError::WritableAndExecutable {
+                segment,
+                address,
+                protection,
+            } => write!(
+                f,
+                "{segment} is {} @ {address:#x}, writable and executable",
+                crate::vm_prot_into_string(*protection)
+            ),
+            SecurityError::Vm(_) => write!(f, "failed to look up the protection of a segment"),
+        }
+    }
+}
+
+impl std::error::Error for SecurityError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SecurityError::Vm(err) => Some(err),
+            SecurityError::WritableAndExecutable { .. } => None,
+        }
+    }
+}
+
+/// Why the loader aborts the process, see [`crate::set_abort_hook`]
+#[derive(Debug)]
+pub enum AbortReason {
+    /// A lazy symbol pointer was called and its dylib does not define
+    /// its symbol
+    LazySymbolNotFound {
+        /// the symbol, without the leading '_'
+        symbol: String,
+    },
+
+    /// A lazy symbol pointer of a task that was shut down was called
+    LazySlotUnloaded,
+
+    /// A thread local variable of a task that was shut down was
+    /// accessed
+    TlvUnloaded,
+
+    /// The thread local storage of a thread could not be allocated
+    TlvAllocationFailed {
+        /// the size of the storage
+        size: usize,
+    },
+}
+
+impl fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbortReason::LazySymbolNotFound { symbol } => {
+                write!(f, "lazy symbol not found: {symbol}")
+            }
+            AbortReason::LazySlotUnloaded => write!(f, "lazy symbol pointer of an unloaded task"),
+            AbortReason::TlvUnloaded => write!(f, "thread local variable of an unloaded task"),
+            AbortReason::TlvAllocationFailed { size } => {
+                write!(f, "failed to allocate {size} bytes of thread local storage")
+            }
+        }
+    }
+}
+
+/// Any error of the loader, for callers that do not tell them apart
+///
+/// It displays as the error it holds, and its [`source`] is the source
+/// of that error.
+///
+/// [`source`]: std::error::Error::source
+#[derive(Debug)]
+pub enum Error {
+    Load(LoadError),
+    Link(LinkError),
+    Exec(ExecError),
+    Vm(VmError),
+    Security(SecurityError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Load(err) => err.fmt(f),
+            Error::Link(err) => err.fmt(f),
+            Error::Exec(err) => err.fmt(f),
+            Error::Vm(err) => err.fmt(f),
+            Error::Security(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Load(err) => err.source(),
+            Error::Link(err) => err.source(),
+            Error::Exec(err) => err.source(),
+            Error::Vm(err) => err.source(),
+            Error::Security(err) => err.source(),
+        }
+    }
+}
+
+impl From<LoadError> for Error {
+    fn from(err: LoadError) -> Self {
+        Error::Load(err)
+    }
+}
+
+impl From<LinkError> for Error {
+    fn from(err: LinkError) -> Self {
+        Error::Link(err)
+    }
+}
+
+impl From<ExecError> for Error {
+    fn from(err: ExecError) -> Self {
+        Error::Exec(err)
+    }
+}
+
+impl From<VmError> for Error {
+    fn from(err: VmError) -> Self {
+        Error::Vm(err)
+    }
+}
+
+impl From<SecurityError> for Error {
+    fn from(err: SecurityError) -> Self {
+        Error::Security(err)
+    }
+}
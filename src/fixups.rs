@@ -0,0 +1,709 @@
+use goblin::mach::{MachO, load_command::CommandVariant};
+
+use crate::{
+    Task,
+    error::LoadError,
+    pac::{PacKey, blend_discriminator},
+};
+
+// The chained fixups formats as defined in "mach-o/fixup-chains.h"
+//
+// `LC_DYLD_CHAINED_FIXUPS` points at a `dyld_chained_fixups_header` in
+// `__LINKEDIT`. It is followed by the starts of the chains in each
+// segment, the imports table and the pool of the import names. Each
+// pointer that needs fixing up holds either a rebase target or an import
+// index, along with the distance to the next one on the same page.
+const DYLD_CHAINED_IMPORT: u32 = 1;
+const DYLD_CHAINED_IMPORT_ADDEND: u32 = 2;
+const DYLD_CHAINED_IMPORT_ADDEND64: u32 = 3;
+
+const DYLD_CHAINED_PTR_ARM64E: u16 = 1;
+const DYLD_CHAINED_PTR_64: u16 = 2;
+const DYLD_CHAINED_PTR_64_OFFSET: u16 = 6;
+const DYLD_CHAINED_PTR_ARM64E_USERLAND: u16 = 9;
+const DYLD_CHAINED_PTR_ARM64E_USERLAND24: u16 = 12;
+
+/// a page with no fixups
+const DYLD_CHAINED_PTR_START_NONE: u16 = 0xffff;
+
+/// An entry of the chained fixups imports table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainedImport {
+    /// the library ordinal, with the special ordinals (0 for the image
+    /// itself, -1 the main executable, -2 flat lookup, -3 weak lookup)
+    /// sign extended
+    pub lib_ordinal: i32,
+
+    /// the import may be missing
+    pub weak: bool,
+
+    /// name, with the leading '_'
+    pub name: String,
+
+    /// added to the address the import resolves to
+    pub addend: i64,
+}
+
+/// A pointer to bind, found while walking the chains
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ChainedBind {
+    /// offset of the pointer within the tasks memory
+    pub offset: usize,
+
+    /// index in the imports table
+    pub import: usize,
+
+    /// added to the address the import resolves to, on top of the
+    /// import's own addend
+    pub addend: i64,
+
+    /// how the pointer is signed, for an authenticated arm64e bind
+    pub auth: Option<ChainedAuth>,
+}
+
+/// How an authenticated arm64e chained pointer is signed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ChainedAuth {
+    pub key: PacKey,
+
+    /// the address of the pointer is blended into the discriminator
+    pub address_diversity: bool,
+
+    /// the constant part of the discriminator
+    pub diversity: u16,
+}
+
+impl ChainedAuth {
+    /// Decodes the `diversity`, `addrDiv` and `key` fields shared by
+    /// `dyld_chained_ptr_arm64e_auth_rebase` and `_auth_bind`.
+    fn decode(raw: u64) -> Self {
+        ChainedAuth {
+            key: match bits(raw, 49, 2) {
+                0 => PacKey::InstructionA,
+                1 => PacKey::InstructionB,
+                2 => PacKey::DataA,
+                _ => PacKey::DataB,
+            },
+            address_diversity: bits(raw, 48, 1) == 1,
+            diversity: bits(raw, 32, 16) as u16,
+        }
+    }
+
+    /// Signs `value`, to be stored at `address`, the way dyld does.
+    pub fn sign(self, value: u64, address: usize) -> u64 {
+        let discriminator = match self.address_diversity {
+            true => blend_discriminator(address as u64, self.diversity),
+            false => self.diversity as u64,
+        };
+        self.key.sign(value, discriminator)
+    }
+}
+
+/// What a chained pointer holds once decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChainedPointer {
+    /// points into the image: `target` is a vmaddr, or an offset from
+    /// the preferred base if `offset` is set
+    Rebase {
+        target: u64,
+        high8: u8,
+        offset: bool,
+        auth: Option<ChainedAuth>,
+    },
+
+    /// binds the import `import`
+    Bind {
+        import: usize,
+        addend: i64,
+        auth: Option<ChainedAuth>,
+    },
+}
+
+/// Sign extends the low `bits` bits of `value`.
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+/// Returns the bits `start..start + len` of `value`.
+fn bits(value: u64, start: u32, len: u32) -> u64 {
+    (value >> start) & ((1 << len) - 1)
+}
+
+/// Returns the distance between two pointers of a chain, in bytes.
+fn pointer_stride(format: u16) -> Option<u64> {
+    match format {
+        DYLD_CHAINED_PTR_64 | DYLD_CHAINED_PTR_64_OFFSET => Some(4),
+        DYLD_CHAINED_PTR_ARM64E
+        | DYLD_CHAINED_PTR_ARM64E_USERLAND
+        | DYLD_CHAINED_PTR_ARM64E_USERLAND24 => Some(8),
+        _ => None,
+    }
+}
+
+/// Decodes the chained pointer `raw` of the format `format` and returns
+/// it with the stride count to the next pointer, 0 at the end of the
+/// chain.
+///
+/// Authenticated arm64e pointers come with how to sign them once
+/// fixed up.
+pub(crate) fn chained_pointer_decode(format: u16, raw: u64) -> Option<(ChainedPointer, u64)> {
+    let pointer = match format {
+        DYLD_CHAINED_PTR_64 | DYLD_CHAINED_PTR_64_OFFSET => {
+            let next = bits(raw, 51, 12);
+            let pointer = match bits(raw, 63, 1) {
+                // dyld_chained_ptr_64_bind
+                1 => ChainedPointer::Bind {
+                    import: bits(raw, 0, 24) as usize,
+                    addend: bits(raw, 24, 8) as i64,
+                    auth: None,
+                },
+                // dyld_chained_ptr_64_rebase
+                _ => ChainedPointer::Rebase {
+                    target: bits(raw, 0, 36),
+                    high8: bits(raw, 36, 8) as u8,
+                    offset: format == DYLD_CHAINED_PTR_64_OFFSET,
+                    auth: None,
+                },
+            };
+            (pointer, next)
+        }
+        DYLD_CHAINED_PTR_ARM64E
+        | DYLD_CHAINED_PTR_ARM64E_USERLAND
+        | DYLD_CHAINED_PTR_ARM64E_USERLAND24 => {
+            let next = bits(raw, 51, 11);
+            let ordinal_bits = match format {
+                DYLD_CHAINED_PTR_ARM64E_USERLAND24 => 24,
+                _ => 16,
+            };
+            let auth = bits(raw, 63, 1) == 1;
+            let pointer = match (auth, bits(raw, 62, 1)) {
+                // dyld_chained_ptr_arm64e_auth_bind
+                (true, 1) => ChainedPointer::Bind {
+                    import: bits(raw, 0, ordinal_bits) as usize,
+                    addend: 0,
+                    auth: Some(ChainedAuth::decode(raw)),
+                },
+                // dyld_chained_ptr_arm64e_bind
+                (false, 1) => ChainedPointer::Bind {
+                    import: bits(raw, 0, ordinal_bits) as usize,
+                    addend: sign_extend(bits(raw, 32, 19), 19),
+                    auth: None,
+                },
+                // dyld_chained_ptr_arm64e_auth_rebase, always an offset
+                (true, _) => ChainedPointer::Rebase {
+                    target: bits(raw, 0, 32),
+                    high8: 0,
+                    offset: true,
+                    auth: Some(ChainedAuth::decode(raw)),
+                },
+                // dyld_chained_ptr_arm64e_rebase
+                (false, _) => ChainedPointer::Rebase {
+                    target: bits(raw, 0, 43),
+                    high8: bits(raw, 43, 8) as u8,
+                    offset: format != DYLD_CHAINED_PTR_ARM64E,
+                    auth: None,
+                },
+            };
+            (pointer, next)
+        }
+        _ => return None,
+    };
+    Some(pointer)
+}
+
+/// Walks the chain starting `start` bytes into `page` and returns the
+/// offset within the page of each of its pointers, with what it holds.
+///
+/// `page` is one `page_size` page of a `dyld_chained_starts_in_segment`,
+/// chains never leave their page. `next` counts strides of the format,
+/// 4 bytes for `DYLD_CHAINED_PTR_64` and 8 for `DYLD_CHAINED_PTR_ARM64E`,
+/// and 0 ends the chain. `None` if the format is unknown, or the chain
+/// runs past the end of the page.
+pub(crate) fn chained_page_walk(
+    format: u16,
+    page: &[u8],
+    start: u16,
+) -> Option<Vec<(usize, ChainedPointer)>> {
+    let stride = pointer_stride(format)? as usize;
+
+    let mut chain = Vec::new();
+    let mut offset = start as usize;
+    loop {
+        let (pointer, next) = chained_pointer_decode(format, read_u64(page, offset)?)?;
+        chain.push((offset, pointer));
+
+        if next == 0 {
+            return Some(chain);
+        }
+        offset = offset.checked_add(next as usize * stride)?;
+    }
+}
+
+/// Reads a little-endian u32 at `pos` in `data`.
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(*data.get(pos..)?.first_chunk()?))
+}
+
+/// Reads a little-endian u16 at `pos` in `data`.
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(*data.get(pos..)?.first_chunk()?))
+}
+
+/// Reads a little-endian u64 at `pos` in `data`.
+fn read_u64(data: &[u8], pos: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(*data.get(pos..)?.first_chunk()?))
+}
+
+/// Reads the nul terminated name at `pos` in `data`.
+fn read_name(data: &[u8], pos: usize) -> Option<String> {
+    let name = data.get(pos..)?;
+    let len = name.iter().position(|&byte| byte == 0)?;
+    String::from_utf8(name[..len].to_vec()).ok()
+}
+
+/// Decodes the imports table of a `dyld_chained_fixups_header`.
+///
+/// `fixups` is the whole blob `LC_DYLD_CHAINED_FIXUPS` points at. All
+/// three import formats are supported, their names are read from the
+/// pool at `symbols_offset`.
+pub fn chained_imports(fixups: &[u8]) -> Option<Vec<ChainedImport>> {
+    let imports_offset = read_u32(fixups, 8)? as usize;
+    let symbols_offset = read_u32(fixups, 12)? as usize;
+    let imports_count = read_u32(fixups, 16)? as usize;
+    let imports_format = read_u32(fixups, 20)?;
+    let symbols_format = read_u32(fixups, 24)?;
+
+    // the pool may be zlib compressed, which dyld never supported either
+    if symbols_format != 0 {
+        return None;
+    }
+
+    let name = |offset: u64| read_name(fixups, symbols_offset.checked_add(offset as usize)?);
+
+    (0..imports_count)
+        .map(|index| match imports_format {
+            // dyld_chained_import
+            DYLD_CHAINED_IMPORT => {
+                let raw = read_u32(fixups, imports_offset + index * 4)? as u64;
+                Some(ChainedImport {
+                    lib_ordinal: sign_extend(bits(raw, 0, 8), 8) as i32,
+                    weak: bits(raw, 8, 1) == 1,
+                    name: name(bits(raw, 9, 23))?,
+                    addend: 0,
+                })
+            }
+            // dyld_chained_import_addend
+            DYLD_CHAINED_IMPORT_ADDEND => {
+                let pos = imports_offset + index * 8;
+                let raw = read_u32(fixups, pos)? as u64;
+                Some(ChainedImport {
+                    lib_ordinal: sign_extend(bits(raw, 0, 8), 8) as i32,
+                    weak: bits(raw, 8, 1) == 1,
+                    name: name(bits(raw, 9, 23))?,
+                    addend: read_u32(fixups, pos + 4)? as i32 as i64,
+                })
+            }
+            // dyld_chained_import_addend64
+            DYLD_CHAINED_IMPORT_ADDEND64 => {
+                let pos = imports_offset + index * 16;
+                let raw = read_u64(fixups, pos)?;
+                Some(ChainedImport {
+                    lib_ordinal: sign_extend(bits(raw, 0, 16), 16) as i32,
+                    weak: bits(raw, 16, 1) == 1,
+                    name: name(bits(raw, 32, 32))?,
+                    addend: read_u64(fixups, pos + 8)? as i64,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+impl Task {
+    /// Walks the `LC_DYLD_CHAINED_FIXUPS` chains of the image.
+    ///
+    /// Rebases are applied right away, like [`Task::rebase`] does for
+    /// the rebase opcodes, and signed if they are authenticated. Binds
+    /// are recorded with the imports table, the chains are gone once
+    /// walked, and are bound with the other imports by
+    /// [`Task::resolve_all_imports`].
+    pub(crate) fn chained_fixups_init(
+        &mut self,
+        macho: &MachO,
+        image: &[u8],
+    ) -> Result<(), LoadError> {
+        let Some((dataoff, datasize)) =
+            macho.load_commands.iter().find_map(|lc| match &lc.command {
+                CommandVariant::DyldChainedFixups(fixups) => {
+                    Some((fixups.dataoff as usize, fixups.datasize as usize))
+                }
+                _ => None,
+            })
+        else {
+            return Ok(());
+        };
+
+        let malformed = |pos: usize| LoadError::MalformedChainedFixups {
+            offset: dataoff + pos,
+        };
+
+        let fixups = dataoff
+            .checked_add(datasize)
+            .and_then(|end| image.get(dataoff..end))
+            .ok_or(malformed(0))?;
+
+        self.chained_imports = chained_imports(fixups).ok_or(malformed(0))?;
+
+        let starts = read_u32(fixups, 4).ok_or(malformed(4))? as usize;
+        let seg_count = read_u32(fixups, starts).ok_or(malformed(starts))? as usize;
+
+        let slide = self.slide() as u64;
+        let base = self.preferred_base;
+
+        for segment_index in 0..seg_count {
+            let pos = starts + 4 + segment_index * 4;
+            let info = read_u32(fixups, pos).ok_or(malformed(pos))? as usize;
+            if info == 0 {
+                continue;
+            }
+
+            // dyld_chained_starts_in_segment
+            let seg = starts + info;
+            let page_size = read_u16(fixups, seg + 4).ok_or(malformed(seg))? as u64;
+            let format = read_u16(fixups, seg + 6).ok_or(malformed(seg))?;
+            let segment_offset = read_u64(fixups, seg + 8).ok_or(malformed(seg))?;
+            let page_count = read_u16(fixups, seg + 20).ok_or(malformed(seg))? as u64;
+            if pointer_stride(format).is_none() {
+                return Err(malformed(seg));
+            }
+
+            for page in 0..page_count {
+                let pos = seg + 22 + page as usize * 2;
+                let start = read_u16(fixups, pos).ok_or(malformed(pos))?;
+                if start == DYLD_CHAINED_PTR_START_NONE {
+                    continue;
+                }
+
+                let page_start = base
+                    .checked_add(segment_offset)
+                    .and_then(|start| start.checked_add(page * page_size))
+                    .and_then(|start| usize::try_from(start).ok())
+                    .filter(|start| *start < self.memory_size)
+                    .ok_or(malformed(pos))?;
+                let page_end = page_start
+                    .saturating_add(page_size as usize)
+                    .min(self.memory_size);
+
+                // read the whole chain before fixing up any of it, the
+                // next fields are overwritten
+                let chain = {
+                    let page = unsafe {
+                        core::slice::from_raw_parts(
+                            self.memory.add(page_start).as_ptr(),
+                            page_end - page_start,
+                        )
+                    };
+                    chained_page_walk(format, page, start).ok_or(malformed(pos))?
+                };
+
+                for (offset, pointer) in chain {
+                    let offset = page_start + offset;
+                    let slot = unsafe { self.memory.add(offset).as_ptr() as *mut u64 };
+
+                    match pointer {
+                        ChainedPointer::Rebase {
+                            target,
+                            high8,
+                            offset: from_base,
+                            auth,
+                        } => {
+                            let target = if from_base { base + target } else { target };
+                            let value = match auth {
+                                Some(auth) => auth.sign(target + slide, slot.addr()),
+                                None => (target + slide) | (high8 as u64) << 56,
+                            };
+                            unsafe { slot.write_unaligned(value) };
+                        }
+                        ChainedPointer::Bind {
+                            import,
+                            addend,
+                            auth,
+                        } => {
+                            if import >= self.chained_imports.len() {
+                                return Err(malformed(seg));
+                            }
+                            // cleared until bound, a stray call faults on null
+                            unsafe { slot.write_unaligned(0) };
+                            self.chained_binds.push(ChainedBind {
+                                offset,
+                                import,
+                                addend,
+                                auth,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        fixups::{
+            ChainedAuth, ChainedImport, ChainedPointer, chained_imports, chained_page_walk,
+            chained_pointer_decode,
+        },
+        pac::{PacKey, blend_discriminator},
+    };
+
+    /// Builds a fixups header with the given imports table and pool.
+    fn fixups_blob(format: u32, count: u32, imports: &[u8], pool: &[u8]) -> Vec<u8> {
+        let imports_offset = 28;
+        let symbols_offset = imports_offset + imports.len() as u32;
+
+        let mut blob = Vec::new();
+        for word in [0, 0, imports_offset, symbols_offset, count, format, 0] {
+            blob.extend_from_slice(&u32::to_le_bytes(word));
+        }
+        blob.extend_from_slice(imports);
+        blob.extend_from_slice(pool);
+        blob
+    }
+
+    #[test]
+    pub fn test_chained_import() {
+        let pool = b"\0_printf\0_environ\0";
+        // ordinal 1, name 1; ordinal -2 (flat), weak, name 9
+        let imports = [1 | (1 << 9), 0xfe | (1 << 8) | (9 << 9)]
+            .iter()
+            .flat_map(|raw: &u32| raw.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        let imports = chained_imports(&fixups_blob(1, 2, &imports, pool)).unwrap();
+        assert_eq!(
+            imports,
+            [
+                ChainedImport {
+                    lib_ordinal: 1,
+                    weak: false,
+                    name: "_printf".to_string(),
+                    addend: 0,
+                },
+                ChainedImport {
+                    lib_ordinal: -2,
+                    weak: true,
+                    name: "_environ".to_string(),
+                    addend: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_chained_import_addend() {
+        let pool = b"\0_table\0";
+        let mut imports = Vec::new();
+        imports.extend_from_slice(&(2u32 | (1 << 9)).to_le_bytes());
+        imports.extend_from_slice(&(-16i32).to_le_bytes());
+
+        let imports = chained_imports(&fixups_blob(2, 1, &imports, pool)).unwrap();
+        assert_eq!(imports[0].lib_ordinal, 2);
+        assert_eq!(imports[0].name, "_table");
+        assert_eq!(imports[0].addend, -16);
+    }
+
+    #[test]
+    pub fn test_chained_import_addend64() {
+        let pool = b"\0_big\0";
+        let mut raw = Vec::new();
+        // ordinal 0x102, weak, name 1
+        raw.extend_from_slice(&(0x102u64 | (1 << 16) | (1 << 32)).to_le_bytes());
+        raw.extend_from_slice(&0x1_0000_0000u64.to_le_bytes());
+
+        let imports = chained_imports(&fixups_blob(3, 1, &raw, pool)).unwrap();
+        assert_eq!(imports[0].lib_ordinal, 0x102);
+        assert!(imports[0].weak);
+        assert_eq!(imports[0].name, "_big");
+        assert_eq!(imports[0].addend, 0x1_0000_0000);
+
+        // an unknown format is rejected
+        assert_eq!(chained_imports(&fixups_blob(4, 1, &raw, pool)), None);
+    }
+
+    #[test]
+    pub fn test_chained_pointer_decode() {
+        // DYLD_CHAINED_PTR_64_OFFSET rebase to 0x3f40, next 2
+        assert_eq!(
+            chained_pointer_decode(6, 0x3f40 | (2 << 51)),
+            Some((
+                ChainedPointer::Rebase {
+                    target: 0x3f40,
+                    high8: 0,
+                    offset: true,
+                    auth: None
+                },
+                2
+            ))
+        );
+
+        // DYLD_CHAINED_PTR_64 bind of import 3, addend 8, end of chain
+        assert_eq!(
+            chained_pointer_decode(2, 3 | (8 << 24) | (1 << 63)),
+            Some((
+                ChainedPointer::Bind {
+                    import: 3,
+                    addend: 8,
+                    auth: None
+                },
+                0
+            ))
+        );
+
+        // DYLD_CHAINED_PTR_ARM64E bind, negative addend
+        let addend = (-4i64 as u64) & ((1 << 19) - 1);
+        assert_eq!(
+            chained_pointer_decode(1, 5 | (addend << 32) | (1 << 62)),
+            Some((
+                ChainedPointer::Bind {
+                    import: 5,
+                    addend: -4,
+                    auth: None
+                },
+                0
+            ))
+        );
+
+        // DYLD_CHAINED_PTR_ARM64E_USERLAND24 auth rebase, key DA,
+        // address diversity, diversity 0x1234
+        let auth = (1 << 63) | (2 << 49) | (1 << 48) | (0x1234 << 32);
+        assert_eq!(
+            chained_pointer_decode(12, auth | 0x4000),
+            Some((
+                ChainedPointer::Rebase {
+                    target: 0x4000,
+                    high8: 0,
+                    offset: true,
+                    auth: Some(ChainedAuth {
+                        key: PacKey::DataA,
+                        address_diversity: true,
+                        diversity: 0x1234
+                    })
+                },
+                0
+            ))
+        );
+
+        // DYLD_CHAINED_PTR_ARM64E auth bind of import 7, key IA
+        assert_eq!(
+            chained_pointer_decode(1, (1 << 63) | (1 << 62) | (0x42 << 32) | 7),
+            Some((
+                ChainedPointer::Bind {
+                    import: 7,
+                    addend: 0,
+                    auth: Some(ChainedAuth {
+                        key: PacKey::InstructionA,
+                        address_diversity: false,
+                        diversity: 0x42
+                    })
+                },
+                0
+            ))
+        );
+
+        assert_eq!(chained_pointer_decode(0x42, 0), None);
+    }
+
+    #[test]
+    pub fn test_chained_auth_sign() {
+        let value = test_chained_auth_sign as *const () as u64;
+        let slot = 0x1_0000_8010usize;
+
+        let auth = ChainedAuth {
+            key: PacKey::DataA,
+            address_diversity: true,
+            diversity: 0x1234,
+        };
+        let signed = auth.sign(value, slot);
+        let discriminator = blend_discriminator(slot as u64, 0x1234);
+        assert_eq!(
+            unsafe { PacKey::DataA.authenticate(signed, discriminator) },
+            value
+        );
+
+        let auth = ChainedAuth {
+            address_diversity: false,
+            ..auth
+        };
+        let signed = auth.sign(value, slot);
+        assert_eq!(unsafe { PacKey::DataA.authenticate(signed, 0x1234) }, value);
+    }
+
+    #[test]
+    pub fn test_chained_page_walk() {
+        let write = |page: &mut [u8], offset: usize, raw: u64| {
+            page[offset..offset + 8].copy_from_slice(&raw.to_le_bytes())
+        };
+
+        // DYLD_CHAINED_PTR_64, strides of 4 bytes: 0x10 -> 0x18 -> 0x30
+        let mut page = vec![0u8; 0x40];
+        write(&mut page, 0x10, 0x1000 | (2 << 51));
+        write(&mut page, 0x18, 1 | (1 << 63) | (6 << 51));
+        write(&mut page, 0x30, 0x2000);
+        assert_eq!(
+            chained_page_walk(2, &page, 0x10),
+            Some(vec![
+                (
+                    0x10,
+                    ChainedPointer::Rebase {
+                        target: 0x1000,
+                        high8: 0,
+                        offset: false,
+                        auth: None
+                    }
+                ),
+                (
+                    0x18,
+                    ChainedPointer::Bind {
+                        import: 1,
+                        addend: 0,
+                        auth: None
+                    }
+                ),
+                (
+                    0x30,
+                    ChainedPointer::Rebase {
+                        target: 0x2000,
+                        high8: 0,
+                        offset: false,
+                        auth: None
+                    }
+                ),
+            ])
+        );
+
+        // DYLD_CHAINED_PTR_ARM64E, strides of 8 bytes: 0 -> 0x8 -> 0x20
+        let mut page = vec![0u8; 0x40];
+        write(&mut page, 0, 0x1000 | (1 << 51));
+        write(&mut page, 0x8, 0x1008 | (3 << 51));
+        write(&mut page, 0x20, 2 | (1 << 62));
+        let offsets = chained_page_walk(1, &page, 0)
+            .unwrap()
+            .into_iter()
+            .map(|(offset, _)| offset)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 0x8, 0x20]);
+
+        // a next running past the page
+        write(&mut page, 0x20, 2 | (1 << 62) | (4 << 51));
+        assert_eq!(chained_page_walk(1, &page, 0), None);
+
+        // a start past the page, an unknown format
+        assert_eq!(chained_page_walk(1, &page, 0x3c), None);
+        assert_eq!(chained_page_walk(0x42, &page, 0), None);
+    }
+}
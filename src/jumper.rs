@@ -1,42 +1,931 @@
-use std::{ffi, ptr::NonNull};
+//! Starting the loaded program.
+//!
+//! # Exiting
+//!
+//! The program runs in the loader's process, `exit`, `_exit` and
+//! `abort` are the real libc functions and end the whole process, host
+//! included. `atexit` handlers it registers run when the host exits.
+//! Only a program that returns from `main` gives control back:
+//!
+//! - [`jumper`] exits the process with the value `main` returns,
+//!   either way the host is gone. A `start` never returns.
+//! - [`Task::run_on_thread`] and [`Task::call_symbol`] return when the
+//!   function returns, an `exit` from the program still ends the host.
+//! - [`Task::run_forked`] and [`jumper_forked`] run the program in a
+//!   child, where `exit` only ends the child and its status is reported
+//!   to the parent.
+//!
+//! Embedders that need to survive a program calling `exit` have to use
+//! [`Task::run_forked`].
 
-/// Jumps and transfers control flow to the offset `entry_point`
-/// from memory.
+use std::{
+    ffi::{self, CStr, CString},
+    fmt, io,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicI32, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Task, error::ExecError};
+
+/// How control is handed to the loaded program, see [`Task::entry`]
+///
+/// Both hold the entry point as a virtual address of the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryPoint {
+    /// `LC_MAIN`: `main(argc, argv, envp, apple)`, called like any
+    /// function. The C runtime that would pass its return value to
+    /// `exit` is not there, the caller has to.
+    Main(usize),
+
+    /// `LC_UNIXTHREAD`: `start`, the C runtime itself. It finds argc,
+    /// argv, envp and apple on its stack, calls `main` and `exit`, and
+    /// never returns.
+    Start(usize),
+}
+
+/// Describes the environment handed to the loaded program.
+#[derive(Debug, Default, Clone)]
+pub enum EnvPolicy {
+    /// Passes the hosts environment as is
+    #[default]
+    Inherit,
+
+    /// Passes an empty environment
+    Clear,
+
+    /// Passes only the given `(key, value)` pairs
+    ///
+    /// Use this to strip variables such as `DYLD_INSERT_LIBRARIES`
+    /// or to inject new ones.
+    Custom(Vec<(String, String)>),
+}
+
+impl EnvPolicy {
+    /// Builds the `KEY=value` strings for the environment.
+    fn build(&self) -> Vec<CString> {
+        match self {
+            EnvPolicy::Inherit => unsafe {
+                // get environment variables from libc
+                let mut envp = *libc::_NSGetEnviron();
+                let mut env = Vec::new();
+
+                while !(*envp).is_null() {
+                    env.push(CStr::from_ptr(*envp).to_owned());
+                    envp = envp.add(1);
+                }
+                env
+            },
+            EnvPolicy::Clear => Vec::new(),
+            EnvPolicy::Custom(vars) => vars
+                .iter()
+                .map(|(key, value)| {
+                    CString::new(format!("{key}={value}"))
+                        .expect("environment variable contains a nul byte")
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Called with the task right before jumping to it, see
+/// [`ExecOptions::before_jump`]
+pub type BeforeJump = Box<dyn FnOnce(&Task)>;
+
+/// Options controlling how the loaded program is started.
+#[derive(Default)]
+pub struct ExecOptions {
+    /// the environment of the program
+    pub env: EnvPolicy,
+
+    /// how long a forked program may run before it is killed,
+    /// see [`Task::run_forked`]
+    pub timeout: Option<Duration>,
+
+    /// the path of the loaded file, passed as `executable_path=` in `apple[]`
+    pub executable_path: Option<String>,
+
+    /// `argv[0]` of the program, defaults to `executable_path`
+    ///
+    /// Multicall binaries dispatch on it, and shells check it for a
+    /// leading '-', `executable_path=` keeps the real path either way.
+    pub argv0: Option<String>,
+
+    /// the stack size of the thread `main` runs on
+    ///
+    /// When set, `main` runs on a new thread with a stack of this size
+    /// instead of the calling thread. [`Task::jump`] fills it in from
+    /// [`Task::requested_stack_size`].
+    pub stack_size: Option<usize>,
+
+    /// called by [`Task::jump`] right before control is transferred to
+    /// the entry point, after all setup and protection
+    ///
+    /// It runs in the loader's process and may read the task to log the
+    /// entry address or set breakpoints, but must not invalidate it:
+    /// remapping, reprotecting or unmapping the segments makes the jump
+    /// that follows land on whatever is left.
+    pub before_jump: Option<BeforeJump>,
+
+    /// resets the dispositions of [`RESET_SIGNALS`] to `SIG_DFL` before
+    /// the program starts, and restores the host's if it returns
+    ///
+    /// The program otherwise inherits them from the host: the Rust
+    /// runtime ignores `SIGPIPE`, so a program writing to a closed pipe
+    /// gets `EPIPE` instead of being killed as it would be when started
+    /// from a shell.
+    pub reset_signals: bool,
+
+    /// refuses to start the program if a segment is writable and
+    /// executable, see [`Task::assert_wx_invariant`]
+    ///
+    /// [`Task::jump`] has no way to return the error and panics instead.
+    pub check_wx: bool,
+}
+
+impl fmt::Debug for ExecOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExecOptions")
+            .field("env", &self.env)
+            .field("timeout", &self.timeout)
+            .field("executable_path", &self.executable_path)
+            .field("argv0", &self.argv0)
+            .field("stack_size", &self.stack_size)
+            .field("before_jump", &self.before_jump.as_ref().map(|_| ".."))
+            .field("reset_signals", &self.reset_signals)
+            .field("check_wx", &self.check_wx)
+            .finish()
+    }
+}
+
+/// Returns 8 random bytes from `getentropy`.
+fn random_u64() -> u64 {
+    let mut value = 0u64;
+    let ret = unsafe { libc::getentropy((&raw mut value).cast(), size_of::<u64>()) };
+    if ret != 0 {
+        panic!("getentropy failed: {}", io::Error::last_os_error());
+    }
+    value
+}
+
+/// Builds the `apple[]` strings handed to `main` after `envp`.
+///
+/// The kernel passes these to dyld, which forwards them to the main
+/// executable. Only the entries that do not describe kernel or dyld
+/// internals are reproduced:
+///
+/// - `executable_path=` the path of the loaded file
+/// - `stack_guard=` a random stack canary, its second byte zero so that
+///   string functions overflowing a buffer can not write it back
+/// - `malloc_entropy=` two random values for the malloc zones
+/// - `ptr_munge=` a random value used by libc to mangle `setjmp` buffers
+///
+/// The random entries are drawn from `getentropy` and formatted like
+/// the kernel does, which is what dyld hands a program. libSystem was
+/// set up by the host's own `apple[]`, only code of the program reading
+/// them sees these.
+///
+/// `main_stack=` and `pfz=` describe the main thread's stack and the
+/// commpage, which the program shares with the host. They are copied
+/// from the host's own `apple[]` when it has them.
+///
+/// The kernel hands dyld the address of the header rather than an
+/// entry, programs find their header through `_NSGetMachExecuteHeader`,
+/// which is bound to a stand-in returning the loaded image's.
+fn apple_build(options: &ExecOptions) -> Vec<CString> {
+    let mut apple = Vec::new();
+
+    if let Some(path) = &options.executable_path {
+        apple.push(
+            CString::new(format!("executable_path={path}"))
+                .expect("executable path contains a nul byte"),
+        );
+    }
+
+    let stack_guard = random_u64() & !(0xff << 8);
+    apple.push(CString::new(format!("stack_guard={stack_guard:#x}")).unwrap());
+    apple.push(
+        CString::new(format!(
+            "malloc_entropy={:#x},{:#x}",
+            random_u64(),
+            random_u64()
+        ))
+        .unwrap(),
+    );
+    apple.push(CString::new(format!("ptr_munge={:#x}", random_u64())).unwrap());
+    apple.extend(["main_stack=", "pfz="].into_iter().filter_map(host_apple));
+    apple
+}
+
+/// Returns the entry of the host's own `apple[]` that starts with `key`.
+///
+/// The kernel lays `apple[]` out right after the null terminated `envp`,
+/// which follows `argv` on the stack of the main thread.
+fn host_apple(key: &str) -> Option<CString> {
+    unsafe {
+        let argv = *libc::_NSGetArgv();
+        let mut entry = argv.add(*libc::_NSGetArgc() as usize + 1);
+        while !(*entry).is_null() {
+            entry = entry.add(1);
+        }
+
+        entry = entry.add(1);
+        while !(*entry).is_null() {
+            let value = CStr::from_ptr(*entry);
+            if value.to_bytes().starts_with(key.as_bytes()) {
+                return Some(value.into());
+            }
+            entry = entry.add(1);
+        }
+    }
+    None
+}
+
+/// Returns the address the entry point `entry_point`, a virtual address
+/// of the image, is at in memory once the image is mapped at `memory`.
+///
+/// Segments are mapped at `memory + vmaddr`, so this is also where
+/// `__TEXT` put the code: no file offset is involved.
+pub fn entry_address(memory: NonNull<u8>, entry_point: usize) -> usize {
+    assert!(entry_point != 0, "the image has no entry point");
+    unsafe { memory.add(entry_point).as_ptr().addr() }
+}
+
+/// Jumps and transfers control flow to `memory + entry_point`,
+/// the entry point being a virtual address of the image.
+///
+/// Never returns: the process exits once `main` returns, or when the
+/// program calls `exit`.
 pub fn jumper(memory: NonNull<u8>, entry_point: usize) -> ! {
+    jumper_with_options(memory, entry_point, &ExecOptions::default())
+}
+
+/// Same as [`jumper`], with the program started according to `options`.
+///
+/// `options.before_jump` is not called, use [`Task::jump`] for that.
+pub fn jumper_with_options(memory: NonNull<u8>, entry_point: usize, options: &ExecOptions) -> ! {
+    jumper_entry(memory, EntryPoint::Main(entry_point), options)
+}
+
+/// Same as [`jumper_with_options`], for either kind of entry point.
+///
+/// `main` is called and its return value passed to `exit`, which runs
+/// the `atexit` handlers and static destructors as the C runtime would.
+/// `start` is jumped to on a fresh stack.
+pub fn jumper_entry(memory: NonNull<u8>, entry: EntryPoint, options: &ExecOptions) -> ! {
+    match entry {
+        EntryPoint::Main(entry_point) => main_jumper(entry_address(memory, entry_point), options),
+        EntryPoint::Start(entry_point) => start_jumper(entry_address(memory, entry_point), options),
+    }
+}
+
+/// Calls the `main` at `entry_address` and exits with what it returns.
+fn main_jumper(entry_address: usize, options: &ExecOptions) -> ! {
+    let call = main_caller(entry_address, options);
+
+    let ret = match options.stack_size {
+        None => call(),
+        Some(stack_size) => thread::Builder::new()
+            .stack_size(stack_size)
+            .spawn(call)
+            .expect("failed to spawn the main thread")
+            .join()
+            .expect("the main thread panicked"),
+    };
+
+    std::process::exit(ret)
+}
+
+/// The stack `start` gets when the program does not ask for one
+const START_STACK_SIZE: usize = 8 << 20;
+
+/// Jumps to the `start` of an `LC_UNIXTHREAD` program, at `entry_address`.
+///
+/// dyld hands over to `start` with `sp` pointing at argc, followed by
+/// the null terminated argv, envp and apple arrays. That layout is
+/// built at the top of a new stack, `sp` is switched to it and `start`
+/// is branched to with no frame to return to.
+fn start_jumper(entry_address: usize, options: &ExecOptions) -> ! {
+    let (name, env, apple) = program_strings(options);
+
+    let mut words = vec![1, name.as_ptr().addr(), 0];
+    words.extend(env.iter().map(|var| var.as_ptr().addr()));
+    words.push(0);
+    words.extend(apple.iter().map(|var| var.as_ptr().addr()));
+    words.push(0);
+
+    let stack_size = crate::mach::vm_page_round(options.stack_size.unwrap_or(START_STACK_SIZE));
+    let stack = crate::mach::vm_alloc_self(stack_size);
+
+    if options.reset_signals {
+        // start never returns, there is nothing to restore
+        std::mem::forget(SignalDispositions::reset());
+    }
+
+    unsafe {
+        let sp = stack
+            .add(stack_size - (words.len() * size_of::<usize>()).next_multiple_of(16))
+            .as_ptr() as *mut usize;
+        sp.copy_from_nonoverlapping(words.as_ptr(), words.len());
+
+        // the strings are still referenced from the new stack
+        std::mem::forget((name, env, apple));
+
+        core::arch::asm!(
+            "mov sp, x9",
+            "mov x29, xzr",
+            "mov x30, xzr",
+            "br x16",
+            in("x9") sp,
+            in("x16") entry_address,
+            options(noreturn)
+        )
+    }
+}
+
+/// Returns `argv[0]`, the environment and the `apple[]` strings of the
+/// program.
+fn program_strings(options: &ExecOptions) -> (CString, Vec<CString>, Vec<CString>) {
     // convert program name to null-terminated string
-    let name = ffi::CString::new("dummy_name").unwrap();
+    let name = options
+        .argv0
+        .as_deref()
+        .or(options.executable_path.as_deref())
+        .unwrap_or("dummy_name");
+    let name = ffi::CString::new(name).expect("argv[0] contains a nul byte");
+
+    (name, options.env.build(), apple_build(options))
+}
+
+/// Builds the arguments of the `main` at `entry_address` and returns a
+/// closure calling it with them, on whichever thread runs the closure.
+///
+/// The closure owns the strings and only builds the pointer arrays
+/// once called, so that it can be sent to another thread.
+fn main_caller(
+    entry_address: usize,
+    options: &ExecOptions,
+) -> impl FnOnce() -> i32 + Send + 'static {
+    let (name, env, apple) = program_strings(options);
+
+    let reset_signals = options.reset_signals;
+
+    move || {
+        // dispositions are per process, restored once main returns
+        let _signals = reset_signals.then(SignalDispositions::reset);
+
+        // MAP_JIT pages are writable or executable per thread, this
+        // one may be new
+        #[cfg(feature = "jit")]
+        crate::mach::jit_write_protect(true);
+
+        // initialize argument vector with program name
+        let argv = [name.as_ptr(), ptr::null()];
+        let argc = argv.len() - 1;
+
+        // build the null terminated environment
+        let envp = env
+            .iter()
+            .map(|var| var.as_ptr())
+            .chain([ptr::null()])
+            .collect::<Vec<_>>();
+
+        // build the null terminated apple[] array
+        let applep = apple
+            .iter()
+            .map(|var| var.as_ptr())
+            .chain([ptr::null()])
+            .collect::<Vec<_>>();
+
+        unsafe {
+            // cast the entry point to a main function
+            let entry_fn = std::mem::transmute::<
+                usize,
+                extern "C" fn(
+                    argc: usize,
+                    argv: *const *const u8,
+                    envp: *const *const u8,
+                    apple: *const *const u8,
+                ) -> libc::c_int,
+            >(entry_address);
+
+            // call it
+            entry_fn(
+                argc,
+                argv.as_ptr() as *const *const u8,
+                envp.as_ptr() as *const *const u8,
+                applep.as_ptr() as *const *const u8,
+            )
+        }
+    }
+}
+
+/// Runs the program in a forked child and waits for it to finish.
+///
+/// The child jumps to the entry point, the parent only waits. When
+/// `options.timeout` is set and runs out, the child is killed with
+/// `SIGKILL` and [`ExecError::Timeout`] is returned.
+///
+/// Returns the exit code of the program.
+pub fn jumper_forked(
+    memory: NonNull<u8>,
+    entry_point: usize,
+    options: &ExecOptions,
+) -> Result<i32, ExecError> {
+    let pid = match unsafe { libc::fork() } {
+        -1 => return Err(ExecError::Fork(io::Error::last_os_error())),
+        0 => jumper_with_options(memory, entry_point, options),
+        pid => pid,
+    };
+
+    let status = wait_child(pid, options.timeout)?;
+
+    if libc::WIFSIGNALED(status) {
+        return Err(ExecError::Signaled {
+            signal: libc::WTERMSIG(status),
+        });
+    }
+    Ok(libc::WEXITSTATUS(status))
+}
+
+/// Waits for the child `pid` and returns its raw status.
+///
+/// The child is killed once `timeout` runs out.
+fn wait_child(pid: libc::pid_t, timeout: Option<Duration>) -> Result<i32, ExecError> {
+    let mut status = 0;
+    match timeout {
+        None => {
+            if unsafe { libc::waitpid(pid, &mut status, 0) } == -1 {
+                return Err(ExecError::Wait(io::Error::last_os_error()));
+            }
+        }
+        Some(timeout) => {
+            let start = Instant::now();
+            loop {
+                match unsafe { libc::waitpid(pid, &mut status, libc::WNOHANG) } {
+                    -1 => return Err(ExecError::Wait(io::Error::last_os_error())),
+                    0 if start.elapsed() >= timeout => {
+                        unsafe {
+                            libc::kill(pid, libc::SIGKILL);
+                            // reap it so it does not linger as a zombie
+                            libc::waitpid(pid, &mut status, 0);
+                        }
+                        return Err(ExecError::Timeout);
+                    }
+                    0 => thread::sleep(Duration::from_millis(10)),
+                    _ => break,
+                }
+            }
+        }
+    }
+    Ok(status)
+}
+
+/// The signals reported as crashes by [`Task::run_forked`]
+const CRASH_SIGNALS: [libc::c_int; 5] = [
+    libc::SIGSEGV,
+    libc::SIGBUS,
+    libc::SIGILL,
+    libc::SIGFPE,
+    libc::SIGTRAP,
+];
+
+/// The signals [`ExecOptions::reset_signals`] resets: those a shell
+/// leaves at their default and a host commonly ignores or handles
+pub const RESET_SIGNALS: [libc::c_int; 12] = [
+    libc::SIGPIPE,
+    libc::SIGCHLD,
+    libc::SIGHUP,
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGTERM,
+    libc::SIGALRM,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+    libc::SIGTSTP,
+    libc::SIGTTIN,
+    libc::SIGTTOU,
+];
+
+/// The host's dispositions of [`RESET_SIGNALS`], put back on drop
+struct SignalDispositions {
+    saved: Vec<(libc::c_int, libc::sigaction)>,
+}
+
+impl SignalDispositions {
+    /// Resets every signal of [`RESET_SIGNALS`] to `SIG_DFL`, saving
+    /// what the host had.
+    fn reset() -> Self {
+        let mut saved = Vec::with_capacity(RESET_SIGNALS.len());
+
+        for signal in RESET_SIGNALS {
+            unsafe {
+                let mut action = std::mem::zeroed::<libc::sigaction>();
+                action.sa_sigaction = libc::SIG_DFL;
+                libc::sigemptyset(&mut action.sa_mask);
 
-    // initialize argument vector with program name
-    let mut argv = vec![name.as_bytes_with_nul()];
+                let mut old = std::mem::zeroed::<libc::sigaction>();
+                if libc::sigaction(signal, &action, &mut old) == 0 {
+                    saved.push((signal, old));
+                }
+            }
+        }
+
+        SignalDispositions { saved }
+    }
+}
+
+impl Drop for SignalDispositions {
+    fn drop(&mut self) {
+        for (signal, action) in &self.saved {
+            unsafe { libc::sigaction(*signal, action, ptr::null_mut()) };
+        }
+    }
+}
+
+/// The write end of the pipe the crash handler reports through, in
+/// the forked child
+static CRASH_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+/// The leading fields of `struct __darwin_ucontext` from "sys/_types/_ucontext.h"
+#[repr(C)]
+#[allow(dead_code)]
+struct Ucontext {
+    uc_onstack: libc::c_int,
+    uc_sigmask: u32,
+    uc_stack: libc::stack_t,
+    uc_link: *mut libc::c_void,
+    uc_mcsize: usize,
+    uc_mcontext: *const Mcontext64,
+}
+
+/// The leading fields of `struct __darwin_mcontext64` on arm64, from
+/// "mach/arm/_structs.h"
+#[repr(C)]
+#[allow(dead_code)]
+struct Mcontext64 {
+    // __darwin_arm_exception_state64
+    far: u64,
+    esr: u32,
+    exception: u32,
+
+    // __darwin_arm_thread_state64
+    x: [u64; 29],
+    fp: u64,
+    lr: u64,
+    sp: u64,
+    pc: u64,
+}
 
-    let argc = argv.len();
+/// Reports the signal, the faulting pc and address to the parent,
+/// then dies from the signal.
+///
+/// Only async-signal-safe calls are made, symbolizing is left to the
+/// parent.
+extern "C" fn crash_handler(
+    signal: libc::c_int,
+    _info: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
     unsafe {
-        // get environment variables from libc
-        let mut envp = *libc::_NSGetEnviron();
+        let mcontext = &*(*(context as *const Ucontext)).uc_mcontext;
+        let report = [signal as u64, mcontext.pc, mcontext.far];
 
-        while !(*envp).is_null() {
-            // push each environment string to argv with null terminator
-            argv.push(ffi::CStr::from_ptr(*envp).to_bytes_with_nul());
-            envp = envp.add(1);
+        libc::write(
+            CRASH_PIPE.load(Ordering::Relaxed),
+            report.as_ptr().cast(),
+            size_of_val(&report),
+        );
+
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+/// The size of the stack [`crash_handler`] runs on
+const CRASH_STACK_SIZE: usize = 64 << 10;
+
+/// Installs [`crash_handler`] for every signal in [`CRASH_SIGNALS`].
+///
+/// A stack overflow leaves no stack to run it on, it runs on `stack`,
+/// the alternate stack of the calling thread. Threads spawned by the
+/// Rust runtime get their own. Nothing is allocated: this runs in the
+/// child of a fork, where another thread of the parent may have held
+/// the malloc lock.
+fn crash_handler_install(pipe: libc::c_int, stack: &mut [u8]) {
+    CRASH_PIPE.store(pipe, Ordering::Relaxed);
+
+    let altstack = libc::stack_t {
+        ss_sp: stack.as_mut_ptr().cast(),
+        ss_size: stack.len(),
+        ss_flags: 0,
+    };
+    unsafe { libc::sigaltstack(&altstack, ptr::null_mut()) };
+
+    for signal in CRASH_SIGNALS {
+        unsafe {
+            let mut action = std::mem::zeroed::<libc::sigaction>();
+            action.sa_sigaction = crash_handler as *const () as usize;
+            action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, ptr::null_mut());
+        }
+    }
+}
+
+impl Task {
+    /// Jumps to the entry point of the task, started according
+    /// to `options`.
+    ///
+    /// `options.before_jump` is called first, with the task as it
+    /// will be run.
+    pub fn jump(&self, mut options: ExecOptions) -> ! {
+        if options.stack_size.is_none() {
+            options.stack_size = self.requested_stack_size();
+        }
+
+        if let Some(before_jump) = options.before_jump.take() {
+            before_jump(self);
         }
 
-        // get the entry point pointer in memory
-        let entry_address = memory.add(entry_point).as_ptr();
+        if let Err(err) = self.wx_check(&options) {
+            panic!("{err}");
+        }
 
-        // cast it to a main function
-        let entry_fn = std::mem::transmute::<
-            *mut u8,
-            extern "C" fn(argc: usize, argv: *const *const u8, envp: *const *const u8),
-        >(entry_address);
+        self.jump_to_entry(&options)
+    }
 
-        // call it
-        entry_fn(
-            argc,
-            argv.as_ptr() as *const *const u8,
-            envp as *const *const u8,
+    /// Runs [`Task::assert_wx_invariant`] if `options` asks for it.
+    fn wx_check(&self, options: &ExecOptions) -> Result<(), ExecError> {
+        match options.check_wx {
+            true => self.assert_wx_invariant().map_err(ExecError::Security),
+            false => Ok(()),
+        }
+    }
+
+    /// Hands control to the program at [`Task::entry_address`], as its
+    /// kind of entry point requires.
+    fn jump_to_entry(&self, options: &ExecOptions) -> ! {
+        let entry_address = self.entry_address().expect("the image has no entry point");
+
+        match self.entry() {
+            Some(EntryPoint::Start(_)) => start_jumper(entry_address, options),
+            _ => main_jumper(entry_address, options),
+        }
+    }
+
+    /// Same as [`jumper_forked`], reporting crashes of the program.
+    ///
+    /// The child catches `SIGSEGV`, `SIGBUS`, `SIGILL`, `SIGFPE` and
+    /// `SIGTRAP` and sends the faulting pc and address to the parent
+    /// before dying from the signal. The parent symbolizes the pc against
+    /// the task and returns [`ExecError::Crashed`]. Other signals are
+    /// returned as [`ExecError::Signaled`].
+    pub fn run_forked(&self, options: &ExecOptions) -> Result<i32, ExecError> {
+        self.wx_check(options)?;
+
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+            return Err(ExecError::Fork(io::Error::last_os_error()));
+        }
+        let [read_end, write_end] = fds;
+
+        // allocated before forking, the child never frees it, it dies
+        // with the program
+        let mut crash_stack = vec![0u8; CRASH_STACK_SIZE];
+
+        let pid = match unsafe { libc::fork() } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                unsafe {
+                    libc::close(read_end);
+                    libc::close(write_end);
+                }
+                return Err(ExecError::Fork(err));
+            }
+            0 => {
+                unsafe { libc::close(read_end) };
+                crash_handler_install(write_end, &mut crash_stack);
+                self.jump_to_entry(options)
+            }
+            pid => pid,
+        };
+        drop(crash_stack);
+
+        // a grandchild may keep the write end open, never block on it
+        unsafe {
+            libc::close(write_end);
+            libc::fcntl(read_end, libc::F_SETFL, libc::O_NONBLOCK);
+        }
+
+        let status = wait_child(pid, options.timeout);
+
+        let mut report = [0u64; 3];
+        let read = unsafe {
+            let read = libc::read(read_end, report.as_mut_ptr().cast(), size_of_val(&report));
+            libc::close(read_end);
+            read
+        };
+        let status = status?;
+
+        if libc::WIFSIGNALED(status) {
+            let signal = libc::WTERMSIG(status);
+            if read == size_of_val(&report) as isize && report[0] == signal as u64 {
+                let [_, pc, address] = report;
+                return Err(ExecError::Crashed {
+                    signal,
+                    pc,
+                    address,
+                    symbol: self
+                        .symbolize(pc as usize)
+                        .map(|(name, offset)| format!("{name}+{offset:#x}")),
+                });
+            }
+            return Err(ExecError::Signaled { signal });
+        }
+        Ok(libc::WEXITSTATUS(status))
+    }
+
+    /// Runs the program on a new thread, waits for `main` to return
+    /// and returns its exit code.
+    ///
+    /// A program calling `exit` instead of returning ends the process,
+    /// see [Exiting](crate::jumper#exiting).
+    ///
+    /// The thread gets a stack of `options.stack_size`, or of the size
+    /// the image asked for in `LC_MAIN`, so the program does not share
+    /// the stack of the calling thread. It still shares the process,
+    /// `options.before_jump` is not called.
+    pub fn run_on_thread(&self, options: &ExecOptions) -> Result<i32, ExecError> {
+        assert!(
+            !matches!(self.entry(), Some(EntryPoint::Start(_))),
+            "an LC_UNIXTHREAD start never returns, use jump or run_forked"
+        );
+        self.wx_check(options)?;
+
+        let mut builder = thread::Builder::new();
+        if let Some(stack_size) = options.stack_size.or(self.requested_stack_size()) {
+            builder = builder.stack_size(stack_size);
+        }
+
+        let ret = builder
+            .spawn(main_caller(
+                self.entry_address().expect("the image has no entry point"),
+                options,
+            ))
+            .map_err(ExecError::Thread)?
+            .join()
+            .expect("the main thread panicked");
+
+        Ok(ret)
+    }
+
+    /// Runs the initializers of the image, see [`Task::initializers`].
+    ///
+    /// dyld runs them before `main`, or before `dlopen` returns for a
+    /// dylib or a bundle. They get no arguments, which is enough for C++
+    /// static constructors and `__attribute__((constructor))` functions
+    /// that do not look at argc/argv.
+    ///
+    /// # Safety
+    ///
+    /// The task must be linked and protected, and the initializers run
+    /// exactly once. The terminators then run on [`Task::shutdown`].
+    pub unsafe fn run_initializers(&mut self) {
+        self.initialized = true;
+
+        for initializer in self.initializers() {
+            let initializer = unsafe { std::mem::transmute::<usize, extern "C" fn()>(initializer) };
+            initializer();
+        }
+    }
+
+    /// Calls the function `name` defined by the loaded image and
+    /// returns what it left in `x0`.
+    ///
+    /// `args` are passed in `x0`-`x7`, so at most 8 integer or pointer
+    /// arguments are supported. Nothing is set up per call: the task must
+    /// already be linked and protected, after which the function can be
+    /// called as many times as needed.
+    ///
+    /// Returns `None` when the image does not define `name`.
+    ///
+    /// # Safety
+    ///
+    /// `name` must be a function taking `args.len()` integer arguments
+    /// and returning an integer or nothing.
+    pub unsafe fn call_symbol(&self, name: &str, args: &[u64]) -> Option<u64> {
+        type F0 = extern "C" fn() -> u64;
+        type F1 = extern "C" fn(u64) -> u64;
+        type F2 = extern "C" fn(u64, u64) -> u64;
+        type F3 = extern "C" fn(u64, u64, u64) -> u64;
+        type F4 = extern "C" fn(u64, u64, u64, u64) -> u64;
+        type F5 = extern "C" fn(u64, u64, u64, u64, u64) -> u64;
+        type F6 = extern "C" fn(u64, u64, u64, u64, u64, u64) -> u64;
+        type F7 = extern "C" fn(u64, u64, u64, u64, u64, u64, u64) -> u64;
+        type F8 = extern "C" fn(u64, u64, u64, u64, u64, u64, u64, u64) -> u64;
+
+        let address = self.symbol_address(name)? as *const ();
+
+        let ret = unsafe {
+            match *args {
+                [] => std::mem::transmute::<*const (), F0>(address)(),
+                [a] => std::mem::transmute::<*const (), F1>(address)(a),
+                [a, b] => std::mem::transmute::<*const (), F2>(address)(a, b),
+                [a, b, c] => std::mem::transmute::<*const (), F3>(address)(a, b, c),
+                [a, b, c, d] => std::mem::transmute::<*const (), F4>(address)(a, b, c, d),
+                [a, b, c, d, e] => std::mem::transmute::<*const (), F5>(address)(a, b, c, d, e),
+                [a, b, c, d, e, f] => {
+                    std::mem::transmute::<*const (), F6>(address)(a, b, c, d, e, f)
+                }
+                [a, b, c, d, e, f, g] => {
+                    std::mem::transmute::<*const (), F7>(address)(a, b, c, d, e, f, g)
+                }
+                [a, b, c, d, e, f, g, h] => {
+                    std::mem::transmute::<*const (), F8>(address)(a, b, c, d, e, f, g, h)
+                }
+                _ => panic!("{name}: {} arguments, at most 8 are supported", args.len()),
+            }
+        };
+
+        Some(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::jumper::{
+        ExecOptions, SignalDispositions, apple_build, host_apple, program_strings,
+    };
+
+    /// Returns the handler of `signal`.
+    fn disposition(signal: libc::c_int) -> libc::sighandler_t {
+        let mut action = unsafe { std::mem::zeroed::<libc::sigaction>() };
+        unsafe { libc::sigaction(signal, std::ptr::null(), &mut action) };
+        action.sa_sigaction
+    }
+
+    #[test]
+    pub fn test_apple_entropy() {
+        let apple = apple_build(&ExecOptions::default());
+        let value = |key: &str| {
+            apple
+                .iter()
+                .find_map(|entry| entry.to_str().unwrap().strip_prefix(key))
+                .unwrap_or_else(|| panic!("no {key} in {apple:?}"))
+                .to_string()
+        };
+        let hex = |value: &str| u64::from_str_radix(value.trim_start_matches("0x"), 16).unwrap();
+
+        assert_eq!(hex(&value("stack_guard=")) & 0xff00, 0);
+
+        let entropy = value("malloc_entropy=");
+        let (first, second) = entropy
+            .split_once(',')
+            .expect("malloc_entropy is one value");
+        assert!(hex(first) != 0 || hex(second) != 0);
+
+        // a fresh canary every time
+        let other = apple_build(&ExecOptions::default());
+        assert_ne!(apple, other);
+    }
+
+    #[test]
+    pub fn test_argv0() {
+        let mut options = ExecOptions {
+            executable_path: Some("/usr/local/bin/busybox".to_string()),
+            ..Default::default()
+        };
+        let (name, _, _) = program_strings(&options);
+        assert_eq!(name.to_str(), Ok("/usr/local/bin/busybox"));
+
+        // argv[0] changes, executable_path= keeps the real path
+        options.argv0 = Some("-sh".to_string());
+        let (name, _, apple) = program_strings(&options);
+        assert_eq!(name.to_str(), Ok("-sh"));
+        assert_eq!(
+            apple[0].to_str(),
+            Ok("executable_path=/usr/local/bin/busybox")
         );
+    }
+
+    #[test]
+    pub fn test_apple_host_entries() {
+        let main_stack = host_apple("main_stack=").expect("the host has a main_stack=");
+        assert!(apple_build(&ExecOptions::default()).contains(&main_stack));
+        assert_eq!(host_apple("no_such_key="), None);
+    }
+
+    #[test]
+    pub fn test_signal_dispositions_reset() {
+        // the Rust runtime ignores SIGPIPE
+        assert_eq!(disposition(libc::SIGPIPE), libc::SIG_IGN);
+
+        let signals = SignalDispositions::reset();
+        assert_eq!(disposition(libc::SIGPIPE), libc::SIG_DFL);
 
-        // completed successfully
-        std::process::exit(0)
+        drop(signals);
+        assert_eq!(disposition(libc::SIGPIPE), libc::SIG_IGN);
     }
 }
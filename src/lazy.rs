@@ -0,0 +1,230 @@
+//! Binding lazy symbol pointers on first call.
+//!
+//! Instead of the address of its symbol, every `__la_symbol_ptr` slot
+//! starts out pointing at its own entry of [`lazy_table`], a table of
+//! branches to [`lazy_common`]. The stubs calling through the slots
+//! (`adrp x16, slot@PAGE; ldr x16, [x16, slot@PAGEOFF]; br x16`) leave
+//! the entry in `x16`, which tells which slot was called. The symbol is
+//! then looked up, written to the slot, and branched to with the
+//! arguments of the call untouched. Later calls go straight to it.
+//!
+//! Only lazy symbol pointers are bound this way: `__got` slots are also
+//! loaded as data (`&errno`, `__stderrp`, ...), and a trampoline in
+//! there would be read as the address of the variable.
+//!
+//! # Thread safety
+//!
+//! Slots are registered under a lock, before the program runs. Two
+//! threads calling through the same slot for the first time both look
+//! the symbol up and write the same address. The slot is an aligned
+//! pointer, the write is a single store, so a thread racing with it
+//! reads either the trampoline or the symbol, never a torn value.
+//! The slot has to stay writable: a page sealed read-only faults on the
+//! first call.
+//!
+//! The table is shared by every task of the process. The entries of a
+//! task are freed when it shuts down and reused by later tasks, once
+//! [`LAZY_ENTRIES`] slots are registered the rest are bound eagerly.
+
+use std::{
+    ffi::CString,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::{Task, abort_with, error::AbortReason, linker::dylib_symbol};
+
+/// The number of entries of [`lazy_table`]
+pub const LAZY_ENTRIES: usize = 4096;
+
+/// The size of an entry of [`lazy_table`], a single `b`
+const LAZY_ENTRY_SIZE: usize = 4;
+
+/// A lazy symbol pointer waiting for its first call
+struct LazySlot {
+    /// address of the slot
+    slot: usize,
+
+    /// the symbol, without the leading '_'
+    symbol: CString,
+
+    /// `dlopen` handle of the dylib it is imported from
+    handle: u64,
+}
+
+/// The registered slots, indexed like [`lazy_table`], `None` once their
+/// task shut down
+static LAZY_SLOTS: Mutex<Vec<Option<LazySlot>>> = Mutex::new(Vec::new());
+
+#[unsafe(naked)]
+/// The entries the lazy slots point at, each a branch to [`lazy_common`].
+unsafe extern "C" fn lazy_table() {
+    core::arch::naked_asm!(
+        ".rept {entries}",
+        "b {common}",
+        ".endr",
+        entries = const LAZY_ENTRIES,
+        common = sym lazy_common,
+    )
+}
+
+#[unsafe(naked)]
+/// Binds the slot of the entry in `x16` and tail calls its symbol.
+///
+/// The argument registers (`x0`-`x8`, `q0`-`q7`) are saved around
+/// [`lazy_bind`], the callee gets them as the caller left them.
+unsafe extern "C" fn lazy_common() {
+    core::arch::naked_asm!(
+        "stp x29, x30, [sp, #-16]!",
+        "mov x29, sp",
+        "stp x0, x1, [sp, #-16]!",
+        "stp x2, x3, [sp, #-16]!",
+        "stp x4, x5, [sp, #-16]!",
+        "stp x6, x7, [sp, #-16]!",
+        "str x8, [sp, #-16]!",
+        "stp q0, q1, [sp, #-32]!",
+        "stp q2, q3, [sp, #-32]!",
+        "stp q4, q5, [sp, #-32]!",
+        "stp q6, q7, [sp, #-32]!",
+        "mov x0, x16",
+        "bl {bind}",
+        "mov x16, x0",
+        "ldp q6, q7, [sp], #32",
+        "ldp q4, q5, [sp], #32",
+        "ldp q2, q3, [sp], #32",
+        "ldp q0, q1, [sp], #32",
+        "ldr x8, [sp], #16",
+        "ldp x6, x7, [sp], #16",
+        "ldp x4, x5, [sp], #16",
+        "ldp x2, x3, [sp], #16",
+        "ldp x0, x1, [sp], #16",
+        "ldp x29, x30, [sp], #16",
+        "br x16",
+        bind = sym lazy_bind,
+    )
+}
+
+/// Looks up the symbol of the slot whose entry is `entry`, writes it to
+/// the slot and returns it.
+///
+/// There is no error to return to the program, an unresolved symbol
+/// aborts like it does under dyld, see [`crate::set_abort_hook`].
+extern "C" fn lazy_bind(entry: usize) -> usize {
+    let index = (entry - lazy_table as *const () as usize) / LAZY_ENTRY_SIZE;
+
+    let bound = {
+        let slots = LAZY_SLOTS.lock().unwrap_or_else(|err| err.into_inner());
+        match &slots[index] {
+            Some(lazy) => match dylib_symbol(lazy.handle, &lazy.symbol) {
+                target if target.is_null() => Err(AbortReason::LazySymbolNotFound {
+                    symbol: lazy.symbol.to_string_lossy().into_owned(),
+                }),
+                target => Ok((lazy.slot, target.addr())),
+            },
+            None => Err(AbortReason::LazySlotUnloaded),
+        }
+    };
+    let (slot, target) = bound.unwrap_or_else(|reason| abort_with(reason));
+
+    unsafe { (*(slot as *const AtomicU64)).store(target as u64, Ordering::Release) };
+    target
+}
+
+/// Registers the lazy symbol pointer at `slot` and returns the entry to
+/// write to it, `None` if the table is full.
+pub(crate) fn lazy_register(slot: usize, symbol: &str, handle: u64) -> Option<usize> {
+    let symbol = CString::new(symbol).ok()?;
+
+    let mut slots = LAZY_SLOTS.lock().unwrap_or_else(|err| err.into_inner());
+    let index = match slots.iter().position(Option::is_none) {
+        Some(index) => index,
+        None if slots.len() < LAZY_ENTRIES => {
+            slots.push(None);
+            slots.len() - 1
+        }
+        None => return None,
+    };
+
+    slots[index] = Some(LazySlot {
+        slot,
+        symbol,
+        handle,
+    });
+    Some(lazy_table as *const () as usize + index * LAZY_ENTRY_SIZE)
+}
+
+/// Frees the entries of the slots in `start..end`.
+fn lazy_unregister(start: usize, end: usize) {
+    let mut slots = LAZY_SLOTS.lock().unwrap_or_else(|err| err.into_inner());
+    for entry in slots.iter_mut() {
+        if entry
+            .as_ref()
+            .is_some_and(|lazy| (start..end).contains(&lazy.slot))
+        {
+            *entry = None;
+        }
+    }
+}
+
+impl Task {
+    /// Frees the entries of the lazy symbol pointers of the task, for
+    /// later tasks to reuse.
+    pub(crate) fn lazy_shutdown(&mut self) {
+        let start = self.memory.as_ptr().addr();
+        lazy_unregister(start, start + self.memory_size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lazy::{LAZY_SLOTS, lazy_register, lazy_unregister};
+
+    #[test]
+    pub fn test_lazy_bind() {
+        let mut slot = 0u64;
+        let entry = lazy_register((&raw mut slot).addr(), "strlen", libc::RTLD_DEFAULT as u64)
+            .expect("the lazy table is full");
+        slot = entry as u64;
+
+        // call through the slot the way a stub does, the argument has to
+        // make it through the trampoline
+        let call = |slot: &u64| {
+            let len: usize;
+            unsafe {
+                core::arch::asm!(
+                    "blr x16",
+                    in("x16") *slot,
+                    inlateout("x0") c"lazy".as_ptr() => len,
+                    clobber_abi("C"),
+                )
+            };
+            len
+        };
+
+        assert_eq!(call(&slot), 4);
+        assert_eq!(slot as usize, libc::strlen as *const () as usize);
+        assert_eq!(call(&slot), 4);
+    }
+
+    #[test]
+    pub fn test_lazy_unregister() {
+        let slots = [0u64; 2];
+        let (start, end) = (slots.as_ptr().addr(), slots.as_ptr_range().end.addr());
+        for slot in [start, start + 8] {
+            lazy_register(slot, "strlen", libc::RTLD_DEFAULT as u64)
+                .expect("the lazy table is full");
+        }
+
+        // the entries of a task are gone once it shuts down
+        lazy_unregister(start, end);
+        let registered = LAZY_SLOTS.lock().unwrap_or_else(|err| err.into_inner());
+        assert!(
+            registered
+                .iter()
+                .flatten()
+                .all(|lazy| !(start..end).contains(&lazy.slot))
+        );
+    }
+}
@@ -1,16 +1,53 @@
-use std::ptr::NonNull;
+use std::{
+    io,
+    os::fd::RawFd,
+    ptr::{self, NonNull},
+    sync::Mutex,
+};
 
 use goblin::mach::{
     Mach, MachO,
-    cputype::CPU_TYPE_ARM64,
-    load_command::{CommandVariant, DylibCommand, LoadCommand},
+    cputype::{CPU_ARCH_ABI64, CPU_TYPE_ARM64, CPU_TYPE_X86_64},
+    header::Header,
+    load_command::{
+        CommandVariant, DylibCommand, LC_DYLD_CHAINED_FIXUPS, LC_DYLD_INFO, LC_DYLD_INFO_ONLY,
+        LC_DYSYMTAB, LC_ID_DYLIB, LC_LAZY_LOAD_DYLIB, LC_LOAD_DYLIB, LC_LOAD_UPWARD_DYLIB,
+        LC_LOAD_WEAK_DYLIB, LC_MAIN, LC_REEXPORT_DYLIB, LC_RPATH, LC_SEGMENT_64, LC_SOURCE_VERSION,
+        LC_SYMTAB, LC_UNIXTHREAD, LC_UUID, LoadCommand, SIZEOF_SECTION_64,
+        SIZEOF_SEGMENT_COMMAND_64,
+    },
 };
 
-use crate::mach::{copy_from_image, vm_alloc_self, vm_dealloc_self, vm_protect};
+use crate::{
+    error::{AbortReason, DlError, ExecError, LoadError, SecurityError, VmError},
+    mach::{
+        MH_CIGAM, MH_CIGAM_64, MH_EXECUTE, MachHeaderFlags, S_ATTR_PURE_INSTRUCTIONS,
+        S_ATTR_SOME_INSTRUCTIONS, S_CSTRING_LITERALS, S_GB_ZEROFILL, S_INIT_FUNC_OFFSETS,
+        S_MOD_INIT_FUNC_POINTERS, S_MOD_TERM_FUNC_POINTERS, S_THREAD_LOCAL_ZEROFILL, S_ZEROFILL,
+        SECTION_TYPE, try_vm_protect, vm_dealloc_self, vm_page_round, vm_page_size,
+        vm_region_containing_self,
+    },
+    symbols::SymbolCache,
+};
 
+#[cfg(feature = "debugger")]
+mod debugger;
+pub mod demangle;
+mod dyld;
+pub mod error;
+pub mod fixups;
 pub mod jumper;
+#[cfg(feature = "lazy_binding")]
+pub mod lazy;
 pub mod linker;
 pub mod mach;
+pub mod pac;
+pub mod rebase;
+pub mod remote;
+pub mod report;
+pub mod rpath;
+pub mod symbols;
+mod tlv;
 
 /// A mach task_t
 ///
@@ -51,6 +88,39 @@ pub struct Segment {
     initprot: i32,
 }
 
+impl Segment {
+    /// Returns the segment name, without its padding.
+    pub fn name(&self) -> &str {
+        self.name.trim_end_matches('\0')
+    }
+
+    /// Returns true if the segment is mapped executable once protected.
+    ///
+    /// Newer arm64 binaries move their code out of `__TEXT` into a
+    /// separate `__TEXT_EXEC` segment, so the name alone is not enough
+    /// to tell where the code lives.
+    pub fn is_executable(&self) -> bool {
+        self.initprot & libc::VM_PROT_EXECUTE != 0
+    }
+
+    /// Returns the protection the segment is sealed with.
+    ///
+    /// That is its `initprot`, minus write for `SG_READ_ONLY` segments:
+    /// `__DATA_CONST` is declared `rw-` so dyld can bind it, then made
+    /// read-only once binding is done.
+    fn sealed_protection(&self) -> i32 {
+        match self.flags & mach::SG_READ_ONLY {
+            0 => self.initprot,
+            _ => self.initprot & !libc::VM_PROT_WRITE,
+        }
+    }
+
+    /// Returns true if `vm_addr` falls within this segment.
+    fn contains_vm_addr(&self, vm_addr: usize) -> bool {
+        vm_addr >= self.vm_addr && vm_addr < self.vm_addr + self.vm_size
+    }
+}
+
 /// Returns the `vm_prot_t` as a human readable string.
 pub fn vm_prot_into_string(prot: libc::vm_prot_t) -> String {
     format!(
@@ -93,18 +163,107 @@ pub struct Section {
 
     /// section alignment (power of 2)
     align: usize,
+
+    /// for symbol pointer sections, the index of the first entry
+    /// in the indirect symbol table
+    reserved1: u32,
+}
+
+impl Section {
+    /// Returns the section name, without its padding.
+    pub fn name(&self) -> &str {
+        self.name.trim_end_matches('\0')
+    }
+
+    /// Returns the address of the section in the image.
+    pub fn vm_addr(&self) -> usize {
+        self.vm_addr
+    }
+
+    /// Returns the size of the section in memory.
+    pub fn vm_size(&self) -> usize {
+        self.vm_size
+    }
+
+    /// Returns the offset of the section in the file, 0 for zerofill
+    /// sections.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the alignment of the section, as a power of 2.
+    pub fn align(&self) -> usize {
+        self.align
+    }
+
+    /// Returns true if the section holds instructions, as flagged by
+    /// `S_ATTR_PURE_INSTRUCTIONS` or `S_ATTR_SOME_INSTRUCTIONS`.
+    pub fn contains_instructions(&self) -> bool {
+        self.flags & (S_ATTR_PURE_INSTRUCTIONS | S_ATTR_SOME_INSTRUCTIONS) != 0
+    }
+
+    /// Returns true if the section has no file backing and starts
+    /// out zeroed, like `__bss` and `__common`.
+    pub fn is_zerofill(&self) -> bool {
+        matches!(
+            self.flags as i32 & SECTION_TYPE,
+            S_ZEROFILL | S_GB_ZEROFILL | S_THREAD_LOCAL_ZEROFILL
+        )
+    }
 }
 
 #[derive(Debug)]
 /// A wrapper around the tasks address space
+///
+/// # Reentrancy
+///
+/// Every `Task` owns its own allocation, segments and symbol tables.
+/// The only global state is a handful of registries behind a mutex,
+/// with an entry per task: the thread-local templates, the lazy binding
+/// table and the images the dyld stand-ins list. A program that was
+/// itself loaded by this crate can therefore load another one, with
+/// these limitations:
+///
+/// - dylib handles come from `dlopen`, dyld reference counts them, so
+///   nested tasks depending on the same dylib share one copy of it.
+/// - [`jumper::jumper`] exits the process once `main` returns, a nested
+///   load has to use [`Task::run_forked`] to get control back.
 pub struct Task {
-    /// the tasks virtual memory
-    pub memory: NonNull<u8>,
+    /// the tasks virtual memory, owned by the task, see
+    /// [`Task::into_raw`]
+    memory: NonNull<u8>,
 
     pub dylibs: Vec<(String, u64)>,
 
+    /// the `dlopen` flags each of `dylibs` was opened with
+    dylib_flags: Vec<libc::c_int>,
+
+    /// the compatibility version each of `dylibs` was linked against
+    dylib_compat_versions: Vec<u32>,
+
+    /// the dylibs of [`TaskOptions::preload_dylibs`], with their handle
+    preloaded: Vec<(String, u64)>,
+
+    /// the directory of [`TaskOptions::image_path`]
+    loader_dir: Option<std::path::PathBuf>,
+
+    /// the imports of the symbol table and their address, see
+    /// [`Task::symbols_init`]
     pub symbols: Vec<(String, u64)>,
 
+    /// the symbols defined by the image
+    definitions: Vec<symbols::Symbol>,
+
+    /// the symbols of the export trie, with their in-memory address
+    trie_exports: Vec<(String, usize)>,
+
+    /// the symbol table, indexed by the indirect symbol table
+    symtab: Vec<symbols::Nlist>,
+
+    /// the indirect symbol table, one symbol index per entry of
+    /// the symbol pointer and stub sections
+    indirect_symbols: Vec<u32>,
+
     /// the tasks virtual memory size
     memory_size: usize,
 
@@ -113,14 +272,62 @@ pub struct Task {
 
     /// The entry point (as a virtual memory address), 0 if none
     pub entry_point: usize,
+
+    /// true if the entry point is the `start` of an `LC_UNIXTHREAD`,
+    /// rather than the `main` of an `LC_MAIN`
+    unixthread: bool,
+
+    /// the mach header of the loaded image
+    header: Header,
+
+    /// the LC_SOURCE_VERSION of the image, if any
+    source_version: Option<u64>,
+
+    /// the LC_ID_DYLIB name, current and compatibility version, if any
+    dylib_id: Option<(String, u32, u32)>,
+
+    /// the LC_MAIN stacksize, 0 for the default
+    stack_size: u64,
+
+    /// whether the segments were sealed to their `initprot`
+    sealed: bool,
+
+    /// whether the initializers ran, see [`Task::shutdown`]
+    initialized: bool,
+
+    /// the `cmd` of every load command, in order
+    load_commands: Vec<u32>,
+
+    /// the LC_UUID of the image, if any
+    uuid: Option<[u8; 16]>,
+
+    /// the address the image was linked to, see [`preferred_base`]
+    preferred_base: u64,
+
+    /// the `LC_DYLD_CHAINED_FIXUPS` imports table
+    chained_imports: Vec<fixups::ChainedImport>,
+
+    /// the chained pointers left to bind, see [`Task::chained_fixups_init`]
+    chained_binds: Vec<fixups::ChainedBind>,
+
+    /// how flat namespace imports pick between definitions
+    flat_lookup: FlatLookup,
+
+    /// the pthread key of the thread local variables, see [`tlv`]
+    tlv_key: Option<libc::pthread_key_t>,
+
+    /// whether lazy symbol pointers are bound on first call
+    #[cfg(feature = "lazy_binding")]
+    lazy_binding: bool,
+
+    /// the entry registered with the debugger's JIT interface
+    #[cfg(feature = "debugger")]
+    jit_entry: Option<*mut debugger::JitCodeEntry>,
 }
 
 impl Drop for Task {
     fn drop(&mut self) {
-        vm_dealloc_self(
-            self.memory.as_ptr() as libc::mach_vm_address_t,
-            self.memory_size,
-        );
+        self.shutdown();
     }
 }
 
@@ -129,10 +336,486 @@ pub fn get_library_ordinal(n_desc: u32) -> u8 {
     ((n_desc >> 8) & 0xff) as u8
 }
 
+/// Supplies a handle for a dylib `dlopen` could not open, see
+/// [`TaskOptions::on_missing_dylib`]
+pub type MissingDylibHook = Box<dyn Fn(&str) -> Option<*mut libc::c_void>>;
+
+/// Told why the process is about to abort, see [`set_abort_hook`]
+pub type AbortHook = fn(&AbortReason);
+
+/// The hook of [`set_abort_hook`]
+static ABORT_HOOK: Mutex<Option<AbortHook>> = Mutex::new(None);
+
+/// Sets the function told why the loader aborts the process, `None`
+/// to abort silently, which is the default.
+///
+/// Lazy binding and thread local variables run on the threads of the
+/// program, with no caller to return an error to: an unresolved lazy
+/// symbol, or thread local storage that can not be allocated, aborts
+/// the process as it does under dyld. The hook is the last chance to
+/// report it, it may be called while allocations are failing.
+pub fn set_abort_hook(hook: Option<AbortHook>) {
+    *ABORT_HOOK.lock().unwrap_or_else(|err| err.into_inner()) = hook;
+}
+
+/// Reports `reason` to the hook of [`set_abort_hook`] and aborts.
+pub(crate) fn abort_with(reason: AbortReason) -> ! {
+    let hook = *ABORT_HOOK.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(hook) = hook {
+        hook(&reason);
+    }
+    std::process::abort()
+}
+
+/// Options controlling how an image is loaded into a [`Task`]
+#[derive(Default)]
+pub struct TaskOptions {
+    /// A symbol table cached from an earlier load of the same image.
+    ///
+    /// It is only used if its UUID matches the image's `LC_UUID`,
+    /// otherwise the symbol table is read from the image as usual.
+    pub symbol_cache: Option<SymbolCache>,
+
+    /// How the dylibs of the image are opened.
+    pub bind_mode: BindMode,
+
+    /// How imports of a flat namespace image pick between several
+    /// loaded images defining them.
+    pub flat_lookup: FlatLookup,
+
+    /// Dylibs opened before those of the image, like
+    /// `DYLD_INSERT_LIBRARIES`.
+    ///
+    /// They are opened `RTLD_GLOBAL`, in order, and flat namespace
+    /// lookups search them right after the image itself, ahead of every
+    /// other loaded image. Imports of a two-level namespace image name
+    /// their dylib and are not affected.
+    pub preload_dylibs: Vec<std::path::PathBuf>,
+
+    /// Fail the load with [`LoadError::DylibTooOld`] if a dylib is older
+    /// than the image was linked against, see
+    /// [`Task::dylib_version_issues`].
+    pub strict_dylib_versions: bool,
+
+    /// How far loading goes before the task is returned.
+    pub load_mode: LoadMode,
+
+    /// The slice to load, instead of the arm64 one.
+    ///
+    /// A fat binary is searched for it, a thin one has to be it, and the
+    /// load fails with [`LoadError::ArchNotFound`] otherwise. A slice
+    /// for another architecture is mapped all the same, which is only
+    /// good for inspecting it ([`LoadMode::Load`], [`Task::report`]):
+    /// it can not run here.
+    pub prefer_arch: Option<TargetArch>,
+
+    /// Where to place the tasks memory, which is also its
+    /// [slide](Task::slide).
+    ///
+    /// The image is mapped there when the range is free. Images are
+    /// position independent, so when a mapping of the host is in the way
+    /// it is placed anywhere else instead, as without a hint.
+    pub slide_hint: Option<usize>,
+
+    /// Do not map `__LINKEDIT` into the tasks memory.
+    ///
+    /// Symbol tables and dyld info are read from the file while
+    /// loading, the mapped copy is never used once the program runs.
+    pub skip_linkedit: bool,
+
+    /// Read every segment back once copied and compare it with the
+    /// file, failing with [`LoadError::CopyVerificationFailed`].
+    ///
+    /// This costs a second pass over the image.
+    pub verify_copy: bool,
+
+    /// The path the image was read from.
+    ///
+    /// `@loader_path` and `@executable_path` in install names and
+    /// `LC_RPATH`s are relative to its directory, they are skipped when
+    /// it is not known. See [`rpath`].
+    pub image_path: Option<std::path::PathBuf>,
+
+    /// Called with the install name of a required dylib that could not
+    /// be opened.
+    ///
+    /// A missing weak dylib (`LC_LOAD_WEAK_DYLIB`) is skipped without
+    /// asking, as dyld does: its weak imports are bound to null, any
+    /// other import from it fails to link.
+    ///
+    /// A host can return the handle of a substitute (a shim for a
+    /// missing dependency, ...), which is then used in its place. The
+    /// task owns that reference, it is `dlclose`d by [`Task::shutdown`].
+    /// Returning `None` fails the load with [`LoadError::DylibNotFound`].
+    pub on_missing_dylib: Option<MissingDylibHook>,
+
+    /// Bind lazy symbol pointers on their first call rather than while
+    /// linking, see [`lazy`].
+    #[cfg(feature = "lazy_binding")]
+    pub lazy_binding: bool,
+}
+
+impl std::fmt::Debug for TaskOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("TaskOptions");
+        debug
+            .field("symbol_cache", &self.symbol_cache)
+            .field("bind_mode", &self.bind_mode)
+            .field("flat_lookup", &self.flat_lookup)
+            .field("preload_dylibs", &self.preload_dylibs)
+            .field("strict_dylib_versions", &self.strict_dylib_versions)
+            .field("load_mode", &self.load_mode)
+            .field("prefer_arch", &self.prefer_arch)
+            .field("slide_hint", &self.slide_hint)
+            .field("skip_linkedit", &self.skip_linkedit)
+            .field("verify_copy", &self.verify_copy)
+            .field("image_path", &self.image_path)
+            .field(
+                "on_missing_dylib",
+                &self.on_missing_dylib.as_ref().map(|_| ".."),
+            );
+        #[cfg(feature = "lazy_binding")]
+        debug.field("lazy_binding", &self.lazy_binding);
+        debug.finish()
+    }
+}
+
+/// A dylib older than the image was linked against, see
+/// [`Task::dylib_version_issues`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DylibVersionIssue {
+    /// install name of the dylib
+    pub name: String,
+
+    /// the compatibility version the image was linked against
+    pub required: u32,
+
+    /// the current version of the loaded dylib
+    pub current: u32,
+}
+
+/// How far [`Task::with_options`] goes before returning the task
+///
+/// None of the modes runs code of the image:
+///
+/// - [`LoadMode::Load`] maps the image and leaves linking to the caller,
+///   who then runs it with [`Task::prepare`], [`Task::run_initializers`]
+///   and [`Task::jump`].
+/// - [`LoadMode::LinkOnly`] also links and seals it, for fuzzers and
+///   sanitizers that inspect the resolved pointers but must not run the
+///   initializers.
+/// - A report ([`Task::report`], `--dump`) only reads what [`LoadMode::Load`]
+///   mapped, it binds nothing and changes no protection.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LoadMode {
+    /// parse, allocate, copy and rebase
+    #[default]
+    Load,
+
+    /// [`LoadMode::Load`], then bind and seal with [`Task::prepare`]
+    LinkOnly,
+}
+
+/// An architecture of a fat binary slice, see [`TaskOptions::prefer_arch`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetArch {
+    /// `CPU_TYPE_ARM64`, the host
+    Arm64,
+
+    /// `CPU_TYPE_X86_64`
+    X86_64,
+}
+
+impl TargetArch {
+    /// Returns the mach-o cputype of the architecture.
+    pub fn cputype(self) -> u32 {
+        match self {
+            TargetArch::Arm64 => CPU_TYPE_ARM64,
+            TargetArch::X86_64 => CPU_TYPE_X86_64,
+        }
+    }
+
+    /// Returns the architecture named `name` as `lipo` names it.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "arm64" => Some(TargetArch::Arm64),
+            "x86_64" => Some(TargetArch::X86_64),
+            _ => None,
+        }
+    }
+}
+
+/// Which addresses an address of the image is given in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AddressSpace {
+    /// where the image lives in this process
+    #[default]
+    Runtime,
+
+    /// where the image was linked to, as static tools (`otool`,
+    /// `objdump`, disassemblers) show it
+    LinkTime,
+}
+
+/// How flat namespace imports are bound when several loaded images
+/// define the symbol, see [`TaskOptions::flat_lookup`]
+///
+/// Two-level namespace imports are not affected: they are always looked
+/// up in the dylib the image recorded for them first, whatever else is
+/// loaded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FlatLookup {
+    /// the first definition in load order, the image's own exports
+    /// first, as dyld does
+    #[default]
+    FirstWins,
+
+    /// fail with [`error::LinkError::AmbiguousSymbol`] when the
+    /// definitions are not all the same
+    Strict,
+}
+
+/// How the dylibs of an image are opened with `dlopen`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BindMode {
+    /// `RTLD_NOW` or `RTLD_LAZY` depending on the load command, weak and
+    /// lazy dylibs are lazy
+    #[default]
+    PerCommand,
+
+    /// every dylib is `RTLD_NOW`, a missing symbol fails the load
+    Now,
+
+    /// every dylib is `RTLD_LAZY`
+    Lazy,
+}
+
+impl BindMode {
+    /// Returns the `dlopen` flags of a dylib, given the ones its load
+    /// command asks for.
+    pub fn rtld_flags(self, flags: libc::c_int) -> libc::c_int {
+        match self {
+            BindMode::PerCommand => flags,
+            BindMode::Now => (flags & !RTLD_LAZY) | RTLD_NOW,
+            BindMode::Lazy => (flags & !RTLD_NOW) | RTLD_LAZY,
+        }
+    }
+}
+
+impl TaskOptions {
+    /// Returns true if `seg` is not to be mapped into the task.
+    fn is_skipped(&self, seg: &goblin::mach::segment::Segment) -> bool {
+        self.skip_linkedit && matches!(seg.name(), Ok("__LINKEDIT"))
+    }
+}
+
 impl Task {
     /// Creates a task given a pointer and a len
+    ///
+    /// # Panics
+    ///
+    /// If the image can not be loaded, see [`Task::try_with_pointer`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` has to be valid for reads of `len` bytes, which are not
+    /// written to until the function returns. The task keeps its own
+    /// copy of the image, the bytes can be dropped afterwards.
     pub unsafe fn with_pointer(ptr: *const u8, len: usize) -> Self {
-        unsafe { task_init(ptr, len) }
+        unsafe { Self::with_options(ptr, len, &TaskOptions::default()) }
+    }
+
+    /// Same as [`Task::with_pointer`], reusing the symbol table of an
+    /// earlier load of the same image, see [`Task::symbol_cache`].
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Task::with_pointer`].
+    pub unsafe fn with_symbol_cache(ptr: *const u8, len: usize, cache: SymbolCache) -> Self {
+        let options = TaskOptions {
+            symbol_cache: Some(cache),
+            ..Default::default()
+        };
+        unsafe { Self::with_options(ptr, len, &options) }
+    }
+
+    /// Same as [`Task::with_pointer`], loading according to `options`.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Task::with_pointer`].
+    pub unsafe fn with_options(ptr: *const u8, len: usize, options: &TaskOptions) -> Self {
+        unsafe { task_init(ptr, len, options) }
+            .unwrap_or_else(|err| panic!("failed to load image: {err}"))
+    }
+
+    /// Creates a task given a pointer and a len, returning an error
+    /// if the image is malformed.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Task::with_pointer`].
+    pub unsafe fn try_with_pointer(ptr: *const u8, len: usize) -> Result<Self, LoadError> {
+        unsafe { Self::try_with_options(ptr, len, &TaskOptions::default()) }
+    }
+
+    /// Same as [`Task::try_with_pointer`], loading according to `options`.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Task::with_pointer`].
+    pub unsafe fn try_with_options(
+        ptr: *const u8,
+        len: usize,
+        options: &TaskOptions,
+    ) -> Result<Self, LoadError> {
+        unsafe { task_init(ptr, len, options) }
+    }
+
+    /// Creates a task from an image the caller already parsed.
+    ///
+    /// `image` has to be the bytes `macho` was parsed from (the slice
+    /// of the architecture, for a fat file).
+    pub fn from_macho(macho: &MachO, image: &[u8]) -> Result<Self, LoadError> {
+        Self::from_macho_with_options(macho, image, &TaskOptions::default())
+    }
+
+    /// Same as [`Task::from_macho`], loading according to `options`.
+    pub fn from_macho_with_options(
+        macho: &MachO,
+        image: &[u8],
+        options: &TaskOptions,
+    ) -> Result<Self, LoadError> {
+        task_init_macho(macho, image, options)
+    }
+
+    /// Creates a task from the image behind an open file descriptor.
+    ///
+    /// The image is mapped straight from `fd`, it never needs a path
+    /// that could be swapped between being written and being loaded.
+    /// The mapping is released once the task is built, the task holds
+    /// its own copy of every segment.
+    pub fn from_fd(fd: RawFd) -> Result<Self, LoadError> {
+        let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+        if unsafe { libc::fstat(fd, stat.as_mut_ptr()) } == -1 {
+            return Err(LoadError::Io(io::Error::last_os_error()));
+        }
+        let len = unsafe { stat.assume_init() }.st_size as usize;
+
+        if len == 0 {
+            return Err(LoadError::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "image is empty",
+            )));
+        }
+
+        let image = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                fd,
+                0,
+            )
+        };
+        if image == libc::MAP_FAILED {
+            return Err(LoadError::Io(io::Error::last_os_error()));
+        }
+
+        let task = unsafe { Self::try_with_pointer(image as *const u8, len) };
+
+        unsafe { libc::munmap(image, len) };
+        task
+    }
+
+    /// Returns the page size used to lay out the task.
+    pub fn page_size(&self) -> usize {
+        vm_page_size()
+    }
+
+    /// Returns the segment mapped at `vm_addr`, if any.
+    ///
+    /// `vm_addr` is relative to the start of the tasks memory.
+    pub fn segment_at(&self, vm_addr: usize) -> Option<&Segment> {
+        self.segments
+            .iter()
+            .find(|segment| segment.contains_vm_addr(vm_addr))
+    }
+
+    /// Returns the contents of the section `segname,sectname`, as
+    /// mapped in the tasks memory.
+    ///
+    /// Sections of a skipped segment have no contents.
+    pub fn section_data(&self, segname: &str, sectname: &str) -> Option<&[u8]> {
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| segment.name.trim_end_matches('\0') == segname)?;
+
+        let section = segment
+            .sections
+            .iter()
+            .find(|section| section.name.trim_end_matches('\0') == sectname)?;
+
+        if section.vm_size == 0 || !segment.contains_vm_addr(section.vm_addr) {
+            return None;
+        }
+
+        Some(unsafe {
+            core::slice::from_raw_parts(self.memory.add(section.vm_addr).as_ptr(), section.vm_size)
+        })
+    }
+
+    /// Returns the raw `Info.plist` embedded in `__TEXT,__info_plist`.
+    pub fn info_plist(&self) -> Option<&[u8]> {
+        self.section_data("__TEXT", "__info_plist")
+    }
+
+    /// Returns the compact unwind information in `__TEXT,__unwind_info`.
+    ///
+    /// Backtraces and exceptions through the image need it, its
+    /// function offsets are relative to the mach_header. The system
+    /// unwinder does not know about the image, a debugger or crash
+    /// reporter has to hand it over itself.
+    pub fn unwind_info(&self) -> Option<&[u8]> {
+        self.section_data("__TEXT", "__unwind_info")
+    }
+
+    /// Returns the C string literals of the image, from every
+    /// `S_CSTRING_LITERALS` section (`__TEXT,__cstring`, ...), in order.
+    ///
+    /// Strings that are not valid UTF-8 are skipped, and so is a last
+    /// one missing its nul terminator.
+    pub fn cstrings(&self) -> Vec<&str> {
+        let mut cstrings = Vec::new();
+
+        for segment in &self.segments {
+            for section in &segment.sections {
+                if section.flags as i32 & SECTION_TYPE != S_CSTRING_LITERALS
+                    || section.vm_size == 0
+                    || !segment.contains_vm_addr(section.vm_addr)
+                {
+                    continue;
+                }
+
+                let data = unsafe {
+                    core::slice::from_raw_parts(
+                        self.memory.add(section.vm_addr).as_ptr(),
+                        section.vm_size,
+                    )
+                };
+                let Some(end) = data.iter().rposition(|byte| *byte == 0) else {
+                    continue;
+                };
+                cstrings.extend(
+                    data[..end]
+                        .split(|byte| *byte == 0)
+                        .filter_map(|string| std::str::from_utf8(string).ok()),
+                );
+            }
+        }
+        cstrings
     }
 }
 
@@ -140,49 +823,377 @@ const RTLD_LAZY: libc::c_int = 0x1;
 const RTLD_NOW: libc::c_int = 0x2;
 const RTLD_LOCAL: libc::c_int = 0x4;
 const RTLD_GLOBAL: libc::c_int = 0x8; // rarely correct to use
+const RTLD_NOLOAD: libc::c_int = 0x10;
+const RTLD_NODELETE: libc::c_int = 0x80;
+const RTLD_FIRST: libc::c_int = 0x100;
+
+/// Returns the `dlopen` flags as a `|` separated list of names.
+pub fn rtld_flags_to_string(flags: libc::c_int) -> String {
+    let mut names = [
+        (RTLD_LAZY, "RTLD_LAZY"),
+        (RTLD_NOW, "RTLD_NOW"),
+        (RTLD_LOCAL, "RTLD_LOCAL"),
+        (RTLD_GLOBAL, "RTLD_GLOBAL"),
+        (RTLD_NOLOAD, "RTLD_NOLOAD"),
+        (RTLD_NODELETE, "RTLD_NODELETE"),
+        (RTLD_FIRST, "RTLD_FIRST"),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| flags & flag != 0)
+    .map(|(_, name)| name.to_string())
+    .collect::<Vec<_>>();
+
+    let unknown = flags
+        & !(RTLD_LAZY
+            | RTLD_NOW
+            | RTLD_LOCAL
+            | RTLD_GLOBAL
+            | RTLD_NOLOAD
+            | RTLD_NODELETE
+            | RTLD_FIRST);
+    if unknown != 0 {
+        names.push(format!("{unknown:#x}"));
+    }
+
+    match names.is_empty() {
+        true => "0".to_string(),
+        false => names.join("|"),
+    }
+}
+
+/// Returns the last `dlerror` message.
+fn dlerror_string() -> String {
+    let err = unsafe { libc::dlerror() };
+    if err.is_null() {
+        return "no error message".to_string();
+    }
+
+    unsafe { std::ffi::CStr::from_ptr(err) }
+        .to_string_lossy()
+        .into_owned()
+}
 
 impl Task {
+    /// Makes every segment as accessible as its `maxprot` allows, minus
+    /// execute, so relocations and binds can be written.
+    ///
+    /// Segments such as `__DATA_CONST` are declared read-only in their
+    /// `initprot` but still have to be written to by the linker. The
+    /// expected ordering is:
+    ///
+    /// 1. [`Task::segments_protect_maxprot`] (writable)
+    /// 2. relocate and bind (e.g. [`linker::Linker::link_raw`])
+    /// 3. [`Task::segments_protect`] (sealed to `initprot`)
+    pub fn segments_protect_maxprot(&mut self) {
+        self.try_segments_protect_maxprot()
+            .unwrap_or_else(|err| panic!("failed to unprotect segments: {err}"));
+    }
+
+    /// Same as [`Task::segments_protect_maxprot`], returning the error
+    /// instead of panicking.
+    pub fn try_segments_protect_maxprot(&mut self) -> Result<(), VmError> {
+        for segment in self.protected_segments() {
+            try_vm_protect(
+                unsafe { self.memory.add(segment.vm_addr).as_ptr().addr() as u64 },
+                vm_page_round(segment.size),
+                false as i32,
+                segment.maxprot & !libc::VM_PROT_EXECUTE,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the segments that occupy memory, zero sized ones
+    /// (linker padding, skipped segments) have no pages to protect.
+    fn mapped_segments(&self) -> impl Iterator<Item = &Segment> {
+        self.segments.iter().filter(|segment| segment.vm_size != 0)
+    }
+
+    /// Returns the segments whose protection is set with
+    /// `mach_vm_protect`.
+    ///
+    /// Under the `jit` feature the executable segments are `MAP_JIT`
+    /// memory, which stays rwx and is only switched between writable and
+    /// executable per thread, see [`mach::jit_write_protect`].
+    fn protected_segments(&self) -> impl Iterator<Item = &Segment> {
+        self.mapped_segments()
+            .filter(|segment| !(cfg!(feature = "jit") && segment.is_executable()))
+    }
+
     /// Applies memory protection to all segments in the address space.
+    ///
+    /// This seals the segments to their `initprot`, `SG_READ_ONLY` ones
+    /// read-only, it has to run after all relocations and binds were
+    /// written.
     pub fn segments_protect(&mut self) {
-        self.segments.iter().for_each(|segment| unsafe {
-            [false, true].into_iter().for_each(|max| {
-                vm_protect(
-                    self.memory.offset(segment.vm_addr as isize).as_ptr().addr() as u64,
-                    segment.size,
+        self.try_segments_protect()
+            .unwrap_or_else(|err| panic!("failed to protect segments: {err}"));
+    }
+
+    /// Same as [`Task::segments_protect`], returning the error instead
+    /// of panicking.
+    pub fn try_segments_protect(&mut self) -> Result<(), VmError> {
+        for segment in self.protected_segments() {
+            for max in [false, true] {
+                try_vm_protect(
+                    unsafe { self.memory.add(segment.vm_addr).as_ptr().addr() as u64 },
+                    vm_page_round(segment.size),
                     max as i32,
-                    segment.initprot,
-                )
+                    segment.sealed_protection(),
+                )?;
+            }
+        }
+
+        self.sealed = true;
+        Ok(())
+    }
+
+    /// Makes the segments that were sealed read-only, but may be written
+    /// (`__DATA_CONST`), writable again.
+    ///
+    /// Those are the segments declared read-only with a writable
+    /// `maxprot`, and the `SG_READ_ONLY` ones. Sealing lowered their
+    /// maximum protection as well. `VM_PROT_COPY`
+    /// gives them a private copy that can be written anyway, the way
+    /// debuggers patch read-only pages. [`Task::try_segments_protect`]
+    /// seals them again.
+    pub(crate) fn try_segments_unseal(&mut self) -> Result<(), VmError> {
+        for segment in self.protected_segments() {
+            if segment.sealed_protection() & libc::VM_PROT_WRITE != 0
+                || segment.maxprot & libc::VM_PROT_WRITE == 0
+            {
+                continue;
+            }
+
+            try_vm_protect(
+                unsafe { self.memory.add(segment.vm_addr).as_ptr().addr() as u64 },
+                vm_page_round(segment.size),
+                false as i32,
+                segment.sealed_protection() | libc::VM_PROT_WRITE | mach::VM_PROT_COPY,
+            )?;
+        }
+
+        self.sealed = false;
+        Ok(())
+    }
+
+    /// Overwrites the start of the segment `name` with `new_bytes`, in
+    /// place.
+    ///
+    /// The rest of the task is left as it is, which makes patching a
+    /// function and running it again cheap. The segment gets a private
+    /// writable copy for the write (`VM_PROT_COPY`, so sealed segments
+    /// are patched too), then its protection is restored and, for code,
+    /// the instruction cache is discarded. `new_bytes` is written as is:
+    /// pointers in it are neither rebased nor bound.
+    ///
+    /// Under the `jit` feature, executable segments are written by lifting
+    /// the write protection of the calling thread instead.
+    pub fn reload_segment(&mut self, name: &str, new_bytes: &[u8]) -> Result<(), VmError> {
+        let segment = self
+            .mapped_segments()
+            .find(|segment| segment.name.trim_end_matches('\0') == name)
+            .ok_or_else(|| VmError::UnknownSegment {
+                name: name.to_string(),
+            })?;
+
+        if new_bytes.len() > segment.vm_size {
+            return Err(VmError::SegmentOverflow {
+                segment: name.to_string(),
+                size: new_bytes.len() as u64,
+                vm_size: segment.vm_size as u64,
             });
-        });
+        }
+        if new_bytes.is_empty() {
+            return Ok(());
+        }
+
+        let address = unsafe { self.memory.add(segment.vm_addr).as_ptr().addr() as u64 };
+
+        #[cfg(feature = "jit")]
+        if segment.is_executable() {
+            mach::jit_write_protect(false);
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    new_bytes.as_ptr(),
+                    address as *mut u8,
+                    new_bytes.len(),
+                );
+            }
+            mach::jit_write_protect(true);
+            unsafe { mach::sys_icache_invalidate(address as *mut libc::c_void, new_bytes.len()) };
+            return Ok(());
+        }
+
+        let size = vm_page_round(new_bytes.len());
+        let protection = vm_region_containing_self(address)?.protection;
+
+        // never writable and executable at once, the code is only run
+        // again once restored
+        try_vm_protect(
+            address,
+            size,
+            false as i32,
+            libc::VM_PROT_READ | libc::VM_PROT_WRITE | mach::VM_PROT_COPY,
+        )?;
+        let written =
+            mach::try_copy_from_image(new_bytes.as_ptr().addr() as u64, address, new_bytes.len());
+        try_vm_protect(address, size, false as i32, protection)?;
+        written?;
+
+        if protection & libc::VM_PROT_EXECUTE != 0 {
+            unsafe { mach::sys_icache_invalidate(address as *mut libc::c_void, new_bytes.len()) };
+        }
+        Ok(())
     }
 
-    pub fn symbols_init(&mut self, macho: &MachO) {
-        let mut symbols = Vec::<(String, u64)>::new();
-        for symbol in macho.symbols() {
-            let (name, nlist) = symbol.unwrap();
+    /// Runs every step between loading and jumping: unprotect, bind,
+    /// seal and check the protections.
+    ///
+    /// Each failure is returned with the step it happened in, so a
+    /// bad image can be told from a kernel refusing a protection.
+    pub fn prepare(&mut self) -> Result<(), ExecError> {
+        self.try_segments_protect_maxprot()
+            .map_err(ExecError::Unprotect)?;
+        self.resolve_all_imports().map_err(ExecError::Link)?;
+        self.try_segments_protect().map_err(ExecError::Protect)?;
+
+        // the image is sealed, the loading thread may execute it now
+        #[cfg(feature = "jit")]
+        mach::jit_write_protect(true);
+
+        self.assert_protection().map_err(ExecError::ProtectionCheck)
+    }
 
-            /* NLIST_TYPE_LOCAL */
-            if nlist.n_type == 1 {
-                // remove the trailing '_'
-                let lib = get_library_ordinal(nlist.n_desc as u32);
-                let (_, lib_handle) = &self.dylibs[(lib - 1) as usize];
-                let name = &name[1..name.len()];
-                let new_pointer = unsafe {
-                    libc::dlsym(*lib_handle as *mut libc::c_void, name.as_ptr() as *const i8)
-                };
+    /// Checks that every segment has the protection it was sealed with.
+    ///
+    /// Returns the first mismatch, leaving it to the caller whether
+    /// that is fatal.
+    pub fn assert_protection(&self) -> Result<(), VmError> {
+        for segment in self.protected_segments() {
+            if segment.size == 0 {
+                continue;
+            }
+
+            let address = unsafe { self.memory.add(segment.vm_addr).as_ptr().addr() as u64 };
+            let region = vm_region_containing_self(address)?;
+
+            if region.protection != segment.sealed_protection() {
+                return Err(VmError::ProtectionMismatch {
+                    address,
+                    expected: segment.sealed_protection(),
+                    actual: region.protection,
+                });
+            }
+        }
+        Ok(())
+    }
 
-                if new_pointer.is_null() {
-                    panic!("failed to init: {name}");
+    /// Checks that no page of the segments is both writable and
+    /// executable, as the kernel reports them.
+    ///
+    /// Unlike [`Task::assert_protection`] this does not trust the
+    /// `initprot` of the image: a `rwx` segment, or a page left writable
+    /// by a fixup, is an error either way. Returns the first offending
+    /// region with its segment.
+    pub fn assert_wx_invariant(&self) -> Result<(), SecurityError> {
+        for segment in self.protected_segments() {
+            let mut address = unsafe { self.memory.add(segment.vm_addr).as_ptr().addr() as u64 };
+            let end = address + vm_page_round(segment.size) as u64;
+
+            // a segment spans several regions once part of it was
+            // reprotected
+            while address < end {
+                let region = vm_region_containing_self(address).map_err(SecurityError::Vm)?;
+                let wx = libc::VM_PROT_WRITE | libc::VM_PROT_EXECUTE;
+
+                if region.protection & wx == wx {
+                    return Err(SecurityError::WritableAndExecutable {
+                        segment: segment.name.trim_end_matches('\0').to_string(),
+                        address,
+                        protection: region.protection,
+                    });
                 }
+                address = region.address + region.size;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the name, current and maximum protection of every
+    /// segment, as the kernel reports them.
+    ///
+    /// Nothing is changed, comparing this with the `initprot` and
+    /// `maxprot` in [`Task::report`] tells whether a protection step
+    /// did not apply or something else changed it afterwards.
+    pub fn report_protections(
+        &self,
+    ) -> Result<Vec<(String, libc::vm_prot_t, libc::vm_prot_t)>, VmError> {
+        self.mapped_segments()
+            .map(|segment| {
+                let address = unsafe { self.memory.add(segment.vm_addr).as_ptr().addr() as u64 };
+                let region = vm_region_containing_self(address)?;
+
+                Ok((
+                    segment.name.trim_end_matches('\0').to_string(),
+                    region.protection,
+                    region.max_protection,
+                ))
+            })
+            .collect()
+    }
+
+    pub fn dylibs_search(&mut self, macho: &MachO, base_addr: &[u8]) -> Result<(), LoadError> {
+        self.dylibs_search_with_mode(macho, base_addr, BindMode::PerCommand)
+    }
+
+    /// Same as [`Task::dylibs_search`], opening the dylibs according to
+    /// `bind_mode`.
+    pub fn dylibs_search_with_mode(
+        &mut self,
+        macho: &MachO,
+        base_addr: &[u8],
+        bind_mode: BindMode,
+    ) -> Result<(), LoadError> {
+        self.dylibs_open(macho, base_addr, bind_mode, None)
+    }
 
-                symbols.push((name.to_string(), new_pointer.addr() as u64));
+    /// Opens the dylibs of [`TaskOptions::preload_dylibs`].
+    fn preload_dylibs_open(&mut self, paths: &[std::path::PathBuf]) -> Result<(), LoadError> {
+        for path in paths {
+            let name = path.display().to_string();
+            let handle = std::ffi::CString::new(name.as_str())
+                .map(|path| unsafe { libc::dlopen(path.as_ptr(), RTLD_NOW | RTLD_GLOBAL) })
+                .unwrap_or(ptr::null_mut());
+
+            if handle.is_null() {
+                return Err(LoadError::DylibNotFound {
+                    error: DlError(format!(
+                        "{} ({})",
+                        dlerror_string(),
+                        rtld_flags_to_string(RTLD_NOW | RTLD_GLOBAL)
+                    )),
+                    name,
+                });
             }
+            self.preloaded.push((name, handle.addr() as u64));
         }
-        self.symbols = symbols;
+        Ok(())
     }
 
-    pub fn dylibs_search(&mut self, macho: &MachO, base_addr: &[u8]) {
+    /// Opens the dylibs of the image, asking `on_missing` for those
+    /// that can not be found.
+    fn dylibs_open(
+        &mut self,
+        macho: &MachO,
+        base_addr: &[u8],
+        bind_mode: BindMode,
+        on_missing: Option<&MissingDylibHook>,
+    ) -> Result<(), LoadError> {
         let mut dylibs: Vec<(String, u64)> = Vec::new();
+        let mut dylib_flags = Vec::new();
+        let mut dylib_compat_versions = Vec::new();
+        let rpaths = rpath::rpaths_from_macho(macho, base_addr)?;
 
         for LoadCommand {
             offset: load_command_offset,
@@ -191,12 +1202,12 @@ impl Task {
         } in &macho.load_commands
         {
             match command {
-                CommandVariant::LoadDylib(DylibCommand { dylib, .. })
-                | CommandVariant::LoadUpwardDylib(DylibCommand { dylib, .. })
-                | CommandVariant::ReexportDylib(DylibCommand { dylib, .. })
-                | CommandVariant::LoadWeakDylib(DylibCommand { dylib, .. })
-                | CommandVariant::LazyLoadDylib(DylibCommand { dylib, .. }) => {
-                    let (flags, _is_weak) = match command {
+                CommandVariant::LoadDylib(DylibCommand { dylib, cmdsize, .. })
+                | CommandVariant::LoadUpwardDylib(DylibCommand { dylib, cmdsize, .. })
+                | CommandVariant::ReexportDylib(DylibCommand { dylib, cmdsize, .. })
+                | CommandVariant::LoadWeakDylib(DylibCommand { dylib, cmdsize, .. })
+                | CommandVariant::LazyLoadDylib(DylibCommand { dylib, cmdsize, .. }) => {
+                    let (flags, is_weak) = match command {
                         CommandVariant::LazyLoadDylib(_) => (RTLD_LAZY | RTLD_LOCAL, false),
                         CommandVariant::LoadWeakDylib(_) => (RTLD_LAZY | RTLD_LOCAL, true),
                         CommandVariant::ReexportDylib(_) => (RTLD_NOW | RTLD_LOCAL, false),
@@ -205,117 +1216,1027 @@ impl Task {
 
                         _ => unreachable!(),
                     };
-
-                    let name_offset = dylib.name as usize;
-                    let dylib_name_ptr = unsafe {
-                        base_addr
-                            .as_ptr()
-                            .add(*load_command_offset)
-                            .add(name_offset) as *const libc::c_char
-                    };
-                    let name = unsafe {
-                        std::ffi::CStr::from_ptr(dylib_name_ptr)
-                            .to_str()
-                            .unwrap_or("<invalid utf8>")
+                    let flags = bind_mode.rtld_flags(flags);
+
+                    let dylib_name = dylib_name_from_command(
+                        base_addr,
+                        *load_command_offset,
+                        *cmdsize as usize,
+                        dylib.name as usize,
+                    )?;
+                    let name = dylib_name.to_str().unwrap_or("<invalid utf8>");
+
+                    // the first path of the search order that opens
+                    let handle =
+                        rpath::dylib_search_paths(name, &rpaths, self.loader_dir.as_deref())
+                            .into_iter()
+                            .filter_map(|path| std::ffi::CString::new(path).ok())
+                            .map(|path| unsafe { libc::dlopen(path.as_ptr(), flags) })
+                            .find(|handle| !handle.is_null())
+                            .unwrap_or(ptr::null_mut());
+                    let handle = match handle.is_null() {
+                        false => handle,
+                        // recorded without a handle, see Task::ordinal_target
+                        true if is_weak => ptr::null_mut(),
+                        true => {
+                            let error = DlError(format!(
+                                "{} ({})",
+                                dlerror_string(),
+                                rtld_flags_to_string(flags)
+                            ));
+                            on_missing
+                                .and_then(|on_missing| on_missing(name))
+                                .filter(|handle| !handle.is_null())
+                                .ok_or_else(|| LoadError::DylibNotFound {
+                                    name: name.to_string(),
+                                    error,
+                                })?
+                        }
                     };
 
-                    let handle = unsafe { libc::dlopen(dylib_name_ptr, flags) };
-                    if handle.is_null() {
-                        panic!("failed to load dylib @ {handle:?}");
-                    }
-
                     dylibs.push((name.to_string(), handle.addr() as u64));
+                    dylib_flags.push(flags);
+                    dylib_compat_versions.push(dylib.compatibility_version);
                 }
 
                 _ => continue,
             };
         }
         self.dylibs = dylibs;
+        self.dylib_flags = dylib_flags;
+        self.dylib_compat_versions = dylib_compat_versions;
+        Ok(())
     }
-}
 
-/// Initialize the [`Task`] struct from a pointer and
-/// a len.
-unsafe fn task_init(ptr: *const u8, len: usize) -> Task {
-    if ptr.is_null() {
-        panic!("image pointer is null");
+    /// Returns the install name and `dlopen` handle of every dylib the
+    /// image was linked against, in load command order.
+    ///
+    /// The handles stay valid as long as the task lives. A weak dylib
+    /// that was not found has a null handle.
+    pub fn loaded_dylibs(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.dylibs
+            .iter()
+            .map(|(name, handle)| (name.as_str(), *handle as usize))
     }
 
-    if len == 0 {
-        panic!("image is empty");
+    /// Returns the `dlopen` handle of the dylib `name`, to `dlsym` in
+    /// that dylib only.
+    ///
+    /// `name` is either the install name (`/usr/lib/libSystem.B.dylib`)
+    /// or its file name (`libSystem.B.dylib`).
+    pub fn dylib_handle(&self, name: &str) -> Option<*mut libc::c_void> {
+        self.loaded_dylibs()
+            .find(|(install_name, _)| {
+                *install_name == name || install_name.rsplit('/').next() == Some(name)
+            })
+            .filter(|(_, handle)| *handle != 0)
+            .map(|(_, handle)| handle as *mut libc::c_void)
     }
 
-    if len > 100_000_000 {
-        panic!("loaded image is too large");
+    /// Returns the dylibs that are older than the image was linked
+    /// against, in load command order.
+    ///
+    /// A dylib is too old when the current version of its `LC_ID_DYLIB`
+    /// is below the compatibility version the image recorded for it.
+    /// The version is read from the loaded dylib, which may live in the
+    /// shared cache rather than on disk. Dylibs dyld does not know about
+    /// (substitutes from [`TaskOptions::on_missing_dylib`] that are not
+    /// images) are not checked.
+    ///
+    /// See [`TaskOptions::strict_dylib_versions`] to fail the load instead.
+    pub fn dylib_version_issues(&self) -> Vec<DylibVersionIssue> {
+        self.dylibs
+            .iter()
+            .zip(&self.dylib_compat_versions)
+            .filter_map(|((name, handle), required)| {
+                let current = loaded_dylib_current_version(*handle as *mut libc::c_void)?;
+                (current < *required).then(|| DylibVersionIssue {
+                    name: name.clone(),
+                    required: *required,
+                    current,
+                })
+            })
+            .collect()
     }
 
-    let image = unsafe { core::slice::from_raw_parts(ptr, len) };
-
-    match Mach::parse(image) {
-        Ok(Mach::Binary(macho)) => {
-            // We only support 64-bit mach-o files
-            if !macho.is_64 {
-                panic!("malforormed mach-o: only 64 bit targets are supported");
+    /// Records the version information of the image.
+    pub fn versions_init(&mut self, macho: &MachO, image: &[u8]) -> Result<(), LoadError> {
+        for LoadCommand {
+            offset, command, ..
+        } in &macho.load_commands
+        {
+            match command {
+                CommandVariant::SourceVersion(version) => {
+                    self.source_version = Some(version.version);
+                }
+                CommandVariant::IdDylib(DylibCommand { dylib, cmdsize, .. }) => {
+                    let name = dylib_name_from_command(
+                        image,
+                        *offset,
+                        *cmdsize as usize,
+                        dylib.name as usize,
+                    )?;
+
+                    self.dylib_id = Some((
+                        name.to_str().unwrap_or("<invalid utf8>").to_string(),
+                        dylib.current_version,
+                        dylib.compatibility_version,
+                    ));
+                }
+                _ => continue,
             }
-
-            // Initialize the actual task now
-            let mut task = task_init_from_macho(&macho, image);
-
-            task.dylibs_search(&macho, image);
-
-            task.symbols_init(&macho);
-
-            task
         }
-        Ok(Mach::Fat(multi_arch)) => {
-            let arch = multi_arch
-                .find_cputype(CPU_TYPE_ARM64)
-                .unwrap()
-                .expect("loaded image does not contain a usable architecture");
+        Ok(())
+    }
 
-            // extract the CPU_TYPE_ARM64 architecture
-            let image = arch.slice(image);
+    /// Returns the `LC_SOURCE_VERSION` of the image, packed as
+    /// `A.B.C.D.E` in 24.10.10.10.10 bits.
+    pub fn source_version(&self) -> Option<u64> {
+        self.source_version
+    }
 
-            // Recurse on the extracted architecture
-            unsafe { task_init(image.as_ptr(), image.len()) }
-        }
-        Err(_) => panic!("loaded image pointer is too large"),
+    /// Returns the install name, current version and compatibility
+    /// version of a dylib, from its `LC_ID_DYLIB`.
+    pub fn dylib_id(&self) -> Option<(String, u32, u32)> {
+        self.dylib_id.clone()
     }
-}
 
-/// Initializes a task given a parsed MachO.
-///
-/// `image` is supposed to hold the slice within
-/// the initial file that corresponds to this parsed
-/// `macho`.
-fn task_init_from_macho(macho: &MachO<'_>, image: &[u8]) -> Task {
-    // determine the lowest virtual address (min_addr) and the highest
-    // virtual address (max_addr) occupied by any segment. The total size is
-    // then calculated as the difference.
-    let vm_size = {
-        let min_addr = macho
-            .segments
+    /// Returns the `cmd` of every load command the loader saw but did
+    /// nothing with, in order.
+    ///
+    /// See [`report::load_command_into_str`] for names.
+    pub fn unhandled_load_commands(&self) -> Vec<u32> {
+        self.load_commands
             .iter()
-            .map(|seg| seg.vmaddr)
-            .min()
-            .unwrap_or(0) as usize;
+            .copied()
+            .filter(|cmd| !HANDLED_LOAD_COMMANDS.contains(cmd))
+            .collect()
+    }
 
-        let max_addr = macho
-            .segments
+    /// Returns the sections that hold instructions and are mapped
+    /// executable once sealed.
+    ///
+    /// Anything executable that is not in there, or a section in there
+    /// that is also writable, is worth a second look in a W^X audit.
+    pub fn executable_sections(&self) -> Vec<&Section> {
+        self.segments
             .iter()
-            .map(|seg| (seg.vmaddr + seg.vmsize) as usize)
-            .max()
-            .unwrap_or(0);
+            .filter(|segment| segment.is_executable())
+            .flat_map(|segment| &segment.sections)
+            .filter(|section| section.contains_instructions())
+            .collect()
+    }
 
-        max_addr.saturating_sub(min_addr)
-    };
+    /// Returns the address the image was linked to, see
+    /// [`preferred_base`].
+    ///
+    /// The image itself lives at `slide() + preferred_base()`.
+    pub fn preferred_base(&self) -> u64 {
+        self.preferred_base
+    }
 
-    let entry_point = macho.entry as usize;
+    /// Returns the decoded mach_header `flags` of the image.
+    pub fn header_flags(&self) -> MachHeaderFlags {
+        MachHeaderFlags(self.header.flags)
+    }
+
+    /// Returns true if the image is arm64e, and expects its
+    /// `__auth_got` slots to hold signed pointers.
+    pub fn is_arm64e(&self) -> bool {
+        self.header.cpusubtype & !mach::CPU_SUBTYPE_MASK == mach::CPU_SUBTYPE_ARM64E
+    }
+
+    /// Returns the `LC_UUID` of the image.
+    pub fn uuid(&self) -> Option<[u8; 16]> {
+        self.uuid
+    }
+
+    /// Returns the address `main` (or `start`) is at in memory, `None` if
+    /// the image has no entry point.
+    ///
+    /// This is the address the program is started at, see
+    /// [`jumper::entry_address`].
+    pub fn entry_address(&self) -> Option<usize> {
+        self.has_entry_point()
+            .then(|| jumper::entry_address(self.memory, self.entry_point))
+    }
+
+    /// Returns how control is handed to the program, `None` if the
+    /// image has no entry point.
+    pub fn entry(&self) -> Option<jumper::EntryPoint> {
+        match (self.entry_point, self.unixthread) {
+            (0, _) => None,
+            (entry_point, false) => Some(jumper::EntryPoint::Main(entry_point)),
+            (entry_point, true) => Some(jumper::EntryPoint::Start(entry_point)),
+        }
+    }
+
+    /// Returns true if the image has an entry point to jump to.
+    ///
+    /// Dylibs and bundles have none, they are only used through
+    /// [`Task::symbol_address`] and [`Task::call_symbol`].
+    pub fn has_entry_point(&self) -> bool {
+        self.entry_point != 0
+    }
+
+    /// Returns the address of the mapped mach_header, that is of the
+    /// segment mapping the start of the file.
+    pub(crate) fn header_address(&self) -> Option<usize> {
+        self.segments
+            .iter()
+            .find(|segment| segment.offset == 0 && segment.size != 0)
+            .map(|segment| unsafe { self.memory.add(segment.vm_addr).as_ptr().addr() })
+    }
+
+    /// Reads the function pointer at the virtual address `vm_addr` of the
+    /// image, a callback table in `__DATA` say, and returns an address
+    /// that can be called.
+    ///
+    /// Once rebased, a plain pointer already holds the slid address. A
+    /// signed one is authenticated with [`pac::PacKey::InstructionA`] and
+    /// a zero discriminator, how arm64e signs C function pointers stored
+    /// in data. Pointers signed with another discriminator (C++ vtables,
+    /// `__ptrauth` qualified fields) have to be authenticated with
+    /// [`pac::PacKey::authenticate`] by the caller.
+    ///
+    /// # Panics
+    ///
+    /// If the pointer does not lie within the tasks memory.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`pac::PacKey::authenticate`], a signed pointer has to be
+    /// signed as described above.
+    pub unsafe fn read_code_pointer(&self, vm_addr: usize) -> usize {
+        assert!(
+            vm_addr
+                .checked_add(size_of::<u64>())
+                .is_some_and(|end| end <= self.memory_size),
+            "code pointer {vm_addr:#x} is outside of the image"
+        );
+
+        let ptr = unsafe { (self.memory.add(vm_addr).as_ptr() as *const u64).read_unaligned() };
+        match pac::is_signed(ptr) {
+            false => ptr as usize,
+            true => unsafe { pac::PacKey::InstructionA.authenticate(ptr, 0) as usize },
+        }
+    }
+
+    /// Returns the addresses of the initializers of the image, in the
+    /// order they have to run.
+    ///
+    /// Those are the `__mod_init_func` pointers, already rebased, and
+    /// the `__init_offsets` offsets from the mach_header.
+    pub fn initializers(&self) -> Vec<usize> {
+        let mut initializers = Vec::new();
+
+        for section in self.segments.iter().flat_map(|segment| &segment.sections) {
+            let start = unsafe { self.memory.add(section.vm_addr).as_ptr() };
+
+            match section.flags as i32 & SECTION_TYPE {
+                S_MOD_INIT_FUNC_POINTERS => {
+                    for i in 0..section.vm_size / size_of::<u64>() {
+                        let pointer = unsafe { (start as *const u64).add(i).read_unaligned() };
+                        initializers.push(pointer as usize);
+                    }
+                }
+                S_INIT_FUNC_OFFSETS => {
+                    let Some(header) = self.header_address() else {
+                        continue;
+                    };
+                    for i in 0..section.vm_size / size_of::<u32>() {
+                        let offset = unsafe { (start as *const u32).add(i).read_unaligned() };
+                        initializers.push(header + offset as usize);
+                    }
+                }
+                _ => {}
+            }
+        }
+        initializers
+    }
+
+    /// Returns the addresses of the terminators of the image, in the
+    /// order they have to run.
+    ///
+    /// Those are the `__mod_term_func` pointers, already rebased, last
+    /// one first.
+    pub fn terminators(&self) -> Vec<usize> {
+        let mut terminators = Vec::new();
+
+        for section in self.segments.iter().flat_map(|segment| &segment.sections) {
+            if section.flags as i32 & SECTION_TYPE != S_MOD_TERM_FUNC_POINTERS {
+                continue;
+            }
+
+            let start = unsafe { self.memory.add(section.vm_addr).as_ptr() };
+            for i in 0..section.vm_size / size_of::<u64>() {
+                let pointer = unsafe { (start as *const u64).add(i).read_unaligned() };
+                terminators.push(pointer as usize);
+            }
+        }
+
+        terminators.reverse();
+        terminators
+    }
+
+    /// Returns the start of the tasks address space.
+    ///
+    /// The task keeps owning it, the pointer is only valid as long as
+    /// the task is alive and was not [shut down](Task::shutdown).
+    pub fn memory(&self) -> NonNull<u8> {
+        self.memory
+    }
+
+    /// Returns the size of the tasks address space, 0 if nothing was
+    /// allocated.
+    pub fn memory_size(&self) -> usize {
+        self.memory_size
+    }
+
+    /// Gives up the address space of the task, returning its start and
+    /// size.
+    ///
+    /// The caller owns the allocation from then on and releases it with
+    /// [`mach::vm_dealloc_self`], dropping the task no longer does. The
+    /// image keeps pointing into its dylibs, they are left open, and the
+    /// terminators do not run.
+    ///
+    /// The image stays registered wherever it was: its thread-local
+    /// key and template, its lazy symbol pointers, the dyld stand-ins
+    /// and the debugger all keep serving it, since it may still run.
+    pub fn into_raw(mut self) -> (NonNull<u8>, usize) {
+        let raw = (self.memory, self.memory_size);
+
+        self.memory = NonNull::dangling();
+        self.memory_size = 0;
+        self.segments.clear();
+        self.dylibs.clear();
+        self.preloaded.clear();
+        self.initialized = false;
+        self.tlv_key = None;
+        #[cfg(feature = "debugger")]
+        {
+            self.jit_entry = None;
+        }
+
+        raw
+    }
+
+    /// Tears the task down, undoing the load in reverse.
+    ///
+    /// The terminators run first, if [`Task::run_initializers`] did,
+    /// while the dylibs they may call into are still loaded. The dylibs
+    /// are then closed, last opened first, preloaded ones included, and
+    /// the address space of the image is deallocated.
+    ///
+    /// The task is left empty, calling it again does nothing. Dropping
+    /// the task calls it.
+    ///
+    /// Running the terminators is sound without an `unsafe` caller: they
+    /// only run once the unsafe [`Task::run_initializers`] did, whose
+    /// contract covers them. The task was linked and protected then,
+    /// and its initializers ran exactly once, so do its terminators.
+    pub fn shutdown(&mut self) {
+        if std::mem::take(&mut self.initialized) {
+            for terminator in self.terminators() {
+                let terminator =
+                    unsafe { std::mem::transmute::<usize, extern "C" fn()>(terminator) };
+                terminator();
+            }
+        }
+
+        #[cfg(feature = "debugger")]
+        self.debugger_unregister();
+
+        self.dyld_unregister();
+
+        self.tlv_shutdown();
+
+        #[cfg(feature = "lazy_binding")]
+        self.lazy_shutdown();
+
+        for (_, handle) in self.dylibs.drain(..).rev() {
+            if handle != 0 {
+                unsafe { libc::dlclose(handle as *mut libc::c_void) };
+            }
+        }
+        self.dylib_flags.clear();
+        self.dylib_compat_versions.clear();
+        for (_, handle) in self.preloaded.drain(..).rev() {
+            unsafe { libc::dlclose(handle as *mut libc::c_void) };
+        }
+
+        // Nothing was allocated for an empty address space, `memory`
+        // is dangling in that case.
+        if self.memory_size != 0 {
+            vm_dealloc_self(
+                self.memory.as_ptr() as libc::mach_vm_address_t,
+                self.memory_size,
+            );
+        }
+
+        self.memory = NonNull::dangling();
+        self.memory_size = 0;
+        self.segments.clear();
+        self.definitions.clear();
+        self.trie_exports.clear();
+        self.entry_point = 0;
+    }
+
+    /// Returns the stack size the program asked for in `LC_MAIN`, or
+    /// `None` when it is happy with the default.
+    pub fn requested_stack_size(&self) -> Option<usize> {
+        match self.stack_size {
+            0 => None,
+            size => Some(size as usize),
+        }
+    }
+}
+
+/// The load commands the loader acts on
+const HANDLED_LOAD_COMMANDS: &[u32] = &[
+    LC_SEGMENT_64,
+    LC_SYMTAB,
+    LC_DYSYMTAB,
+    LC_MAIN,
+    LC_UNIXTHREAD,
+    LC_DYLD_INFO,
+    LC_DYLD_INFO_ONLY,
+    LC_DYLD_CHAINED_FIXUPS,
+    LC_LOAD_DYLIB,
+    LC_LOAD_WEAK_DYLIB,
+    LC_REEXPORT_DYLIB,
+    LC_LOAD_UPWARD_DYLIB,
+    LC_LAZY_LOAD_DYLIB,
+    LC_ID_DYLIB,
+    LC_SOURCE_VERSION,
+    LC_UUID,
+    LC_RPATH,
+];
+
+/// Returns the current version in the `LC_ID_DYLIB` of the mach_header
+/// and load commands `header`, `None` if it has none.
+fn id_dylib_current_version(header: &[u8]) -> Option<u32> {
+    let read_u32 = |offset: usize| {
+        let bytes = header.get(offset..offset.checked_add(4)?)?;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+
+    let ncmds = read_u32(16)?;
+    let mut offset = goblin::mach::header::SIZEOF_HEADER_64;
+    for _ in 0..ncmds {
+        let (cmd, cmdsize) = (read_u32(offset)?, read_u32(offset + 4)?);
+        if cmd == LC_ID_DYLIB {
+            return read_u32(offset + 16);
+        }
+        if cmdsize == 0 {
+            return None;
+        }
+        offset = offset.checked_add(cmdsize as usize)?;
+    }
+    None
+}
+
+/// Returns the current version of the loaded dylib `handle` refers to.
+///
+/// dyld hands out one handle per image, the image is found by opening
+/// each loaded one again with `RTLD_NOLOAD` and comparing handles.
+fn loaded_dylib_current_version(handle: *mut libc::c_void) -> Option<u32> {
+    for index in 0..unsafe { libc::_dyld_image_count() } {
+        let name = unsafe { libc::_dyld_get_image_name(index) };
+        if name.is_null() {
+            continue;
+        }
+
+        let other = unsafe { libc::dlopen(name, libc::RTLD_LAZY | libc::RTLD_NOLOAD) };
+        if other.is_null() {
+            continue;
+        }
+        unsafe { libc::dlclose(other) };
+        if other != handle {
+            continue;
+        }
+
+        let header = unsafe { libc::_dyld_get_image_header(index) }.cast::<u8>();
+        if header.is_null() {
+            return None;
+        }
+        let sizeofcmds = unsafe { (header.add(20) as *const u32).read_unaligned() };
+        let header = unsafe {
+            core::slice::from_raw_parts(
+                header,
+                goblin::mach::header::SIZEOF_HEADER_64 + sizeofcmds as usize,
+            )
+        };
+        return id_dylib_current_version(header);
+    }
+    None
+}
+
+/// Reads the nul terminated name of a dylib load command.
+///
+/// `name_offset` is relative to the start of the load command, and the
+/// name has to be terminated within the `cmdsize` bytes of the command.
+fn dylib_name_from_command(
+    image: &[u8],
+    command_offset: usize,
+    cmdsize: usize,
+    name_offset: usize,
+) -> Result<&std::ffi::CStr, LoadError> {
+    let malformed = || LoadError::MalformedDylibName {
+        command_offset,
+        name_offset,
+    };
+
+    let command = command_offset
+        .checked_add(cmdsize)
+        .and_then(|command_end| image.get(command_offset..command_end))
+        .ok_or_else(malformed)?;
+
+    let name = command.get(name_offset..).ok_or_else(malformed)?;
+
+    std::ffi::CStr::from_bytes_until_nul(name).map_err(|_| malformed())
+}
+
+/// The largest image the loader accepts, in bytes
+pub(crate) const IMAGE_SIZE_MAX: usize = 100_000_000;
+
+/// Checks the pointer and len an image is loaded from, before they are
+/// made into a slice.
+pub(crate) fn image_check(ptr: *const u8, len: usize) -> Result<(), LoadError> {
+    if ptr.is_null() {
+        return Err(LoadError::NullImage);
+    }
+    if len == 0 {
+        return Err(LoadError::EmptyImage);
+    }
+    if len > IMAGE_SIZE_MAX {
+        return Err(LoadError::ImageTooLarge { len });
+    }
+    Ok(())
+}
+
+/// Returns the error for an image without a `requested` slice, listing
+/// the cputypes it has.
+pub(crate) fn arch_not_found(requested: TargetArch, found: &[u32]) -> LoadError {
+    LoadError::ArchNotFound {
+        requested: report::cputype_into_str(requested.cputype()).to_string(),
+        found: found
+            .iter()
+            .map(|cputype| report::cputype_into_str(*cputype).to_string())
+            .collect(),
+    }
+}
+
+/// Initialize the [`Task`] struct from a pointer and
+/// a len.
+unsafe fn task_init(ptr: *const u8, len: usize, options: &TaskOptions) -> Result<Task, LoadError> {
+    image_check(ptr, len)?;
+
+    let image = unsafe { core::slice::from_raw_parts(ptr, len) };
+
+    // A byte-swapped header comes from a big-endian target, whose
+    // code could never run here.
+    if let Some(magic) = image
+        .first_chunk::<4>()
+        .map(|magic| u32::from_le_bytes(*magic))
+        && (magic == MH_CIGAM || magic == MH_CIGAM_64)
+    {
+        return Err(LoadError::UnsupportedEndianness { magic });
+    }
+
+    match Mach::parse(image) {
+        Ok(Mach::Binary(macho)) => match options.prefer_arch {
+            Some(requested) if macho.header.cputype != requested.cputype() => {
+                Err(arch_not_found(requested, &[macho.header.cputype]))
+            }
+            _ => task_init_macho(&macho, image, options),
+        },
+        Ok(Mach::Fat(multi_arch)) => {
+            let arches = multi_arch
+                .iter_arches()
+                .filter_map(|arch| arch.ok())
+                .collect::<Vec<_>>();
+
+            // the requested slice, whether or not it runs here
+            if let Some(requested) = options.prefer_arch {
+                let arch = arches
+                    .iter()
+                    .find(|arch| arch.cputype == requested.cputype())
+                    .ok_or_else(|| {
+                        let found = arches.iter().map(|arch| arch.cputype).collect::<Vec<_>>();
+                        arch_not_found(requested, &found)
+                    })?;
+                let image = arch.slice(image);
+
+                return unsafe { task_init(image.as_ptr(), image.len(), options) };
+            }
+
+            // a fat binary with no 64 bit slice can not have an arm64 one,
+            // say what it has instead
+            if !arches.iter().any(|arch| arch.cputype & CPU_ARCH_ABI64 != 0) {
+                return Err(LoadError::No64BitArch {
+                    found: arches
+                        .iter()
+                        .map(|arch| report::cputype_into_str(arch.cputype).to_string())
+                        .collect(),
+                });
+            }
+
+            let arch = arches
+                .iter()
+                .find(|arch| arch.cputype == CPU_TYPE_ARM64)
+                .ok_or_else(|| {
+                    let found = arches.iter().map(|arch| arch.cputype).collect::<Vec<_>>();
+                    arch_not_found(TargetArch::Arm64, &found)
+                })?;
+
+            // extract the CPU_TYPE_ARM64 architecture
+            let image = arch.slice(image);
+
+            // Recurse on the extracted architecture
+            unsafe { task_init(image.as_ptr(), image.len(), options) }
+        }
+        Err(err) => Err(LoadError::Parse(err)),
+    }
+}
+
+/// Loads a parsed MachO into a new task.
+fn task_init_macho(
+    macho: &MachO<'_>,
+    image: &[u8],
+    options: &TaskOptions,
+) -> Result<Task, LoadError> {
+    if !macho.little_endian {
+        return Err(LoadError::UnsupportedEndianness {
+            magic: macho.header.magic,
+        });
+    }
+
+    // We only support 64-bit mach-o files
+    if !macho.is_64 {
+        return Err(LoadError::No64BitArch {
+            found: vec![report::cputype_into_str(macho.header.cputype).to_string()],
+        });
+    }
+
+    // goblin walks exactly `ncmds` commands, one after the other from
+    // the end of the header. They have to fill exactly the region the
+    // header declares, or the header lies about it
+    let sizeofcmds = macho
+        .load_commands
+        .iter()
+        .map(|lc| lc.command.cmdsize() as u64)
+        .sum::<u64>();
+    if sizeofcmds != macho.header.sizeofcmds as u64 {
+        return Err(LoadError::LoadCommandMismatch {
+            ncmds: macho.header.ncmds,
+            sizeofcmds: macho.header.sizeofcmds,
+            parsed_size: sizeofcmds,
+        });
+    }
+
+    // The image always lands at a random address, a non-PIE executable
+    // would keep absolute pointers that nothing rebases. Dylibs and
+    // bundles are always position independent.
+    if macho.header.filetype == MH_EXECUTE && !MachHeaderFlags(macho.header.flags).pie() {
+        return Err(fixed_base_check(macho));
+    }
+
+    // Initialize the actual task now
+    let mut task = task_init_from_macho(macho, image, options)?;
+
+    task.rebase(macho, image)?;
+
+    task.chained_fixups_init(macho, image)?;
+
+    task.preload_dylibs_open(&options.preload_dylibs)?;
+
+    task.dylibs_open(
+        macho,
+        image,
+        options.bind_mode,
+        options.on_missing_dylib.as_ref(),
+    )?;
+
+    if options.strict_dylib_versions
+        && let Some(issue) = task.dylib_version_issues().into_iter().next()
+    {
+        return Err(LoadError::DylibTooOld {
+            name: issue.name,
+            required: issue.required,
+            current: issue.current,
+        });
+    }
+
+    // the cache stands in for the symbol table walk, as long as it was
+    // built from this very image
+    match options
+        .symbol_cache
+        .as_ref()
+        .filter(|cache| task.uuid == Some(cache.uuid))
+    {
+        Some(cache) => task.symtab_from_cache(cache),
+        None => task.symtab_init(macho, image)?,
+    }
+
+    task.definitions_init();
+
+    task.exports_init(macho, image)?;
+
+    task.versions_init(macho, image)?;
+
+    #[cfg(feature = "debugger")]
+    task.debugger_register();
+
+    task.dyld_register(options.image_path.as_deref());
+
+    if options.load_mode == LoadMode::LinkOnly {
+        task.prepare().map_err(LoadError::Prepare)?;
+    }
+
+    Ok(task)
+}
+
+/// Offset of `reserved1` within a `section_64`
+const SECTION_64_RESERVED1: usize = 68;
+
+/// Returns the `reserved1` of every section of `segment`, in order.
+///
+/// goblin's `Section` leaves it out, it is read from the `section_64`
+/// headers that follow the load command of the segment.
+fn sections_reserved1(
+    macho: &MachO,
+    image: &[u8],
+    segment: &goblin::mach::segment::Segment,
+) -> Vec<u32> {
+    let Some(headers) = macho.load_commands.iter().find_map(|lc| match &lc.command {
+        CommandVariant::Segment64(command)
+            if command.segname == segment.segname && command.vmaddr == segment.vmaddr =>
+        {
+            Some(lc.offset + SIZEOF_SEGMENT_COMMAND_64)
+        }
+        _ => None,
+    }) else {
+        return Vec::new();
+    };
+
+    (0..segment.nsects as usize)
+        .map(|index| headers + index * SIZEOF_SECTION_64 + SECTION_64_RESERVED1)
+        .map_while(|pos| image.get(pos..)?.first_chunk().copied())
+        .map(u32::from_le_bytes)
+        .collect()
+}
+
+/// Returns the address the image was linked to: the lowest `vmaddr` of
+/// its segments, `__PAGEZERO` left out.
+///
+/// That is `0x100000000` for an executable, right after its
+/// `__PAGEZERO`, and 0 for a dylib. Link-time addresses (symbol values,
+/// rebase targets) are relative to it.
+pub fn preferred_base(macho: &MachO<'_>) -> u64 {
+    macho
+        .segments
+        .iter()
+        .filter(|seg| seg.vmsize != 0 && !matches!(seg.name(), Ok("__PAGEZERO")))
+        .map(|seg| seg.vmaddr)
+        .min()
+        .unwrap_or(0)
+}
+
+/// Tells why the position dependent executable `macho` can not be
+/// loaded.
+///
+/// It would have to be mapped at its preferred base, which is checked
+/// first: when something of the host already lives there the image can
+/// never run in this process, and the error names the mapping in the
+/// way. Otherwise it is still refused, the address space of a task
+/// always starts at a fresh allocation and can not be given a slide of
+/// 0.
+fn fixed_base_check(macho: &MachO<'_>) -> LoadError {
+    let required = preferred_base(macho);
+    let end = macho
+        .segments
+        .iter()
+        .filter(|seg| seg.vmsize != 0 && !matches!(seg.name(), Ok("__PAGEZERO")))
+        .map(|seg| seg.vmaddr.saturating_add(seg.vmsize))
+        .max();
+
+    let Some(end) = end else {
+        return LoadError::NotPositionIndependent;
+    };
+
+    let size = vm_page_round((end - required) as usize);
+    match mach::vm_alloc_fixed_self(required, size) {
+        Ok(memory) => {
+            vm_dealloc_self(memory.as_ptr().addr() as u64, size);
+            LoadError::NotPositionIndependent
+        }
+        Err(reason) => LoadError::BaseAddressUnavailable { required, reason },
+    }
+}
+
+/// Returns the virtual address of `entry` if it lands in the segment
+/// mapping `fileoff..fileoff + filesize` at `vmaddr..vmaddr + vmsize`.
+///
+/// LC_MAIN gives a file offset, which is moved to where the segment
+/// holding it is mapped. LC_UNIXTHREAD (`old_style`) already gives a
+/// virtual address.
+fn entry_in_segment(
+    entry: u64,
+    old_style: bool,
+    fileoff: u64,
+    filesize: u64,
+    vmaddr: u64,
+    vmsize: u64,
+) -> Option<u64> {
+    let (start, size) = match old_style {
+        true => (vmaddr, vmsize),
+        false => (fileoff, filesize),
+    };
+    if entry < start || entry - start >= size {
+        return None;
+    }
+
+    match old_style {
+        true => Some(entry),
+        false => Some(vmaddr + (entry - fileoff)),
+    }
+}
+
+/// Returns the virtual address of the entry point, 0 if the image has
+/// none.
+///
+/// Fails with [`LoadError::BadEntryPoint`] if the entry point does not
+/// land in executable code, whether that is `__TEXT` or `__TEXT_EXEC`.
+pub(crate) fn entry_point_from_macho(macho: &MachO<'_>) -> Result<usize, LoadError> {
+    let entry = macho.entry;
+    if entry == 0 {
+        return Ok(0);
+    }
+
+    let entry_segment = macho.segments.iter().find_map(|seg| {
+        let vm_addr = entry_in_segment(
+            entry,
+            macho.old_style_entry,
+            seg.fileoff,
+            seg.filesize,
+            seg.vmaddr,
+            seg.vmsize,
+        )?;
+        Some((seg, vm_addr))
+    });
+
+    match entry_segment {
+        Some((seg, vm_addr)) if seg.initprot as i32 & libc::VM_PROT_EXECUTE != 0 => {
+            Ok(vm_addr as usize)
+        }
+        Some((seg, _)) => Err(LoadError::BadEntryPoint {
+            entry,
+            segment: Some(seg.name().unwrap_or("<invalid utf8>").to_string()),
+        }),
+        None => Err(LoadError::BadEntryPoint {
+            entry,
+            segment: None,
+        }),
+    }
+}
+
+/// Initializes a task given a parsed MachO.
+///
+/// `image` is supposed to hold the slice within
+/// the initial file that corresponds to this parsed
+/// `macho`.
+fn task_init_from_macho(
+    macho: &MachO<'_>,
+    image: &[u8],
+    options: &TaskOptions,
+) -> Result<Task, LoadError> {
+    let vm_size = segments_vm_size(
+        macho
+            .segments
+            .iter()
+            .filter(|seg| !options.is_skipped(seg))
+            .map(|seg| {
+                (
+                    seg.name().unwrap_or("<invalid utf8>").to_string(),
+                    seg.vmaddr,
+                    seg.vmsize,
+                )
+            }),
+    )?;
+
+    // a later segment overlapping an earlier one would overwrite it
+    // while being copied
+    segments_check_overlap(
+        macho
+            .segments
+            .iter()
+            .filter(|seg| !options.is_skipped(seg) && !matches!(seg.name(), Ok("__PAGEZERO")))
+            .map(|seg| {
+                (
+                    seg.name().unwrap_or("<invalid utf8>").to_string(),
+                    seg.vmaddr,
+                    seg.vmaddr.saturating_add(seg.vmsize),
+                )
+            }),
+    )?;
+
+    // section pointers are computed from the section alone, and have to
+    // land in the part of the address space of their segment
+    for seg in macho.segments.iter().filter(|seg| !options.is_skipped(seg)) {
+        let segment = seg.name().unwrap_or("<invalid utf8>");
+        let sections = seg.sections().unwrap_or_default();
+
+        segment_check_protection(segment, seg.initprot as i32, seg.maxprot as i32)?;
+
+        segment_check_file_size(segment, seg.filesize, seg.vmsize)?;
+
+        segment_check_file_range(segment, seg.fileoff, seg.filesize, image.len())?;
+
+        section_check_containment(
+            segment,
+            seg.vmaddr,
+            seg.vmsize,
+            sections.iter().map(|(section, _)| {
+                (
+                    section.name().unwrap_or("<invalid utf8>").to_string(),
+                    section.addr,
+                    section.size,
+                )
+            }),
+        )?;
+    }
+
+    let entry_point = entry_point_from_macho(macho)?;
+
+    let stack_size = macho
+        .load_commands
+        .iter()
+        .find_map(|lc| match lc.command {
+            CommandVariant::Main(entry) => Some(entry.stacksize),
+            _ => None,
+        })
+        .unwrap_or(0);
 
     // allocate the tasks address space on our own
     // task
-    let memory_size = vm_size;
-    let memory = vm_alloc_self(memory_size);
+    let memory_size = vm_page_round(vm_size);
+    let memory = match memory_size {
+        0 => NonNull::dangling(),
+        _ => address_space_alloc(memory_size, options.slide_hint)?,
+    };
+
+    // The task owns the allocation from here on, so that if anything
+    // below fails, `Drop` releases exactly what was allocated.
+    let mut task = Task {
+        memory,
+        dylibs: Vec::new(),
+        dylib_flags: Vec::new(),
+        dylib_compat_versions: Vec::new(),
+        preloaded: Vec::new(),
+        loader_dir: options
+            .image_path
+            .as_deref()
+            .and_then(std::path::Path::parent)
+            .map(std::path::Path::to_path_buf),
+        symbols: Vec::new(),
+        definitions: Vec::new(),
+        trie_exports: Vec::new(),
+        symtab: Vec::new(),
+        indirect_symbols: Vec::new(),
+        memory_size,
+        segments: Vec::new(),
+        entry_point,
+        unixthread: macho.old_style_entry,
+        header: macho.header,
+        source_version: None,
+        dylib_id: None,
+        stack_size,
+        sealed: false,
+        initialized: false,
+        load_commands: macho
+            .load_commands
+            .iter()
+            .map(|lc| lc.command.cmd())
+            .collect(),
+        preferred_base: preferred_base(macho),
+        uuid: macho.load_commands.iter().find_map(|lc| match lc.command {
+            CommandVariant::Uuid(uuid) => Some(uuid.uuid),
+            _ => None,
+        }),
+        chained_imports: Vec::new(),
+        chained_binds: Vec::new(),
+        flat_lookup: options.flat_lookup,
+        tlv_key: None,
+        #[cfg(feature = "lazy_binding")]
+        lazy_binding: options.lazy_binding,
+        #[cfg(feature = "debugger")]
+        jit_entry: None,
+    };
+
+    #[cfg(feature = "jit")]
+    jit_segments_map(macho, memory, options)?;
 
     let segments = macho
         .segments
@@ -332,11 +2253,12 @@ fn task_init_from_macho(macho: &MachO<'_>, image: &[u8]) -> Task {
                 ..
             } = *seg;
 
-            let sections = seg
+            let sections: Vec<Section> = seg
                 .sections()
-                .expect("no sections found")
+                .map_err(LoadError::Parse)?
                 .into_iter()
-                .map(|(section, _)| {
+                .zip(sections_reserved1(macho, image, seg))
+                .map(|((section, _), reserved1)| {
                     let goblin::mach::segment::Section {
                         flags,
                         offset,
@@ -348,28 +2270,74 @@ fn task_init_from_macho(macho: &MachO<'_>, image: &[u8]) -> Task {
 
                     Section {
                         flags,
-                        name: String::from_utf8(section.sectname.to_vec()).unwrap(),
+                        name: String::from_utf8(section.sectname.to_vec())
+                            .unwrap_or_else(|_| "<invalid utf8>".to_string()),
                         offset: offset as usize,
                         vm_addr: addr as usize,
                         vm_size: size as usize,
                         align: align as usize,
+                        reserved1,
                     }
                 })
                 .collect();
 
-            // Copy the segment data from the Mach-O image into the
-            // corresponding location in the address space.
-            unsafe {
-                copy_from_image(
-                    image.as_ptr().add(fileoff as usize).addr() as u64,
-                    memory.as_ptr().add(vmaddr as usize).addr() as u64,
-                    filesize as usize,
-                )
+            // A skipped segment is kept with an empty range, so that
+            // segment indices still match the load commands.
+            let (vmsize, filesize) = match options.is_skipped(seg) {
+                true => (0, 0),
+                false => (vmsize, filesize),
             };
 
-            Segment {
+            // Copy the segment data from the Mach-O image into the
+            // corresponding location in the address space. A segment
+            // without a vm range (linker padding) has nowhere to go.
+            if vmsize != 0 {
+                unsafe {
+                    segment_copy(
+                        image.as_ptr().add(fileoff as usize),
+                        memory.as_ptr().add(vmaddr as usize),
+                        filesize as usize,
+                    )
+                }?;
+
+                // before the zero fill and the fixups change it
+                let copied = || unsafe {
+                    core::slice::from_raw_parts(
+                        memory.as_ptr().add(vmaddr as usize),
+                        filesize as usize,
+                    )
+                };
+                let source = image.get(fileoff as usize..(fileoff + filesize) as usize);
+                if options.verify_copy && source != Some(copied()) {
+                    return Err(LoadError::CopyVerificationFailed {
+                        segment: seg.name().unwrap_or("<invalid utf8>").to_string(),
+                    });
+                }
+            }
+
+            // Zero fill sections have no file backing. The allocation
+            // is fresh, but a segment's file data may still run over
+            // them, so clear them explicitly.
+            let segment_end = vmaddr.saturating_add(vmsize);
+            for section in sections.iter().filter(|section| section.is_zerofill()) {
+                let start = (section.vm_addr as u64).max(vmaddr);
+                let end = (section.vm_addr as u64)
+                    .saturating_add(section.vm_size as u64)
+                    .min(segment_end);
+
+                if start < end {
+                    unsafe {
+                        memory
+                            .add(start as usize)
+                            .write_bytes(0, (end - start) as usize)
+                    };
+                }
+            }
+
+            Ok(Segment {
                 flags,
-                name: String::from_utf8(seg.segname.to_vec()).unwrap(),
+                name: String::from_utf8(seg.segname.to_vec())
+                    .unwrap_or_else(|_| "<invalid utf8>".to_string()),
                 sections,
                 offset: fileoff as usize,
                 vm_addr: vmaddr as usize,
@@ -377,16 +2345,617 @@ fn task_init_from_macho(macho: &MachO<'_>, image: &[u8]) -> Task {
                 size: filesize as usize,
                 maxprot: maxprot as i32,
                 initprot: initprot as i32,
-            }
+            })
         })
+        .collect::<Result<Vec<_>, LoadError>>()?;
+
+    task.segments = segments;
+    Ok(task)
+}
+
+/// Allocates the address space of a task, at `hint` if it is free.
+///
+/// A taken `hint` is retried anywhere, see [`TaskOptions::slide_hint`].
+fn address_space_alloc(size: usize, hint: Option<usize>) -> Result<NonNull<u8>, LoadError> {
+    match hint.map(|hint| mach::vm_alloc_fixed_self(hint as u64, size)) {
+        Some(Ok(memory)) => Ok(memory),
+        Some(Err(VmError::RegionOccupied { .. })) | None => {
+            mach::try_vm_alloc_self(size).map_err(LoadError::Vm)
+        }
+        Some(Err(err)) => Err(LoadError::Vm(err)),
+    }
+}
+
+/// Remaps the executable segments of `macho` in the address space at
+/// `memory` as `MAP_JIT` memory.
+///
+/// Their pages are left writable for the calling thread, which loads
+/// and binds the image. They are switched to executable once the task
+/// is sealed, see [`Task::prepare`]. The other segments are protected
+/// as usual.
+#[cfg(feature = "jit")]
+fn jit_segments_map(
+    macho: &MachO<'_>,
+    memory: NonNull<u8>,
+    options: &TaskOptions,
+) -> Result<(), LoadError> {
+    for seg in macho.segments.iter().filter(|seg| {
+        !options.is_skipped(seg)
+            && seg.vmsize != 0
+            && seg.initprot as i32 & libc::VM_PROT_EXECUTE != 0
+    }) {
+        let address = unsafe { memory.add(seg.vmaddr as usize).as_ptr().addr() };
+        mach::vm_remap_jit_self(address, vm_page_round(seg.vmsize as usize))
+            .map_err(LoadError::Io)?;
+    }
+
+    mach::jit_write_protect(false);
+    Ok(())
+}
+
+/// Copies `count` bytes of a segment from the image into the task.
+#[cfg(not(feature = "jit"))]
+unsafe fn segment_copy(src: *const u8, dst: *mut u8, count: usize) -> Result<(), LoadError> {
+    mach::try_copy_from_image(src.addr() as u64, dst.addr() as u64, count).map_err(LoadError::Vm)
+}
+
+/// Copies `count` bytes of a segment from the image into the task.
+///
+/// `MAP_JIT` pages are only writable by a thread that lifted their
+/// write protection, so the copy has to happen from this thread rather
+/// than through the kernel.
+#[cfg(feature = "jit")]
+unsafe fn segment_copy(src: *const u8, dst: *mut u8, count: usize) -> Result<(), LoadError> {
+    unsafe { std::ptr::copy_nonoverlapping(src, dst, count) };
+    Ok(())
+}
+
+/// Returns the size of the address space holding the
+/// `(name, vmaddr, vmsize)` segments.
+///
+/// Segments are mapped at `memory + vmaddr`, so the address space
+/// reaches from 0 to the highest virtual address (max_addr) occupied by
+/// any segment. Segments with a zero vmsize occupy nothing and are left
+/// out, one that still maps part of the file is refused by
+/// [`segment_check_file_size`].
+fn segments_vm_size(
+    segments: impl Iterator<Item = (String, u64, u64)>,
+) -> Result<usize, LoadError> {
+    let mut max_addr = 0;
+
+    for (name, vmaddr, vmsize) in segments {
+        if vmsize == 0 {
+            continue;
+        }
+
+        let end = vmaddr
+            .checked_add(vmsize)
+            .ok_or(LoadError::AddressOverflow { segment: name })?;
+        max_addr = max_addr.max(end);
+    }
+
+    usize::try_from(max_addr).map_err(|_| LoadError::AddressOverflow {
+        segment: String::new(),
+    })
+}
+
+/// Checks that every `(name, addr, size)` section of the segment
+/// `segment` lies within its `vmaddr`, `vmsize` range.
+fn section_check_containment(
+    segment: &str,
+    vmaddr: u64,
+    vmsize: u64,
+    sections: impl Iterator<Item = (String, u64, u64)>,
+) -> Result<(), LoadError> {
+    let segment_end = vmaddr.saturating_add(vmsize);
+
+    for (section, addr, size) in sections {
+        let contained = addr
+            .checked_add(size)
+            .is_some_and(|end| addr >= vmaddr && end <= segment_end);
+
+        if !contained {
+            return Err(LoadError::SectionOutsideSegment {
+                segment: segment.to_string(),
+                section,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the `initprot` of the segment `segment` is within its
+/// `maxprot`.
+///
+/// The kernel refuses such a protection, or clamps it, and
+/// [`Task::assert_protection`] would only fail once the segments are
+/// sealed.
+pub(crate) fn segment_check_protection(
+    segment: &str,
+    initprot: i32,
+    maxprot: i32,
+) -> Result<(), LoadError> {
+    match initprot & !maxprot {
+        0 => Ok(()),
+        _ => Err(LoadError::ProtectionInconsistent {
+            segment: segment.to_string(),
+            initprot,
+            maxprot,
+        }),
+    }
+}
+
+/// Checks that a segment does not map more of the file than its
+/// `vmsize`.
+///
+/// The copy would run past the end of the segment, into whatever
+/// segment follows it in the address space. A segment without a vm
+/// range mapping part of the file would silently lose it.
+pub(crate) fn segment_check_file_size(
+    segment: &str,
+    filesize: u64,
+    vmsize: u64,
+) -> Result<(), LoadError> {
+    match filesize > vmsize {
+        false => Ok(()),
+        true => Err(LoadError::FileSizeExceedsVmSize {
+            segment: segment.to_string(),
+            filesize,
+            vmsize,
+        }),
+    }
+}
+
+/// Checks that the `fileoff`, `filesize` range of a segment lies within
+/// the `image_len` bytes of the image.
+pub(crate) fn segment_check_file_range(
+    segment: &str,
+    fileoff: u64,
+    filesize: u64,
+    image_len: usize,
+) -> Result<(), LoadError> {
+    match fileoff
+        .checked_add(filesize)
+        .is_some_and(|end| end <= image_len as u64)
+    {
+        true => Ok(()),
+        false => Err(LoadError::SegmentOutsideImage {
+            segment: segment.to_string(),
+            fileoff,
+            filesize,
+        }),
+    }
+}
+
+/// Checks that no two of the `(name, start, end)` ranges overlap.
+pub(crate) fn segments_check_overlap(
+    ranges: impl Iterator<Item = (String, u64, u64)>,
+) -> Result<(), LoadError> {
+    let mut ranges = ranges
+        .filter(|(_, start, end)| start < end)
         .collect::<Vec<_>>();
+    ranges.sort_by_key(|(_, start, _)| *start);
+
+    // with the ranges sorted, an overlap always involves the range
+    // reaching the furthest so far
+    let mut furthest: Option<&(String, u64, u64)> = None;
+    for range in &ranges {
+        if let Some(previous) = furthest
+            && range.1 < previous.2
+        {
+            return Err(LoadError::OverlappingSegments {
+                a: previous.0.clone(),
+                b: range.0.clone(),
+            });
+        }
 
-    Task {
-        memory,
-        dylibs: Vec::new(),
-        symbols: Vec::new(),
-        memory_size,
-        segments,
-        entry_point,
+        if furthest.is_none_or(|previous| range.2 > previous.2) {
+            furthest = Some(range);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::NonNull;
+
+    use crate::{FlatLookup, Task};
+
+    #[test]
+    pub fn test_drop_empty_task() {
+        // Must not hand the dangling pointer to mach_vm_deallocate.
+        let task = Task {
+            memory: NonNull::dangling(),
+            dylibs: Vec::new(),
+            dylib_flags: Vec::new(),
+            dylib_compat_versions: Vec::new(),
+            preloaded: Vec::new(),
+            loader_dir: None,
+            symbols: Vec::new(),
+            definitions: Vec::new(),
+            trie_exports: Vec::new(),
+            symtab: Vec::new(),
+            indirect_symbols: Vec::new(),
+            memory_size: 0,
+            segments: Vec::new(),
+            entry_point: 0,
+            unixthread: false,
+            header: Default::default(),
+            source_version: None,
+            dylib_id: None,
+            stack_size: 0,
+            sealed: false,
+            initialized: false,
+            load_commands: Vec::new(),
+            uuid: None,
+            preferred_base: 0,
+            chained_imports: Vec::new(),
+            chained_binds: Vec::new(),
+            flat_lookup: FlatLookup::FirstWins,
+            tlv_key: None,
+            #[cfg(feature = "lazy_binding")]
+            lazy_binding: false,
+            #[cfg(feature = "debugger")]
+            jit_entry: None,
+        };
+
+        drop(task);
+    }
+
+    #[test]
+    pub fn test_id_dylib_current_version() {
+        // a mach_header_64, an LC_UUID and an LC_ID_DYLIB of version 1.2.3
+        let mut header = vec![0u8; 32];
+        header[16..20].copy_from_slice(&2u32.to_le_bytes());
+
+        header.extend_from_slice(&0x1bu32.to_le_bytes());
+        header.extend_from_slice(&24u32.to_le_bytes());
+        header.extend_from_slice(&[0; 16]);
+
+        header.extend_from_slice(&0xdu32.to_le_bytes());
+        header.extend_from_slice(&32u32.to_le_bytes());
+        header.extend_from_slice(&24u32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&0x10203u32.to_le_bytes());
+        header.extend_from_slice(&0x10000u32.to_le_bytes());
+        header.extend_from_slice(b"libfoo\0\0");
+
+        assert_eq!(crate::id_dylib_current_version(&header), Some(0x10203));
+
+        // the LC_ID_DYLIB is past ncmds
+        header[16..20].copy_from_slice(&1u32.to_le_bytes());
+        assert_eq!(crate::id_dylib_current_version(&header), None);
+    }
+
+    #[test]
+    pub fn test_dylib_name_from_command() {
+        // a 32 byte load command at offset 8, its name at offset 24
+        let mut image = [0u8; 48];
+        image[32..38].copy_from_slice(b"libfoo");
+
+        let name = crate::dylib_name_from_command(&image, 8, 32, 24).unwrap();
+        assert_eq!(name.to_bytes(), b"libfoo");
+
+        // the name starts past the end of the command
+        assert!(crate::dylib_name_from_command(&image, 8, 32, 40).is_err());
+
+        // the command runs past the end of the image
+        assert!(crate::dylib_name_from_command(&image, 8, 64, 24).is_err());
+
+        // the name is not terminated within the command
+        image[32..40].copy_from_slice(b"libfooba");
+        assert!(crate::dylib_name_from_command(&image, 8, 32, 24).is_err());
+    }
+
+    #[test]
+    pub fn test_rtld_flags_to_string() {
+        assert_eq!(
+            crate::rtld_flags_to_string(libc::RTLD_NOW | libc::RTLD_LOCAL),
+            "RTLD_NOW|RTLD_LOCAL"
+        );
+        assert_eq!(crate::rtld_flags_to_string(0x2 | 0x1000), "RTLD_NOW|0x1000");
+        assert_eq!(crate::rtld_flags_to_string(0), "0");
+    }
+
+    #[test]
+    pub fn test_section_contains_instructions() {
+        let section = |name: &str, flags| crate::Section {
+            flags,
+            name: format!("{name:\0<16}"),
+            offset: 0,
+            vm_addr: 0,
+            vm_size: 0,
+            align: 0,
+            reserved1: 0,
+        };
+
+        // __text: S_REGULAR | PURE_INSTRUCTIONS | SOME_INSTRUCTIONS
+        let text = section("__text", 0x80000400);
+        assert!(text.contains_instructions());
+        assert_eq!(text.name(), "__text");
+
+        // __stubs: S_SYMBOL_STUBS | PURE_INSTRUCTIONS | SOME_INSTRUCTIONS
+        assert!(section("__stubs", 0x80000408).contains_instructions());
+        assert!(!section("__cstring", 0x2).contains_instructions());
+    }
+
+    #[test]
+    pub fn test_entry_in_segment() {
+        // LC_MAIN: __TEXT maps file 0..0x4000 at 0x100000000
+        assert_eq!(
+            crate::entry_in_segment(0x3f40, false, 0, 0x4000, 0x100000000, 0x4000),
+            Some(0x100003f40)
+        );
+        // __TEXT_EXEC maps file 0x4000..0x8000 at 0x100008000
+        assert_eq!(
+            crate::entry_in_segment(0x4010, false, 0x4000, 0x4000, 0x100008000, 0x4000),
+            Some(0x100008010)
+        );
+        assert_eq!(
+            crate::entry_in_segment(0x8000, false, 0x4000, 0x4000, 0x100008000, 0x4000),
+            None
+        );
+
+        // LC_UNIXTHREAD gives the address already
+        assert_eq!(
+            crate::entry_in_segment(0x100003f40, true, 0, 0x4000, 0x100000000, 0x4000),
+            Some(0x100003f40)
+        );
+    }
+
+    #[test]
+    pub fn test_bind_mode_rtld_flags() {
+        use crate::BindMode;
+
+        let lazy = libc::RTLD_LAZY | libc::RTLD_LOCAL;
+        let now = libc::RTLD_NOW | libc::RTLD_LOCAL;
+
+        assert_eq!(BindMode::PerCommand.rtld_flags(lazy), lazy);
+        assert_eq!(BindMode::Now.rtld_flags(lazy), now);
+        assert_eq!(BindMode::Now.rtld_flags(now), now);
+        assert_eq!(BindMode::Lazy.rtld_flags(now), lazy);
+    }
+
+    #[test]
+    pub fn test_segments_vm_size() {
+        let segment = |name: &str, vmaddr, vmsize| (name.to_string(), vmaddr, vmsize);
+
+        let vm_size = crate::segments_vm_size(
+            [
+                segment("__TEXT", 0x1000, 0x1000),
+                segment("__DATA", 0x2000, 0x2000),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+        assert_eq!(vm_size, 0x4000);
+
+        // a zero sized segment does not stretch the range
+        let vm_size = crate::segments_vm_size(
+            [
+                segment("__PAD", 0, 0),
+                segment("__TEXT", 0x1000, 0x1000),
+                segment("__PAD", u64::MAX, 0),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+        assert_eq!(vm_size, 0x2000);
+
+        let err = crate::segments_vm_size(
+            [
+                segment("__TEXT", 0x1000, 0x1000),
+                segment("__HUGE", u64::MAX - 0x10, 0x1000),
+            ]
+            .into_iter(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::LoadError::AddressOverflow { segment } if segment == "__HUGE"
+        ));
+    }
+
+    #[test]
+    pub fn test_section_check_containment() {
+        let section = |name: &str, addr, size| (name.to_string(), addr, size);
+
+        assert!(
+            crate::section_check_containment(
+                "__TEXT",
+                0x1000,
+                0x1000,
+                [
+                    section("__text", 0x1000, 0x800),
+                    section("__const", 0x1800, 0x800)
+                ]
+                .into_iter()
+            )
+            .is_ok()
+        );
+
+        // starts in the segment, ends past it
+        let err = crate::section_check_containment(
+            "__TEXT",
+            0x1000,
+            0x1000,
+            [
+                section("__text", 0x1000, 0x800),
+                section("__const", 0x1800, 0x1000),
+            ]
+            .into_iter(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::LoadError::SectionOutsideSegment { segment, section }
+                if segment == "__TEXT" && section == "__const"
+        ));
+
+        // wraps around the address space
+        assert!(
+            crate::section_check_containment(
+                "__TEXT",
+                0x1000,
+                0x1000,
+                [section("__text", 0x1000, u64::MAX)].into_iter()
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    pub fn test_segments_check_overlap() {
+        let segment = |name: &str, start, end| (name.to_string(), start, end);
+
+        assert!(
+            crate::segments_check_overlap(
+                [
+                    segment("__TEXT", 0x1000, 0x2000),
+                    segment("__DATA", 0x2000, 0x3000),
+                ]
+                .into_iter()
+            )
+            .is_ok()
+        );
+
+        let err = crate::segments_check_overlap(
+            [
+                segment("__TEXT", 0x1000, 0x3000),
+                segment("__DATA", 0x2000, 0x4000),
+            ]
+            .into_iter(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::LoadError::OverlappingSegments { a, b } if a == "__TEXT" && b == "__DATA"
+        ));
+    }
+
+    #[test]
+    pub fn test_byte_swapped_image() {
+        let mut image = [0u8; 32];
+        image[..4].copy_from_slice(&crate::mach::MH_CIGAM_64.to_le_bytes());
+
+        let err = unsafe { Task::try_with_pointer(image.as_ptr(), image.len()) }.unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::LoadError::UnsupportedEndianness { .. }
+        ));
+    }
+
+    #[test]
+    pub fn test_fat_without_64_bit_slice() {
+        // fat_header and two fat_arch, big-endian: i386 and armv7
+        let mut image = Vec::new();
+        for word in [0xcafebabe_u32, 2] {
+            image.extend_from_slice(&word.to_be_bytes());
+        }
+        for (cputype, offset) in [(7_u32, 0x1000_u32), (12, 0x2000)] {
+            for word in [cputype, 0, offset, 0x1000, 12] {
+                image.extend_from_slice(&word.to_be_bytes());
+            }
+        }
+        image.resize(0x3000, 0);
+
+        let err = unsafe { Task::try_with_pointer(image.as_ptr(), image.len()) }.unwrap_err();
+        match err {
+            crate::error::LoadError::No64BitArch { found } => assert_eq!(found, ["i386", "arm"]),
+            err => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    pub fn test_fat_without_arm64_slice() {
+        // fat_header and one fat_arch, big-endian: x86_64
+        let mut image = Vec::new();
+        for word in [0xcafebabe_u32, 1, 0x01000007, 3, 0x1000, 0x1000, 12] {
+            image.extend_from_slice(&word.to_be_bytes());
+        }
+        image.resize(0x2000, 0);
+
+        let err = unsafe { Task::try_with_pointer(image.as_ptr(), image.len()) }.unwrap_err();
+        match err {
+            crate::error::LoadError::ArchNotFound { requested, found } => {
+                assert_eq!(requested, "arm64");
+                assert_eq!(found, ["x86_64"]);
+            }
+            err => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    pub fn test_invalid_image_pointer() {
+        use crate::error::LoadError;
+
+        let err = unsafe { Task::try_with_pointer(std::ptr::null(), 0x1000) }.unwrap_err();
+        assert!(matches!(err, LoadError::NullImage));
+
+        let image = [0_u8; 4];
+        let err = unsafe { Task::try_with_pointer(image.as_ptr(), 0) }.unwrap_err();
+        assert!(matches!(err, LoadError::EmptyImage));
+
+        let err = unsafe { Task::try_with_pointer(image.as_ptr(), image.len()) }.unwrap_err();
+        assert!(matches!(err, LoadError::Parse(_)));
+    }
+
+    #[test]
+    pub fn test_header_flags() {
+        use crate::mach::{
+            MH_DYLDLINK, MH_HAS_TLV_DESCRIPTORS, MH_PIE, MH_TWOLEVEL, MachHeaderFlags,
+        };
+
+        let flags = MachHeaderFlags(MH_DYLDLINK | MH_TWOLEVEL | MH_PIE);
+        assert!(flags.pie());
+        assert!(flags.contains(MH_DYLDLINK | MH_TWOLEVEL));
+        assert!(!flags.contains(MH_DYLDLINK | MH_HAS_TLV_DESCRIPTORS));
+        assert!(!flags.has_tlv_descriptors());
+        assert!(!flags.no_heap_execution());
+        assert!(!flags.allow_stack_execution());
+        assert_eq!(
+            format!("{flags:?}"),
+            "MachHeaderFlags(MH_DYLDLINK|MH_TWOLEVEL|MH_PIE)"
+        );
+    }
+
+    #[test]
+    pub fn test_executable_without_pie() {
+        // a bare mach_header_64 of an arm64 MH_EXECUTE, no load commands
+        let mut image = Vec::new();
+        for word in [0xfeedfacf_u32, 0x0100000c, 0, 2, 0, 0, 0x85, 0] {
+            image.extend_from_slice(&word.to_le_bytes());
+        }
+
+        let err = unsafe { Task::try_with_pointer(image.as_ptr(), image.len()) }.unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::LoadError::NotPositionIndependent
+        ));
+    }
+
+    #[test]
+    pub fn test_truncated_load_commands() {
+        // a mach_header_64 of an arm64 MH_DYLIB declaring 16 bytes of
+        // commands, followed by a 24 byte LC_UUID
+        let mut image = Vec::new();
+        for word in [0xfeedfacf_u32, 0x0100000c, 0, 6, 1, 0x10, 0, 0, 0x1b, 0x18] {
+            image.extend_from_slice(&word.to_le_bytes());
+        }
+        image.extend_from_slice(&[0xab; 16]);
+
+        let err = unsafe { Task::try_with_pointer(image.as_ptr(), image.len()) }.unwrap_err();
+        match err {
+            crate::error::LoadError::LoadCommandMismatch {
+                ncmds,
+                sizeofcmds,
+                parsed_size,
+            } => {
+                assert_eq!(ncmds, 1);
+                assert_eq!((sizeofcmds, parsed_size), (0x10, 0x18));
+            }
+            err => panic!("unexpected error: {err}"),
+        }
     }
 }
@@ -1,149 +1,912 @@
+use std::ffi::CString;
+
 use crate::{
-    Section, Segment, Task,
-    mach::{S_LAZY_SYMBOL_POINTERS, S_NON_LAZY_SYMBOL_POINTERS, SECTION_TYPE},
+    FlatLookup, RTLD_FIRST, RTLD_LAZY, RTLD_NOLOAD, Section, Task, dlerror_string,
+    dyld::dyld_stand_in,
+    error::{DlError, LinkError},
+    mach::{
+        INDIRECT_SYMBOL_ABS, INDIRECT_SYMBOL_LOCAL, MH_TWOLEVEL, N_EXT, N_UNDF, N_WEAK_REF,
+        S_LAZY_SYMBOL_POINTERS, S_NON_LAZY_SYMBOL_POINTERS, SECTION_TYPE, VM_PROT_COPY,
+        try_vm_protect, vm_page_size, vm_region_containing_self,
+    },
+    pac::{self, PacKey},
+    symbols::{Nlist, symbol_address_from_nlist},
 };
 
-#[unsafe(naked)]
-#[unsafe(no_mangle)]
-/// Pointer Authenticate au random pointer
-///
-/// Signs the 'modifier' (`p`) with the key `context`.
-/// The instruction computes and inserting a Pointer Authentication Code for `p`
-/// and returns it signed.
-///
-/// https://developer.arm.com/documentation/ddi0602/2025-09/Base-Instructions/PACIA--PACIA1716--PACIASP--PACIAZ--PACIZA--Pointer-Authentication-Code-for-instruction-address--using-key-A-
-pub unsafe extern "C" fn pacia(p: u64, context: u64) -> u64 {
-    core::arch::naked_asm!("pacia x0, x1", "ret")
+const POINTER_SIZE: usize = 8;
+
+/// The dylib name of imports bound to the loaded image itself
+pub const SELF_IMAGE: &str = "<self>";
+
+/// The dylib name of imports looked up in every loaded image
+pub const FLAT_LOOKUP: &str = "<flat>";
+
+/// The dylib name of dyld functions bound to a stand-in
+pub const DYLD_STAND_IN: &str = "<dyld>";
+
+#[inline]
+pub fn get_library_ordinal(n_desc: u32) -> u8 {
+    ((n_desc >> 8) & 0xff) as u8
+}
+
+// The special library ordinals of an nlist, as defined in "mach-o/nlist.h"
+const SELF_LIBRARY_ORDINAL: u8 = 0x0;
+const DYNAMIC_LOOKUP_ORDINAL: u8 = 0xfe;
+const EXECUTABLE_ORDINAL: u8 = 0xff;
+
+// The special ordinals of the bind opcodes and chained fixups, as
+// defined in "mach-o/loader.h"
+const BIND_SPECIAL_DYLIB_SELF: i32 = 0;
+const BIND_SPECIAL_DYLIB_MAIN_EXECUTABLE: i32 = -1;
+const BIND_SPECIAL_DYLIB_FLAT_LOOKUP: i32 = -2;
+const BIND_SPECIAL_DYLIB_WEAK_LOOKUP: i32 = -3;
+
+/// Returns the library ordinal of an nlist `n_desc` with its special
+/// values mapped to the `BIND_SPECIAL_DYLIB_*` ordinals.
+fn nlist_ordinal(n_desc: u16) -> i32 {
+    match get_library_ordinal(n_desc as u32) {
+        SELF_LIBRARY_ORDINAL => BIND_SPECIAL_DYLIB_SELF,
+        EXECUTABLE_ORDINAL => BIND_SPECIAL_DYLIB_MAIN_EXECUTABLE,
+        DYNAMIC_LOOKUP_ORDINAL => BIND_SPECIAL_DYLIB_FLAT_LOOKUP,
+        ordinal => ordinal as i32,
+    }
+}
+
+/// The state of a global offset table slot after linking
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GotStatus {
+    /// points into a loaded image
+    Bound,
+
+    /// was never written to
+    Null,
+
+    /// points outside of any loaded image
+    Unmapped,
+}
+
+/// A single slot of `__got` or `__auth_got`, as read back after linking
+#[derive(Debug, Clone)]
+pub struct GotEntry {
+    /// name of the section the slot lives in
+    pub section: String,
+
+    /// index of the slot within the section
+    pub slot_index: usize,
+
+    /// address of the slot in memory
+    pub slot_addr: usize,
+
+    /// the symbol the slot was bound to, if known
+    pub symbol: Option<String>,
+
+    /// the value currently held by the slot, without its PAC
+    pub resolved_addr: usize,
+
+    pub status: GotStatus,
 }
 
-#[unsafe(naked)]
-#[unsafe(no_mangle)]
-/// Authenticate a pointer previously signed with `context`
+/// Returns the number of entries of a symbol pointer section.
 ///
-/// The pointer that is authenticated must have been previously signed.
-/// If the authentication passes, the upper bits of the address are restored and
-/// the pointer is returned.
+/// The section has to hold a whole number of pointers, and each of them
+/// needs its own indirect symbol table entry from `reserved1` on.
+fn symbol_pointers_count(
+    section: &str,
+    size: usize,
+    reserved1: u32,
+    indirect_symbols: usize,
+) -> Result<usize, LinkError> {
+    if !size.is_multiple_of(POINTER_SIZE) {
+        return Err(LinkError::MisalignedSymbolPointers {
+            section: section.to_string(),
+            size,
+        });
+    }
+
+    let count = size / POINTER_SIZE;
+    if (reserved1 as usize)
+        .checked_add(count)
+        .is_none_or(|end| end > indirect_symbols)
+    {
+        return Err(LinkError::IndirectSymbolsOverrun {
+            section: section.to_string(),
+            first: reserved1 as usize,
+            count,
+        });
+    }
+
+    Ok(count)
+}
+
+/// What an indirect symbol table entry binds its pointer to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IndirectEntry {
+    /// a definition of the image itself, the pointer only needs the slide
+    Local,
+
+    /// an absolute value, the pointer is left as is
+    Absolute,
+
+    /// the symbol at this index of the symbol table
+    Symbol(usize),
+}
+
+/// Decodes an indirect symbol table entry, `None` if it is neither a
+/// sentinel nor the index of one of the `symbols` symbols.
+fn indirect_entry(entry: u32, symbols: usize) -> Option<IndirectEntry> {
+    if entry & INDIRECT_SYMBOL_ABS != 0 {
+        // INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS included
+        return Some(IndirectEntry::Absolute);
+    }
+    if entry == INDIRECT_SYMBOL_LOCAL {
+        return Some(IndirectEntry::Local);
+    }
+
+    let index = entry as usize;
+    (index < symbols).then_some(IndirectEntry::Symbol(index))
+}
+
+/// Looks `name` up in the dylib `handle` and the dylibs it re-exports.
 ///
-/// https://developer.arm.com/documentation/ddi0602/2025-09/Base-Instructions/AUTIA--AUTIA1716--AUTIASP--AUTIAZ--AUTIZA--Authenticate-instruction-address--using-key-A-
-pub unsafe extern "C" fn autia(p: u64, context: u64) -> u64 {
-    core::arch::naked_asm!("autia x0, x1", "ret")
+/// Dylibs living in the dyld shared cache often only re-export what
+/// they are linked against (`libSystem` re-exports `libsystem_c`, ...),
+/// `dlsym` on their handle follows the re-exports. A symbol found
+/// nowhere among them is not looked up any further: the image names
+/// the dylib of each import, and dyld fails the bind in that case.
+pub(crate) fn dylib_symbol(handle: u64, name: &CString) -> *mut libc::c_void {
+    unsafe { libc::dlsym(handle as *mut libc::c_void, name.as_ptr()) }
 }
 
-// unsafe extern "C" {
-//     pub fn pacia(p: u64, context: u64) -> u64;
-//     pub fn autia(p: u64, context: u64) -> u64;
-// }
+/// Returns every distinct definition of `name` among the images dyld
+/// loaded, with the path of the image defining it.
+///
+/// Dylibs re-exporting the symbol of another (`libSystem` and
+/// `libsystem_c`, ...) resolve to the same address, which is only
+/// listed once.
+fn loaded_definitions(name: &CString) -> Vec<(usize, String)> {
+    let mut definitions = Vec::<(usize, String)>::new();
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    pub fn test_pac() {
-        unsafe {
-            let ptr: u64 = 0x0000_1234_5678_9abc;
-            let ctx_ok: u64 = 666;
-            let ctx_bad: u64 = 777;
+    for index in 0..unsafe { libc::_dyld_image_count() } {
+        let path = unsafe { libc::_dyld_get_image_name(index) };
+        if path.is_null() {
+            continue;
+        }
 
-            let signed = crate::linker::pacia(ptr, ctx_ok);
-            let auth_ok = crate::linker::autia(signed, ctx_ok);
-            let auth_bad = crate::linker::autia(signed, ctx_bad);
+        let handle = unsafe { libc::dlopen(path, RTLD_LAZY | RTLD_NOLOAD | RTLD_FIRST) };
+        if handle.is_null() {
+            continue;
+        }
+        let address = unsafe { libc::dlsym(handle, name.as_ptr()) }.addr();
+        unsafe { libc::dlclose(handle) };
 
-            assert!(signed != ptr);
-            assert!(auth_ok == ptr);
-            assert!(auth_bad != ptr);
+        if address != 0 && definitions.iter().all(|(other, _)| *other != address) {
+            let path = unsafe { std::ffi::CStr::from_ptr(path) };
+            definitions.push((address, path.to_string_lossy().into_owned()));
         }
     }
+    definitions
 }
 
-#[inline]
-pub fn get_library_ordinal(n_desc: u32) -> u8 {
-    ((n_desc >> 8) & 0xff) as u8
+/// An import bound by [`Task::resolve_all_imports`]
+#[derive(Debug, Clone)]
+pub struct ImportBinding {
+    /// the imported symbol, without the leading '_'
+    pub symbol: String,
+
+    /// the dylib it was found in
+    pub dylib: String,
+
+    /// address of the symbol pointer that was written
+    pub slot_addr: usize,
+
+    /// the address the symbol resolved to, an arm64e `__auth_got` slot
+    /// holds it signed
+    pub target: usize,
+}
+
+impl Task {
+    /// Binds every entry of every symbol pointer section (`__got`,
+    /// `__auth_got`, `__la_symbol_ptr`, ...) and returns what each was
+    /// bound to.
+    ///
+    /// Entries are matched to their symbol through the indirect symbol
+    /// table, starting at the `reserved1` index of their section, and
+    /// looked up with `dlsym` in the dylib named by the symbol's library
+    /// ordinal. Lazy pointers are bound eagerly, like the others, unless
+    /// the `lazy_binding` feature is on and
+    /// [`crate::TaskOptions::lazy_binding`] set. They then point at a
+    /// trampoline binding them on first call, which is what is returned.
+    ///
+    /// Entries marked `INDIRECT_SYMBOL_LOCAL` point into the image and
+    /// get the slide, unless the rebase opcodes already moved them.
+    /// Entries marked `INDIRECT_SYMBOL_ABS` are left untouched. Neither
+    /// is returned as a binding.
+    ///
+    /// The binds of the `LC_DYLD_CHAINED_FIXUPS` chains are bound after
+    /// those, see [`Task::ordinal_target`]. Pointers already bound
+    /// through the indirect symbol table are not bound twice.
+    ///
+    /// The sections have to be writable, see
+    /// [`Task::segments_protect_maxprot`].
+    pub fn resolve_all_imports(&mut self) -> Result<Vec<ImportBinding>, LinkError> {
+        let mut bindings = Vec::new();
+
+        for segment in &self.segments {
+            for section in &segment.sections {
+                if !matches!(
+                    section.flags as i32 & SECTION_TYPE,
+                    S_NON_LAZY_SYMBOL_POINTERS | S_LAZY_SYMBOL_POINTERS
+                ) {
+                    continue;
+                }
+
+                let sectname = section.name.trim_end_matches('\0');
+                let sect_ptr = unsafe { self.memory.add(section.vm_addr).as_ptr() as *mut u64 };
+
+                let count = symbol_pointers_count(
+                    sectname,
+                    section.vm_size,
+                    section.reserved1,
+                    self.indirect_symbols.len(),
+                )?;
+
+                for slot_index in 0..count {
+                    let index = section.reserved1 as usize + slot_index;
+                    let slot = unsafe { sect_ptr.add(slot_index) };
+
+                    let entry = indirect_entry(self.indirect_symbols[index], self.symtab.len())
+                        .ok_or_else(|| LinkError::BadIndirectIndex {
+                            section: sectname.to_string(),
+                            index,
+                        })?;
+
+                    let (symbol_index, nlist) = match entry {
+                        IndirectEntry::Symbol(symbol_index) => {
+                            (symbol_index, &self.symtab[symbol_index])
+                        }
+                        IndirectEntry::Absolute => continue,
+                        IndirectEntry::Local => {
+                            let value = unsafe { slot.read() };
+                            if self
+                                .segments
+                                .iter()
+                                .any(|segment| segment.contains_vm_addr(value as usize))
+                            {
+                                unsafe { slot.write(value.wrapping_add(self.slide() as u64)) };
+                            }
+                            continue;
+                        }
+                    };
+
+                    if let Some(n_strx) = nlist.bad_strx {
+                        return Err(LinkError::BadStringIndex {
+                            index: symbol_index,
+                            n_strx,
+                        });
+                    }
+                    let symbol = nlist.name.strip_prefix('_').unwrap_or(&nlist.name);
+
+                    #[cfg(feature = "lazy_binding")]
+                    if self.lazy_binding
+                        && section.flags as i32 & SECTION_TYPE == S_LAZY_SYMBOL_POINTERS
+                        && let Some((dylib, entry)) =
+                            self.lazy_target(symbol, nlist, slot.addr())?
+                    {
+                        unsafe { slot.write(entry as u64) };
+                        bindings.push(ImportBinding {
+                            symbol: symbol.to_string(),
+                            dylib,
+                            slot_addr: slot.addr(),
+                            target: entry,
+                        });
+                        continue;
+                    }
+
+                    // a missing weak import is null
+                    let Some((dylib, target)) = self.import_target(symbol, nlist)? else {
+                        unsafe { slot.write(0) };
+                        continue;
+                    };
+
+                    // https://developer.arm.com/documentation/ddi0602/2025-09/Base-Instructions/BRAA--BRAAZ--BRAB--BRABZ--Branch-to-register--with-pointer-authentication-
+                    //
+                    // Jumps to an authenticated pointer seems to be done through a wrapper in __auth_stubs.
+                    // It doesn't directly jumps to these pointers in the offset table, it jumps to a wrapper which
+                    // authenticates that pointer first.
+                    //
+                    // This is an example of a wrapper (in this case ___assert_rtn)
+                    //
+                    // First it loads the address where that signed pointer is into x17.
+                    // The pointer at that address has to be signed.
+                    //
+                    //     adrp    x17, 0x100008000 // offset table
+                    //     add     x17, x17, #0x28  // index within the table
+                    //
+                    // Then it loads the actual pointer itself into x16.
+                    //
+                    //     ldr     x16, [x17]
+                    //
+                    // Then it does an authenticated jump to that address. It does so by
+                    // branching to the (authenticated) pointer using the location of it as
+                    // modifier.
+                    //
+                    //     braa    x16, x17 // jumps or faults if no match
+                    //
+                    // The slot address is the modifier, an arm64e __auth_got
+                    // slot is signed with it. arm64 binaries only have plain
+                    // pointers, and branch to them with `br`.
+                    unsafe { slot.write(self.slot_value(section, slot.addr(), target)) };
+
+                    bindings.push(ImportBinding {
+                        symbol: symbol.to_string(),
+                        dylib,
+                        slot_addr: slot.addr(),
+                        target,
+                    });
+                }
+            }
+        }
+
+        for bind in &self.chained_binds {
+            let slot = unsafe { self.memory.add(bind.offset).as_ptr() as *mut u64 };
+            if bindings
+                .iter()
+                .any(|binding| binding.slot_addr == slot.addr())
+            {
+                continue;
+            }
+
+            let import = &self.chained_imports[bind.import];
+            let symbol = import.name.strip_prefix('_').unwrap_or(&import.name);
+
+            // a missing weak import stays null
+            let Some((dylib, target)) =
+                self.ordinal_target(symbol, import.lib_ordinal, import.weak)?
+            else {
+                continue;
+            };
+            let target = target.wrapping_add_signed((import.addend + bind.addend) as isize);
+
+            // an authenticated bind is signed as the fixup says
+            let value = match bind.auth {
+                Some(auth) => auth.sign(target as u64, slot.addr()),
+                None => target as u64,
+            };
+            unsafe { slot.write(value) };
+
+            bindings.push(ImportBinding {
+                symbol: symbol.to_string(),
+                dylib,
+                slot_addr: slot.addr(),
+                target,
+            });
+        }
+
+        // the descriptor thunks were just bound to dyld's
+        self.tlv_init()?;
+
+        Ok(bindings)
+    }
+
+    /// Returns the dylib an import is bound from and its address,
+    /// `None` if it is weak and was not found.
+    ///
+    /// Positive ordinals index the dylibs, a symbol missing from its
+    /// dylib is looked up in every loaded image only for a flat namespace
+    /// image. The imports of a weak dylib that was not found are
+    /// missing. The special ordinals resolve as dyld does:
+    ///
+    /// - `BIND_SPECIAL_DYLIB_SELF` and `BIND_SPECIAL_DYLIB_MAIN_EXECUTABLE`
+    ///   bind to the image's own definition, the loaded image standing in
+    ///   for the main executable.
+    /// - `BIND_SPECIAL_DYLIB_FLAT_LOOKUP` looks in every loaded image in
+    ///   load order: the image's own exports first, then the
+    ///   [preloaded](crate::TaskOptions::preload_dylibs) dylibs, then
+    ///   everything `dlsym(RTLD_DEFAULT)` sees. With [`FlatLookup::Strict`] it fails
+    ///   if those images do not all agree.
+    /// - `BIND_SPECIAL_DYLIB_WEAK_LOOKUP` does the same with the image's
+    ///   own weak definitions only.
+    ///
+    /// Whatever the ordinal, the dyld functions that list the images bind
+    /// to their stand-ins, except from the image itself.
+    fn ordinal_target(
+        &self,
+        symbol: &str,
+        ordinal: i32,
+        weak: bool,
+    ) -> Result<Option<(String, usize)>, LinkError> {
+        let unresolved = |dylib: &str, error: String| LinkError::UnresolvedSymbol {
+            symbol: symbol.to_string(),
+            dylib: dylib.to_string(),
+            error: DlError(error),
+        };
+        let name = CString::new(symbol)
+            .map_err(|_| unresolved(SELF_IMAGE, "the name holds a nul byte".to_string()))?;
+
+        if !matches!(
+            ordinal,
+            BIND_SPECIAL_DYLIB_SELF | BIND_SPECIAL_DYLIB_MAIN_EXECUTABLE
+        ) && let Some(stand_in) = dyld_stand_in(symbol)
+        {
+            return Ok(Some((DYLD_STAND_IN.to_string(), stand_in)));
+        }
+
+        let (dylib, target) = match ordinal {
+            BIND_SPECIAL_DYLIB_SELF | BIND_SPECIAL_DYLIB_MAIN_EXECUTABLE => (
+                SELF_IMAGE.to_string(),
+                self.symbol_address(symbol).unwrap_or(0),
+            ),
+            BIND_SPECIAL_DYLIB_FLAT_LOOKUP | BIND_SPECIAL_DYLIB_WEAK_LOOKUP => {
+                let own = match ordinal {
+                    BIND_SPECIAL_DYLIB_FLAT_LOOKUP => self.exported_definition(symbol),
+                    _ => self.weak_definition(symbol),
+                };
+
+                if ordinal == BIND_SPECIAL_DYLIB_FLAT_LOOKUP
+                    && self.flat_lookup == FlatLookup::Strict
+                {
+                    let mut definitions = loaded_definitions(&name);
+                    if let Some(own) = own
+                        && definitions.iter().all(|(other, _)| *other != own.address)
+                    {
+                        definitions.insert(0, (own.address, SELF_IMAGE.to_string()));
+                    }
+                    if definitions.len() > 1 {
+                        return Err(LinkError::AmbiguousSymbol {
+                            symbol: symbol.to_string(),
+                            images: definitions.into_iter().map(|(_, image)| image).collect(),
+                        });
+                    }
+                }
+
+                // the image, then the preloaded dylibs, then everything
+                // else that is loaded
+                match own {
+                    Some(definition) => (SELF_IMAGE.to_string(), definition.address),
+                    None => self
+                        .preloaded
+                        .iter()
+                        .map(|(dylib, handle)| (dylib.clone(), dylib_symbol(*handle, &name).addr()))
+                        .find(|(_, target)| *target != 0)
+                        .unwrap_or_else(|| {
+                            (FLAT_LOOKUP.to_string(), unsafe {
+                                libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()).addr()
+                            })
+                        }),
+                }
+            }
+            ordinal => {
+                let (dylib, handle) = usize::try_from(ordinal)
+                    .ok()
+                    .and_then(|ordinal| ordinal.checked_sub(1))
+                    .and_then(|index| self.dylibs.get(index))
+                    .ok_or_else(|| LinkError::BadOrdinal {
+                        symbol: symbol.to_string(),
+                        ordinal: ordinal as u16,
+                    })?;
+                // a weak dylib that was not found has no handle
+                let mut target = match *handle {
+                    0 => std::ptr::null_mut(),
+                    handle => dylib_symbol(handle, &name),
+                };
+
+                // a flat namespace image only records the dylib a symbol
+                // came from at link time, dyld looks in every image
+                if target.is_null() && self.flat_namespace() {
+                    target = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()) };
+                }
+                if target.is_null() && *handle == 0 && !weak {
+                    return Err(unresolved(
+                        dylib,
+                        "the weak dylib was not found".to_string(),
+                    ));
+                }
+                (dylib.clone(), target.addr())
+            }
+        };
+
+        match target {
+            0 if weak => Ok(None),
+            0 if dylib == SELF_IMAGE => Err(unresolved(
+                &dylib,
+                "the image does not define it".to_string(),
+            )),
+            0 => Err(unresolved(&dylib, dlerror_string())),
+            target => Ok(Some((dylib, target))),
+        }
+    }
+
+    /// Returns true if the image was linked with `-flat_namespace`, its
+    /// imports are then looked up in every loaded image.
+    fn flat_namespace(&self) -> bool {
+        self.header.flags & MH_TWOLEVEL == 0
+    }
+
+    /// Returns the dylib an import is bound from and its address, `None`
+    /// if it is weak (`N_WEAK_REF`) and was not found.
+    ///
+    /// See [`Task::ordinal_target`] for the special library ordinals.
+    ///
+    /// # Local entries
+    ///
+    /// Not every symbol pointer is an import: images linked with
+    /// `-interposable`, among others, reach their own functions through
+    /// them too. A symbol the image defines (`N_SECT`, `N_ABS`) binds to
+    /// its `n_value`, slid, without any lookup. Only undefined symbols
+    /// are looked up by ordinal.
+    ///
+    /// # Weak definitions
+    ///
+    /// dyld coalesces weak definitions (C++ templates and inline
+    /// functions) so that every image uses a single copy. The loaded
+    /// image is the first image in load order, so an import it also
+    /// defines is bound to its own definition, which is also what its
+    /// internal references use. Imports the image does not define are
+    /// looked up in their dylib as usual.
+    fn import_target(
+        &self,
+        symbol: &str,
+        nlist: &Nlist,
+    ) -> Result<Option<(String, usize)>, LinkError> {
+        if let Some(definition) = self.weak_definition(symbol) {
+            return Ok(Some((SELF_IMAGE.to_string(), definition.address)));
+        }
+
+        if let Some(address) = symbol_address_from_nlist(nlist.n_type, nlist.n_value, self.slide())
+        {
+            return Ok(Some((SELF_IMAGE.to_string(), address)));
+        }
+
+        self.ordinal_target(
+            symbol,
+            nlist_ordinal(nlist.n_desc),
+            nlist.n_desc & N_WEAK_REF != 0,
+        )
+    }
+
+    /// Resolves the imports of the symbol table into [`Task::symbols`],
+    /// the way [`Task::resolve_all_imports`] binds them.
+    ///
+    /// Every undefined external symbol is looked up by its library
+    /// ordinal, special ordinals included, a missing weak import is 0.
+    /// Loading does not call it, unresolved imports are reported when
+    /// linking.
+    pub fn symbols_init(&mut self) -> Result<(), LinkError> {
+        let mut symbols = Vec::new();
+
+        for (index, nlist) in self.symtab.iter().enumerate() {
+            if nlist.n_type != N_UNDF | N_EXT {
+                continue;
+            }
+            if let Some(n_strx) = nlist.bad_strx {
+                return Err(LinkError::BadStringIndex { index, n_strx });
+            }
+
+            let symbol = nlist.name.strip_prefix('_').unwrap_or(&nlist.name);
+            let target = self
+                .import_target(symbol, nlist)?
+                .map_or(0, |(_, target)| target);
+            symbols.push((symbol.to_string(), target as u64));
+        }
+
+        self.symbols = symbols;
+        Ok(())
+    }
+
+    /// Registers the lazy symbol pointer `slot` for binding on first
+    /// call and returns its dylib and the trampoline to write to it,
+    /// see [`crate::lazy`].
+    ///
+    /// Returns `None` for imports to bind right away: those the image
+    /// defines itself, those with a special ordinal, the dyld functions
+    /// with a stand-in, those of a flat namespace image or of a weak
+    /// dylib that was not found, and any once the trampolines ran out.
+    #[cfg(feature = "lazy_binding")]
+    fn lazy_target(
+        &self,
+        symbol: &str,
+        nlist: &Nlist,
+        slot: usize,
+    ) -> Result<Option<(String, usize)>, LinkError> {
+        let ordinal = nlist_ordinal(nlist.n_desc);
+        if self.weak_definition(symbol).is_some()
+            || symbol_address_from_nlist(nlist.n_type, nlist.n_value, 0).is_some()
+            || ordinal <= 0
+            || dyld_stand_in(symbol).is_some()
+            || self.flat_namespace()
+        {
+            return Ok(None);
+        }
+
+        let (dylib, handle) =
+            self.dylibs
+                .get(ordinal as usize - 1)
+                .ok_or_else(|| LinkError::BadOrdinal {
+                    symbol: symbol.to_string(),
+                    ordinal: ordinal as u16,
+                })?;
+
+        // a weak dylib that was not found, bound to null right away
+        if *handle == 0 {
+            return Ok(None);
+        }
+        Ok(crate::lazy::lazy_register(slot, symbol, *handle).map(|entry| (dylib.clone(), entry)))
+    }
+
+    /// Returns the address of every symbol pointer that binds `symbol`,
+    /// with its section.
+    fn import_slots(&self, symbol: &str) -> Vec<(&Section, usize)> {
+        let mut slots = Vec::new();
+
+        for section in self.segments.iter().flat_map(|segment| &segment.sections) {
+            if !matches!(
+                section.flags as i32 & SECTION_TYPE,
+                S_NON_LAZY_SYMBOL_POINTERS | S_LAZY_SYMBOL_POINTERS
+            ) {
+                continue;
+            }
+
+            for slot_index in 0..section.vm_size / POINTER_SIZE {
+                let Some(IndirectEntry::Symbol(symbol_index)) = self
+                    .indirect_symbols
+                    .get(section.reserved1 as usize + slot_index)
+                    .and_then(|entry| indirect_entry(*entry, self.symtab.len()))
+                else {
+                    continue;
+                };
+                let nlist = &self.symtab[symbol_index];
+
+                if nlist.name.strip_prefix('_').unwrap_or(&nlist.name) == symbol {
+                    let slot_addr = section.vm_addr + slot_index * POINTER_SIZE;
+                    slots.push((section, unsafe {
+                        self.memory.add(slot_addr).as_ptr().addr()
+                    }));
+                }
+            }
+        }
+        slots
+    }
+
+    /// Returns what a symbol pointer of `section` at `slot_addr` has to
+    /// hold to point at `target`.
+    ///
+    /// A slot with an authenticated chained bind is signed the way the
+    /// fixup says. Other `__auth_got` slots of an arm64e image are signed
+    /// with the IA key and the slot address, as its `__auth_stubs` check
+    /// them with `braa x16, x17`. Other slots hold `target` as is.
+    fn slot_value(&self, section: &Section, slot_addr: usize, target: usize) -> u64 {
+        let offset = slot_addr - self.memory.as_ptr().addr();
+        let auth = self
+            .chained_binds
+            .iter()
+            .find(|bind| bind.offset == offset)
+            .and_then(|bind| bind.auth);
+
+        match auth {
+            Some(auth) => auth.sign(target as u64, slot_addr),
+            None if self.is_arm64e() && section.name() == "__auth_got" => {
+                PacKey::InstructionA.sign(target as u64, slot_addr as u64)
+            }
+            None => target as u64,
+        }
+    }
+
+    /// Redirects the import `symbol` to `target`, after binding.
+    ///
+    /// Every symbol pointer bound to `symbol` is overwritten. A page that
+    /// was sealed read-only, `__DATA_CONST` once [`Task::prepare`] ran,
+    /// gets a private writable copy for the write (`VM_PROT_COPY`, as
+    /// [`Task::reload_segment`] does), then its protection and maximum
+    /// protection are restored.
+    pub fn override_import(&mut self, symbol: &str, target: usize) -> Result<(), LinkError> {
+        let slots = self.import_slots(symbol);
+        if slots.is_empty() {
+            return Err(LinkError::UnknownImport {
+                symbol: symbol.to_string(),
+            });
+        }
+
+        let page_size = vm_page_size();
+        for (section, slot) in slots {
+            let region = vm_region_containing_self(slot as u64).map_err(LinkError::Vm)?;
+            let page = (slot & !(page_size - 1)) as u64;
+
+            let writable = region.protection & libc::VM_PROT_WRITE != 0;
+            if !writable {
+                try_vm_protect(
+                    page,
+                    page_size,
+                    false as i32,
+                    region.protection | libc::VM_PROT_WRITE | VM_PROT_COPY,
+                )
+                .map_err(LinkError::Vm)?;
+            }
+
+            unsafe { (slot as *mut u64).write(self.slot_value(section, slot, target)) };
+
+            if !writable {
+                try_vm_protect(page, page_size, true as i32, region.max_protection)
+                    .map_err(LinkError::Vm)?;
+                try_vm_protect(page, page_size, false as i32, region.protection)
+                    .map_err(LinkError::Vm)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads back every global offset table slot.
+    ///
+    /// This is a debugging aid, slots that are still null or that
+    /// do not point into any loaded image are flagged, since those only
+    /// show up as crashes once the program calls through them.
+    ///
+    /// Slots are named after the symbol the indirect symbol table gives
+    /// them, as [`Task::resolve_all_imports`] binds them, or after their
+    /// chained import. The PAC of a signed `__auth_got` slot is stripped
+    /// before the value is looked up.
+    pub fn dump_got(&self) -> Vec<GotEntry> {
+        let mut entries = Vec::new();
+
+        for section in self.segments.iter().flat_map(|segment| &segment.sections) {
+            if section.flags as i32 & SECTION_TYPE != S_NON_LAZY_SYMBOL_POINTERS {
+                continue;
+            }
+
+            let sect_ptr = unsafe { self.memory.add(section.vm_addr).as_ptr() as *const u64 };
+            for index in 0..section.vm_size / POINTER_SIZE {
+                let slot = unsafe { sect_ptr.add(index) };
+                let resolved_addr = pac::strip(unsafe { slot.read() }) as usize;
+
+                let status = if resolved_addr == 0 {
+                    GotStatus::Null
+                } else {
+                    let mut info = std::mem::MaybeUninit::<libc::Dl_info>::uninit();
+                    match unsafe {
+                        libc::dladdr(resolved_addr as *const libc::c_void, info.as_mut_ptr())
+                    } {
+                        0 => GotStatus::Unmapped,
+                        _ => GotStatus::Bound,
+                    }
+                };
+
+                entries.push(GotEntry {
+                    section: section.name().to_string(),
+                    slot_index: index,
+                    slot_addr: slot.addr(),
+                    symbol: self.slot_symbol(section, index),
+                    resolved_addr,
+                    status,
+                });
+            }
+        }
+        entries
+    }
+
+    /// Returns the symbol the `slot_index`th pointer of the symbol
+    /// pointer section `section` is bound to, without the leading '_'.
+    fn slot_symbol(&self, section: &Section, slot_index: usize) -> Option<String> {
+        let entry = self
+            .indirect_symbols
+            .get(section.reserved1 as usize + slot_index)
+            .and_then(|entry| indirect_entry(*entry, self.symtab.len()));
+
+        let name = match entry {
+            Some(IndirectEntry::Symbol(symbol_index)) => &self.symtab[symbol_index].name,
+            _ => {
+                let offset = section.vm_addr + slot_index * POINTER_SIZE;
+                let bind = self
+                    .chained_binds
+                    .iter()
+                    .find(|bind| bind.offset == offset)?;
+                &self.chained_imports[bind.import].name
+            }
+        };
+        Some(name.strip_prefix('_').unwrap_or(name).to_string())
+    }
 }
 
 #[derive(Debug, Default)]
 /// A dynamic linker
+///
+/// # Interaction with dyld
+///
+/// The loaded image is never registered with the real dyld, which
+/// has a few consequences:
+///
+/// - `_dyld_get_image_header`, `_dyld_image_count`, `_dyld_get_image_name`,
+///   `_dyld_get_image_vmaddr_slide` and `_NSGetMachExecuteHeader` are
+///   bound to stand-ins that list the loaded images before
+///   the images of the host.
+/// - `__dyld_private` is left zeroed. It is only read by the lazy binding
+///   stub helpers (`dyld_stub_binder`), which are never reached since
+///   every import is bound without them.
+/// - `dyld_all_image_infos` and `dladdr` are the hosts, debuggers will not
+///   see the loaded image through them.
 pub struct Linker {}
 impl Linker {
     pub fn new() -> Self {
         Self {}
     }
 
+    /// Binds every import of `task`, see [`Task::resolve_all_imports`].
+    ///
+    /// The symbol pointers live in `__DATA_CONST`, which is read-only once
+    /// the task is sealed by [`Task::segments_protect`]. A sealed task is
+    /// made writable for binding and sealed again afterwards.
+    ///
+    /// Panics if an import can not be bound.
     pub fn link_raw(&mut self, task: &mut Task) {
-        for Segment {
-            name: segname,
-            sections,
-            ..
-        } in &task.segments
-        {
-            if segname != "__DATA_CONST\0\0\0\0" {
-                continue;
-            }
+        let sealed = task.sealed;
+        if sealed {
+            task.try_segments_unseal()
+                .unwrap_or_else(|err| panic!("failed to unseal segments: {err}"));
+        }
 
-            for Section {
-                name: sectname,
-                flags,
-                vm_addr,
-                vm_size,
-                ..
-            } in sections
-            {
-                let (is_got, is_auth_got) = (
-                    sectname == "__got\0\0\0\0\0\0\0\0\0\0\0",
-                    sectname == "__auth_got\0\0\0\0\0\0",
-                );
-
-                if is_got || is_auth_got {
-                    if matches!(
-                        *flags as i32 & SECTION_TYPE,
-                        S_NON_LAZY_SYMBOL_POINTERS | S_LAZY_SYMBOL_POINTERS
-                    ) {
-                        unsafe {
-                            let sect_ptr = task.memory.add(*vm_addr).as_ptr() as *mut u64;
-                            for index in 0..vm_size / 8 {
-                                let linked_sym_offset = task
-                                    .symbols
-                                    .get(index)
-                                    .map(|(_, symoff)| symoff.to_owned())
-                                    .expect("symbol not found");
-
-                                let dst_ptr = sect_ptr.add(index);
-
-                                if is_auth_got {
-                                    // https://developer.arm.com/documentation/ddi0602/2025-09/Base-Instructions/BRAA--BRAAZ--BRAB--BRABZ--Branch-to-register--with-pointer-authentication-
-                                    //
-                                    // Jumps to an authenticated pointer seems to be done through a wrapper in __auth_stubs.
-                                    // It doesn't directly jumps to these pointers in the offset table, it jumps to a wrapper which
-                                    // authenticates that pointer first.
-                                    //
-                                    // This is an example of a wrapper (in this case ___assert_rtn)
-                                    //
-                                    // First it loads the address where that signed pointer is into x17.
-                                    // The pointer at that address has to be signed.
-                                    //
-                                    //     adrp    x17, 0x100008000 // offset table
-                                    //     add     x17, x17, #0x28  // index within the table
-                                    //
-                                    // Then it loads the actual pointer itself into x16.
-                                    //
-                                    //     ldr     x16, [x17]
-                                    //
-                                    // Then it does an authenticated jump to that address. It does so by
-                                    // branching to the (authenticated) pointer using the location of it as
-                                    // modifier.
-                                    //
-                                    //     braa    x16, x17 // jumps or faults if no match
-
-                                    // linked_sym_offset =
-                                    //     pacia(linked_sym_offset, dst_ptr.addr() as u64);
-                                }
-
-                                *dst_ptr = linked_sym_offset;
-                            }
-                        }
-                    } else {
-                        panic!("unsupported flags: {}", *flags as i32 & SECTION_TYPE);
-                    }
-                }
-            }
+        if let Err(err) = task.resolve_all_imports() {
+            panic!("failed to link: {err}");
         }
+
+        if sealed {
+            task.segments_protect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::LinkError,
+        linker::{IndirectEntry, indirect_entry, nlist_ordinal, symbol_pointers_count},
+        mach::{INDIRECT_SYMBOL_ABS, INDIRECT_SYMBOL_LOCAL},
+    };
+
+    #[test]
+    pub fn test_symbol_pointers_count() {
+        assert_eq!(symbol_pointers_count("__got", 0x18, 2, 5).unwrap(), 3);
+
+        // not a whole number of pointers
+        assert!(matches!(
+            symbol_pointers_count("__got", 0x14, 0, 5),
+            Err(LinkError::MisalignedSymbolPointers { size: 0x14, .. })
+        ));
+
+        // runs past the end of the indirect symbol table
+        assert!(matches!(
+            symbol_pointers_count("__got", 0x18, 3, 5),
+            Err(LinkError::IndirectSymbolsOverrun {
+                first: 3,
+                count: 3,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    pub fn test_nlist_ordinal() {
+        assert_eq!(nlist_ordinal(0x0100), 1);
+        assert_eq!(nlist_ordinal(0x0240), 2);
+        assert_eq!(nlist_ordinal(0x0000), 0);
+        assert_eq!(nlist_ordinal(0xfe00), -2);
+        assert_eq!(nlist_ordinal(0xff00), -1);
+    }
+
+    #[test]
+    pub fn test_indirect_entry() {
+        assert_eq!(indirect_entry(2, 3), Some(IndirectEntry::Symbol(2)));
+        assert_eq!(indirect_entry(3, 3), None);
+
+        assert_eq!(
+            indirect_entry(INDIRECT_SYMBOL_LOCAL, 3),
+            Some(IndirectEntry::Local)
+        );
+        assert_eq!(
+            indirect_entry(INDIRECT_SYMBOL_ABS, 3),
+            Some(IndirectEntry::Absolute)
+        );
+        assert_eq!(
+            indirect_entry(INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS, 3),
+            Some(IndirectEntry::Absolute)
+        );
     }
 }
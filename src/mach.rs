@@ -2,6 +2,8 @@ use std::ptr::NonNull;
 
 use libc::{mach_error_string, mach_port_t};
 
+use crate::error::VmError;
+
 #[allow(nonstandard_style)]
 pub type kern_return_t = libc::c_int;
 
@@ -13,15 +15,12 @@ pub type task_t = mach_port_name_t;
 
 const KERN_SUCCESS: kern_return_t = libc::KERN_SUCCESS;
 
-/// Abort everything
-///
-/// Panics with a descriptive Mach kernel error message for the given `kern_return_t`.
-/// Falls back to a placeholder if no message or invalid UTF-8 is returned.
-fn panic_kr_error(kern_return: kern_return_t) -> ! {
+/// Returns the Mach kernel error message for the given `kern_return_t`.
+pub fn kr_error_string(kern_return: kern_return_t) -> String {
     let msg = unsafe {
         let ptr = mach_error_string(kern_return);
         if ptr.is_null() {
-            panic!("panic_kr_error: {kern_return}: no error message");
+            return format!("{kern_return}: no error message");
         }
 
         std::ffi::CStr::from_ptr(ptr)
@@ -29,7 +28,15 @@ fn panic_kr_error(kern_return: kern_return_t) -> ! {
             .unwrap_or("<invalid utf8>")
     };
 
-    panic!("panic_kr_error: {kern_return}: {msg}");
+    format!("{kern_return}: {msg}")
+}
+
+/// Abort everything
+///
+/// Panics with a descriptive Mach kernel error message for the given `kern_return_t`.
+/// Falls back to a placeholder if no message or invalid UTF-8 is returned.
+fn panic_kr_error(kern_return: kern_return_t) -> ! {
+    panic!("panic_kr_error: {}", kr_error_string(kern_return));
 }
 
 unsafe extern "C" {
@@ -43,12 +50,42 @@ unsafe extern "C" {
     /// with the same user ID.
     ///
     /// Note: if pid == 0, an error is return no matter who is calling.
+    #[link_name = "task_for_pid"]
     pub fn _task_for_pid(target_tport: task_t, pid: libc::c_int, tn: *mut task_t) -> kern_return_t;
+
+    /// Releases a user reference on the port `name` of `task`.
+    pub fn mach_port_deallocate(task: task_t, name: mach_port_name_t) -> kern_return_t;
 }
 
+//	Allocate new VM region at the specified virtual address, if possible.
+const VM_FLAGS_FIXED: i32 = 0x00000000;
+
 //	Allocate new VM region anywhere it would fit in the address space.
 const VM_FLAGS_ANYWHERE: i32 = 0x00000001;
 
+// The flavor of mach_vm_region that returns a `vm_region_basic_info_64`
+const VM_REGION_BASIC_INFO_64: libc::c_int = 9;
+
+/// Basic information about a VM region as defined in "mach/vm_region.h"
+///
+/// The header declares it under `#pragma pack(4)`.
+#[repr(C, packed(4))]
+#[derive(Debug, Default, Clone, Copy)]
+#[allow(nonstandard_style)]
+pub struct vm_region_basic_info_64 {
+    pub protection: libc::vm_prot_t,
+    pub max_protection: libc::vm_prot_t,
+    pub inheritance: libc::vm_inherit_t,
+    pub shared: libc::boolean_t,
+    pub reserved: libc::boolean_t,
+    pub offset: libc::memory_object_offset_t,
+    pub behavior: libc::c_int,
+    pub user_wired_count: libc::c_ushort,
+}
+
+const VM_REGION_BASIC_INFO_COUNT_64: libc::mach_msg_type_number_t =
+    (size_of::<vm_region_basic_info_64>() / size_of::<libc::c_int>()) as _;
+
 unsafe extern "C" {
     /// Allocate a region of virtual memory.
     /// As opposed to `vm_allocate`, this function allocates 64-bit memory
@@ -76,6 +113,18 @@ unsafe extern "C" {
         size: libc::mach_vm_size_t,
     ) -> kern_return_t;
 
+    /// Returns information about the region at or after `address`.
+    /// `address` and `size` are updated to describe that region.
+    pub fn mach_vm_region(
+        target: task_t,
+        address: *mut libc::mach_vm_address_t,
+        size: *mut libc::mach_vm_size_t,
+        flavor: libc::c_int,
+        info: *mut libc::c_int,
+        info_count: *mut libc::mach_msg_type_number_t,
+        object_name: *mut mach_port_t,
+    ) -> kern_return_t;
+
     /// Change memory protection for a region of virtual memory.
     /// As opposed to `vm_protect`, this function works with 64-bit addresses
     pub fn mach_vm_protect(
@@ -85,6 +134,36 @@ unsafe extern "C" {
         set_maximum: libc::boolean_t,
         new_protection: libc::vm_prot_t,
     ) -> kern_return_t;
+
+    /// Discards the instruction cache for `len` bytes at `start`, as
+    /// defined in "libkern/OSCacheControl.h"
+    pub fn sys_icache_invalidate(start: *mut libc::c_void, len: libc::size_t);
+}
+
+/// Logs a Mach VM call, its arguments and what it returned at debug
+/// level, with the `trace_syscalls` feature.
+///
+/// Errors surface as a [`VmError`] naming the call that failed, this
+/// tells which one it was among the hundreds a load makes.
+#[cfg(feature = "trace_syscalls")]
+macro_rules! vm_trace {
+    ($call:literal, $kern_return:expr, $($args:tt)+) => {
+        log::debug!(
+            target: "loader::vm",
+            "{}({}) = {} ({})",
+            $call,
+            format_args!($($args)+),
+            $kern_return,
+            kr_error_string($kern_return)
+        )
+    };
+}
+
+/// Expands to nothing without the `trace_syscalls` feature, the
+/// arguments are not even formatted.
+#[cfg(not(feature = "trace_syscalls"))]
+macro_rules! vm_trace {
+    ($($tokens:tt)*) => {};
 }
 
 #[inline]
@@ -113,6 +192,21 @@ fn mach_task_self() -> mach_port_t {
 pub const SECTION_TYPE: i32 = 0x000000ff; // 256 section types
 pub const SECTION_ATTRIBUTES: u32 = 0xffffff00; // 24 section attributes
 
+// Constants for the section attributes part of the flags field
+pub const S_ATTR_PURE_INSTRUCTIONS: u32 = 0x80000000; // section contains only true machine instructions
+pub const S_ATTR_SOME_INSTRUCTIONS: u32 = 0x00000400; // section contains some machine instructions
+
+// Constants for the type of a section
+pub const S_ZEROFILL: i32 = 0x1; // zero fill on demand section
+pub const S_CSTRING_LITERALS: i32 = 0x2; // section with only literal C strings
+pub const S_MOD_INIT_FUNC_POINTERS: i32 = 0x9; // section with only function pointers for initialization
+pub const S_MOD_TERM_FUNC_POINTERS: i32 = 0xa; // section with only function pointers for termination
+pub const S_GB_ZEROFILL: i32 = 0xc; // zero fill on demand section (that can be larger than 4 gigabytes)
+pub const S_THREAD_LOCAL_REGULAR: i32 = 0x11; // template of initial values for TLVs
+pub const S_THREAD_LOCAL_ZEROFILL: i32 = 0x12; // template of initial values for TLVs that are zero filled
+pub const S_THREAD_LOCAL_VARIABLES: i32 = 0x13; // TLV descriptors
+pub const S_INIT_FUNC_OFFSETS: i32 = 0x16; // 32-bit offsets to initializers
+
 // For the two types of symbol pointers sections and the symbol stubs section
 // they have indirect symbol table entries.  For each of the entries in the
 // section the indirect symbol table entries, in corresponding order in the
@@ -126,6 +220,185 @@ pub const SECTION_ATTRIBUTES: u32 = 0xffffff00; // 24 section attributes
 pub const S_NON_LAZY_SYMBOL_POINTERS: i32 = 0x6; // section with only non-lazy  symbol pointers
 pub const S_LAZY_SYMBOL_POINTERS: i32 = 0x7; // section with only lazy symbol
 
+// An indirect symbol table entry is normally the index of the symbol it
+// binds. These two values instead mark a pointer to a local definition,
+// which may be combined with INDIRECT_SYMBOL_ABS for an absolute one.
+pub const INDIRECT_SYMBOL_LOCAL: u32 = 0x80000000;
+pub const INDIRECT_SYMBOL_ABS: u32 = 0x40000000;
+
+// The n_type field of an nlist_64 is a byte broken down into:
+pub const N_STAB: u8 = 0xe0; // if any of these bits set, a symbolic debugging entry
+pub const N_PEXT: u8 = 0x10; // private external symbol bit
+pub const N_TYPE: u8 = 0x0e; // mask for the type bits
+pub const N_EXT: u8 = 0x01; // external symbol bit, set for external symbols
+
+// Values for N_TYPE bits of the n_type field.
+pub const N_UNDF: u8 = 0x0; // undefined, n_sect == NO_SECT
+pub const N_ABS: u8 = 0x2; // absolute, n_sect == NO_SECT
+pub const N_SECT: u8 = 0xe; // defined in section number n_sect
+pub const N_PBUD: u8 = 0xc; // prebound undefined (defined in a dylib)
+pub const N_INDR: u8 = 0xa; // indirect
+
+// Flags in the n_desc field of an nlist_64
+pub const N_WEAK_REF: u16 = 0x0040; // symbol is weak referenced
+pub const N_WEAK_DEF: u16 = 0x0080; // coalesced symbol is a weak definition
+
+// Flags of a terminal node of the export trie
+pub const EXPORT_SYMBOL_FLAGS_KIND_MASK: u64 = 0x03; // mask for the kind bits
+pub const EXPORT_SYMBOL_FLAGS_KIND_ABSOLUTE: u64 = 0x02; // the address is absolute
+pub const EXPORT_SYMBOL_FLAGS_REEXPORT: u64 = 0x08; // re-exported from a dylib, no address
+pub const EXPORT_SYMBOL_FLAGS_STUB_AND_RESOLVER: u64 = 0x10; // followed by the resolver offset
+
+// Constants for the flags field of the segment_command
+pub const SG_READ_ONLY: u32 = 0x10; // made read-only by dyld once fixups are applied
+
+// Extra bit of a vm_prot_t, as defined in "mach/vm_prot.h"
+pub const VM_PROT_COPY: libc::vm_prot_t = 0x10; // copy-on-write the range, allowing writes
+
+// Constants for the magic field of the mach_header
+pub const MH_MAGIC: u32 = 0xfeedface; // the mach magic number
+pub const MH_CIGAM: u32 = 0xcefaedfe; // NXSwapInt(MH_MAGIC)
+pub const MH_MAGIC_64: u32 = 0xfeedfacf; // the 64-bit mach magic number
+pub const MH_CIGAM_64: u32 = 0xcffaedfe; // NXSwapInt(MH_MAGIC_64)
+
+// Constants for the cpusubtype field of the mach_header
+pub const CPU_SUBTYPE_MASK: u32 = 0xff000000; // mask for feature flags
+pub const CPU_SUBTYPE_ARM64E: u32 = 2; // arm64 with pointer authentication
+
+// Constants for the filetype field of the mach_header
+pub const MH_OBJECT: u32 = 0x1; // relocatable object file
+pub const MH_EXECUTE: u32 = 0x2; // demand paged executable file
+pub const MH_FVMLIB: u32 = 0x3; // fixed VM shared library file
+pub const MH_CORE: u32 = 0x4; // core file
+pub const MH_PRELOAD: u32 = 0x5; // preloaded executable file
+pub const MH_DYLIB: u32 = 0x6; // dynamically bound shared library
+pub const MH_DYLINKER: u32 = 0x7; // dynamic link editor
+pub const MH_BUNDLE: u32 = 0x8; // dynamically bound bundle file
+pub const MH_DYLIB_STUB: u32 = 0x9; // shared library stub for static linking only
+pub const MH_DSYM: u32 = 0xa; // companion file with only debug sections
+pub const MH_KEXT_BUNDLE: u32 = 0xb; // x86_64 kexts
+pub const MH_FILESET: u32 = 0xc; // set of mach-o's
+
+// Constants for the flags field of the mach_header
+pub const MH_NOUNDEFS: u32 = 0x1; // the object file has no undefined references
+pub const MH_INCRLINK: u32 = 0x2; // the object file is the output of an incremental link
+pub const MH_DYLDLINK: u32 = 0x4; // the object file is input for the dynamic linker
+pub const MH_BINDATLOAD: u32 = 0x8; // the undefined references are bound by the dynamic linker when loaded
+pub const MH_PREBOUND: u32 = 0x10; // the file has its dynamic undefined references prebound
+pub const MH_SPLIT_SEGS: u32 = 0x20; // the file has its read-only and read-write segments split
+pub const MH_LAZY_INIT: u32 = 0x40; // the shared library init routine is to be run lazily
+pub const MH_TWOLEVEL: u32 = 0x80; // the image is using two-level name space bindings
+pub const MH_FORCE_FLAT: u32 = 0x100; // the executable is forcing all images to use flat name space bindings
+pub const MH_NOMULTIDEFS: u32 = 0x200; // this umbrella guarantees no multiple definitions of symbols
+pub const MH_NOFIXPREBINDING: u32 = 0x400; // do not have dyld notify the prebinding agent
+pub const MH_PREBINDABLE: u32 = 0x800; // the binary is not prebound but can have its prebinding redone
+pub const MH_ALLMODSBOUND: u32 = 0x1000; // this binary binds to all two-level namespace modules
+pub const MH_SUBSECTIONS_VIA_SYMBOLS: u32 = 0x2000; // safe to divide up the sections into sub-sections
+pub const MH_CANONICAL: u32 = 0x4000; // the binary has been canonicalized via the unprebind operation
+pub const MH_WEAK_DEFINES: u32 = 0x8000; // the final linked image contains external weak symbols
+pub const MH_BINDS_TO_WEAK: u32 = 0x10000; // the final linked image uses weak symbols
+pub const MH_ALLOW_STACK_EXECUTION: u32 = 0x20000; // all stacks in the task will be given stack execution privilege
+pub const MH_ROOT_SAFE: u32 = 0x40000; // the binary declares it is safe for use in processes with uid zero
+pub const MH_SETUID_SAFE: u32 = 0x80000; // the binary declares it is safe for use in processes when issetugid() is true
+pub const MH_NO_REEXPORTED_DYLIBS: u32 = 0x100000; // the dylib has no re-exported dylibs
+pub const MH_PIE: u32 = 0x200000; // the OS will load the main executable at a random address
+pub const MH_DEAD_STRIPPABLE_DYLIB: u32 = 0x400000; // the dylib can be dead stripped by the static linker
+pub const MH_HAS_TLV_DESCRIPTORS: u32 = 0x800000; // contains a section of type S_THREAD_LOCAL_VARIABLES
+pub const MH_NO_HEAP_EXECUTION: u32 = 0x1000000; // the OS will run the main executable with a non-executable heap
+pub const MH_APP_EXTENSION_SAFE: u32 = 0x02000000; // the code was linked for use in an application extension
+pub const MH_NLIST_OUTOFSYNC_WITH_DYLDINFO: u32 = 0x04000000; // the external symbols listed in the nlist symbol table do not include all the symbols listed in the dyld info
+pub const MH_SIM_SUPPORT: u32 = 0x08000000; // allow LC_MIN_VERSION_MACOS and LC_BUILD_VERSION load commands
+pub const MH_DYLIB_IN_CACHE: u32 = 0x80000000; // the dylib is part of the dyld shared cache
+
+/// The mach_header `flags`, decoded
+///
+/// Only the flags that change how an image has to be loaded have their
+/// own accessor, [`MachHeaderFlags::contains`] tests the others.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct MachHeaderFlags(pub u32);
+
+impl MachHeaderFlags {
+    /// Returns the raw `flags`.
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns true if every bit of `flag` is set.
+    pub fn contains(self, flag: u32) -> bool {
+        self.0 & flag == flag
+    }
+
+    /// `MH_PIE`: the executable can be loaded at any address. Dylibs
+    /// and bundles always can, they do not carry the flag.
+    pub fn pie(self) -> bool {
+        self.contains(MH_PIE)
+    }
+
+    /// `MH_NO_HEAP_EXECUTION`: the heap is never executable, which
+    /// arm64 enforces for every process anyway.
+    pub fn no_heap_execution(self) -> bool {
+        self.contains(MH_NO_HEAP_EXECUTION)
+    }
+
+    /// `MH_ALLOW_STACK_EXECUTION`: the program expects executable
+    /// stacks, which arm64 never grants.
+    pub fn allow_stack_execution(self) -> bool {
+        self.contains(MH_ALLOW_STACK_EXECUTION)
+    }
+
+    /// `MH_HAS_TLV_DESCRIPTORS`: the image has thread-local variables,
+    /// a `S_THREAD_LOCAL_VARIABLES` section of descriptors to set up.
+    pub fn has_tlv_descriptors(self) -> bool {
+        self.contains(MH_HAS_TLV_DESCRIPTORS)
+    }
+}
+
+impl std::fmt::Debug for MachHeaderFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "MachHeaderFlags({})",
+            crate::report::mh_flags_into_string(self.0)
+        )
+    }
+}
+
+/// The names of the mach_header flags, in bit order
+pub const MH_FLAG_NAMES: &[(u32, &str)] = &[
+    (MH_NOUNDEFS, "MH_NOUNDEFS"),
+    (MH_INCRLINK, "MH_INCRLINK"),
+    (MH_DYLDLINK, "MH_DYLDLINK"),
+    (MH_BINDATLOAD, "MH_BINDATLOAD"),
+    (MH_PREBOUND, "MH_PREBOUND"),
+    (MH_SPLIT_SEGS, "MH_SPLIT_SEGS"),
+    (MH_LAZY_INIT, "MH_LAZY_INIT"),
+    (MH_TWOLEVEL, "MH_TWOLEVEL"),
+    (MH_FORCE_FLAT, "MH_FORCE_FLAT"),
+    (MH_NOMULTIDEFS, "MH_NOMULTIDEFS"),
+    (MH_NOFIXPREBINDING, "MH_NOFIXPREBINDING"),
+    (MH_PREBINDABLE, "MH_PREBINDABLE"),
+    (MH_ALLMODSBOUND, "MH_ALLMODSBOUND"),
+    (MH_SUBSECTIONS_VIA_SYMBOLS, "MH_SUBSECTIONS_VIA_SYMBOLS"),
+    (MH_CANONICAL, "MH_CANONICAL"),
+    (MH_WEAK_DEFINES, "MH_WEAK_DEFINES"),
+    (MH_BINDS_TO_WEAK, "MH_BINDS_TO_WEAK"),
+    (MH_ALLOW_STACK_EXECUTION, "MH_ALLOW_STACK_EXECUTION"),
+    (MH_ROOT_SAFE, "MH_ROOT_SAFE"),
+    (MH_SETUID_SAFE, "MH_SETUID_SAFE"),
+    (MH_NO_REEXPORTED_DYLIBS, "MH_NO_REEXPORTED_DYLIBS"),
+    (MH_PIE, "MH_PIE"),
+    (MH_DEAD_STRIPPABLE_DYLIB, "MH_DEAD_STRIPPABLE_DYLIB"),
+    (MH_HAS_TLV_DESCRIPTORS, "MH_HAS_TLV_DESCRIPTORS"),
+    (MH_NO_HEAP_EXECUTION, "MH_NO_HEAP_EXECUTION"),
+    (MH_APP_EXTENSION_SAFE, "MH_APP_EXTENSION_SAFE"),
+    (
+        MH_NLIST_OUTOFSYNC_WITH_DYLDINFO,
+        "MH_NLIST_OUTOFSYNC_WITH_DYLDINFO",
+    ),
+    (MH_SIM_SUPPORT, "MH_SIM_SUPPORT"),
+    (MH_DYLIB_IN_CACHE, "MH_DYLIB_IN_CACHE"),
+];
+
 /// Internal function that calls mach_vm_allocate from
 /// "mach/mach_vm.c"
 unsafe fn vm_alloc_internal(size: usize) -> Result<NonNull<u8>, kern_return_t> {
@@ -138,12 +411,138 @@ unsafe fn vm_alloc_internal(size: usize) -> Result<NonNull<u8>, kern_return_t> {
             VM_FLAGS_ANYWHERE,
         )
     };
+    vm_trace!(
+        "mach_vm_allocate",
+        kern_return,
+        "size: {size:#x}, anywhere -> {addr:#x}"
+    );
     match kern_return {
         KERN_SUCCESS => NonNull::new(addr as *mut u8).ok_or_else(|| panic!("ptr is null")),
         _ => Err(kern_return),
     }
 }
 
+/// A mapped region of the address space, as returned by `mach_vm_region`
+#[derive(Debug, Clone, Copy)]
+pub struct VmRegion {
+    /// start of the region
+    pub address: u64,
+
+    /// size of the region
+    pub size: u64,
+
+    /// current protection
+    pub protection: libc::vm_prot_t,
+
+    /// maximum protection
+    pub max_protection: libc::vm_prot_t,
+}
+
+/// Returns the region containing `address`, or the first one after it.
+pub fn vm_region_self(address: libc::mach_vm_address_t) -> Result<VmRegion, VmError> {
+    let mut region_address = address;
+    let mut region_size = 0;
+    let mut info = vm_region_basic_info_64::default();
+    let mut info_count = VM_REGION_BASIC_INFO_COUNT_64;
+    let mut object_name = 0;
+
+    let kern_return = unsafe {
+        mach_vm_region(
+            mach_task_self(),
+            &mut region_address,
+            &mut region_size,
+            VM_REGION_BASIC_INFO_64,
+            (&raw mut info).cast(),
+            &mut info_count,
+            &mut object_name,
+        )
+    };
+    vm_trace!(
+        "mach_vm_region",
+        kern_return,
+        "address: {address:#x} -> {region_address:#x}-{:#x}, protection: {}/{}",
+        region_address + region_size,
+        crate::vm_prot_into_string(info.protection),
+        crate::vm_prot_into_string(info.max_protection)
+    );
+
+    match kern_return {
+        KERN_SUCCESS => Ok(VmRegion {
+            address: region_address,
+            size: region_size,
+            protection: info.protection,
+            max_protection: info.max_protection,
+        }),
+        kern_return => Err(VmError::Kern {
+            call: "mach_vm_region",
+            kern_return,
+        }),
+    }
+}
+
+/// Returns the region containing `address`.
+///
+/// `mach_vm_region` moves on to the next region when `address` is in
+/// a hole, which would describe the wrong memory.
+pub fn vm_region_containing_self(address: libc::mach_vm_address_t) -> Result<VmRegion, VmError> {
+    match vm_region_self(address) {
+        Ok(region) if region.address > address => Err(VmError::AddressUnmapped { address }),
+        Err(VmError::Kern {
+            kern_return: libc::KERN_INVALID_ADDRESS,
+            ..
+        }) => Err(VmError::AddressUnmapped { address }),
+        region => region,
+    }
+}
+
+/// Allocates `size` bytes exactly at `address` on the current task.
+///
+/// If the range is already in use, the error names the mapping that
+/// is in the way, which is the actionable part when a position
+/// dependent image can not be placed.
+pub fn vm_alloc_fixed_self(
+    address: libc::mach_vm_address_t,
+    size: usize,
+) -> Result<NonNull<u8>, VmError> {
+    let mut addr = address;
+    let kern_return = unsafe {
+        mach_vm_allocate(
+            mach_task_self(),
+            &mut addr,
+            size as libc::mach_vm_size_t,
+            VM_FLAGS_FIXED,
+        )
+    };
+    vm_trace!(
+        "mach_vm_allocate",
+        kern_return,
+        "address: {address:#x}, size: {size:#x}, fixed"
+    );
+
+    match kern_return {
+        KERN_SUCCESS => NonNull::new(addr as *mut u8).ok_or(VmError::Kern {
+            call: "mach_vm_allocate",
+            kern_return,
+        }),
+        libc::KERN_NO_SPACE => match vm_region_self(address) {
+            Ok(region) if region.address < address + size as u64 => Err(VmError::RegionOccupied {
+                address,
+                size: size as u64,
+                region_start: region.address,
+                region_end: region.address + region.size,
+            }),
+            _ => Err(VmError::Kern {
+                call: "mach_vm_allocate",
+                kern_return,
+            }),
+        },
+        kern_return => Err(VmError::Kern {
+            call: "mach_vm_allocate",
+            kern_return,
+        }),
+    }
+}
+
 /// Internal function that calls mach_vm_deallocate from
 /// "mach/mach_vm.c"
 unsafe fn vm_dealloc_internal(
@@ -152,6 +551,11 @@ unsafe fn vm_dealloc_internal(
 ) -> Result<(), kern_return_t> {
     let kern_return =
         unsafe { mach_vm_deallocate(mach_task_self(), address, size as libc::mach_vm_size_t) };
+    vm_trace!(
+        "mach_vm_deallocate",
+        kern_return,
+        "address: {address:#x}, size: {size:#x}"
+    );
 
     match kern_return {
         KERN_SUCCESS => Ok(()),
@@ -168,6 +572,11 @@ unsafe fn vm_copy_overwrite_internal(
 ) -> Result<(), kern_return_t> {
     let kern_return =
         unsafe { mach_vm_write(mach_task_self(), dst, src, count as libc::mach_vm_size_t) };
+    vm_trace!(
+        "mach_vm_write",
+        kern_return,
+        "address: {dst:#x}, data: {src:#x}, size: {count:#x}"
+    );
 
     match kern_return {
         KERN_SUCCESS => Ok(()),
@@ -185,6 +594,12 @@ unsafe fn vm_protect_internal(
 ) -> Result<(), kern_return_t> {
     unsafe {
         let kern_return = mach_vm_protect(mach_task_self(), ptr, size, set_maximum, protection);
+        vm_trace!(
+            "mach_vm_protect",
+            kern_return,
+            "address: {ptr:#x}, size: {size:#x}, set_maximum: {set_maximum}, protection: {}",
+            crate::vm_prot_into_string(protection)
+        );
         match kern_return {
             KERN_SUCCESS => Ok(()),
             kern_return => Err(kern_return),
@@ -192,6 +607,18 @@ unsafe fn vm_protect_internal(
     }
 }
 
+/// Returns the page size of the host.
+///
+/// 16KB on Apple silicon, 4KB on Intel.
+pub fn vm_page_size() -> usize {
+    unsafe { libc::vm_page_size }
+}
+
+/// Rounds `size` up to a multiple of the host page size.
+pub fn vm_page_round(size: usize) -> usize {
+    size.next_multiple_of(vm_page_size())
+}
+
 /// Allocates memory on the current task address space
 ///
 /// # Panics
@@ -201,6 +628,56 @@ pub fn vm_alloc_self(size: usize) -> NonNull<u8> {
     unsafe { vm_alloc_internal(size).unwrap_or_else(|kern_error| panic_kr_error(kern_error)) }
 }
 
+/// Same as [`vm_alloc_self`], returning the error instead of panicking.
+pub fn try_vm_alloc_self(size: usize) -> Result<NonNull<u8>, VmError> {
+    unsafe { vm_alloc_internal(size) }.map_err(|kern_return| VmError::Kern {
+        call: "mach_vm_allocate",
+        kern_return,
+    })
+}
+
+/// Replaces the `size` bytes at `address` on the current task with
+/// `MAP_JIT` memory.
+///
+/// The pages are mapped rwx, but each thread can only either write or
+/// execute them at a time, see [`jit_write_protect`]. Under the hardened
+/// runtime this needs the `com.apple.security.cs.allow-jit` entitlement.
+///
+/// `MAP_JIT` can not be combined with `MAP_FIXED`: the range is released
+/// and asked for again as a hint, which fails if another thread took it
+/// in between.
+#[cfg(feature = "jit")]
+pub fn vm_remap_jit_self(address: usize, size: usize) -> std::io::Result<()> {
+    vm_dealloc_self(address as libc::mach_vm_address_t, size);
+
+    let memory = unsafe {
+        libc::mmap(
+            std::ptr::without_provenance_mut(address),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+            libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_JIT,
+            -1,
+            0,
+        )
+    };
+
+    if memory == libc::MAP_FAILED {
+        return Err(std::io::Error::last_os_error());
+    }
+    if memory.addr() != address {
+        unsafe { libc::munmap(memory, size) };
+        return Err(std::io::ErrorKind::AddrInUse.into());
+    }
+    Ok(())
+}
+
+/// Switches the `MAP_JIT` pages of the calling thread between writable
+/// (`false`) and executable (`true`).
+#[cfg(feature = "jit")]
+pub fn jit_write_protect(enabled: bool) {
+    unsafe { libc::pthread_jit_write_protect_np(enabled as libc::c_int) }
+}
+
 /// Deallocates memory on the current task address space
 ///
 /// # Panics
@@ -215,12 +692,154 @@ pub fn vm_dealloc_self(address: libc::mach_vm_address_t, size: usize) {
 
 /// Copies `count` bytes from source into `dst`
 pub fn copy_from_image(src: libc::mach_vm_address_t, dst: libc::mach_vm_address_t, count: usize) {
-    unsafe {
-        vm_copy_overwrite_internal(src, dst, count)
-            .unwrap_or_else(|kern_error| panic_kr_error(kern_error))
+    try_copy_from_image(src, dst, count).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Same as [`copy_from_image`], returning the error instead of panicking.
+///
+/// Copying to pages that were already protected read-only means the
+/// segments were protected too early, or overlap. This is returned as
+/// [`VmError::WriteToProtected`], the pages are not made writable behind
+/// the caller's back.
+pub fn try_copy_from_image(
+    src: libc::mach_vm_address_t,
+    dst: libc::mach_vm_address_t,
+    count: usize,
+) -> Result<(), VmError> {
+    unsafe { vm_copy_overwrite_internal(src, dst, count) }.map_err(
+        |kern_return| match kern_return {
+            libc::KERN_PROTECTION_FAILURE => VmError::WriteToProtected {
+                address: dst,
+                size: count as u64,
+            },
+            kern_return => VmError::Kern {
+                call: "mach_vm_write",
+                kern_return,
+            },
+        },
+    )
+}
+
+/// Returns the task port of the process `pid`.
+///
+/// The caller needs the `com.apple.security.cs.debugger` entitlement,
+/// or to run as root, and the target must not be protected by the
+/// hardened runtime or SIP. The port is released by [`task_port_release`].
+pub fn task_for_pid(pid: libc::pid_t) -> Result<task_t, VmError> {
+    let mut task = 0;
+    match unsafe { _task_for_pid(mach_task_self(), pid, &mut task) } {
+        KERN_SUCCESS => Ok(task),
+        kern_return => Err(VmError::Kern {
+            call: "task_for_pid",
+            kern_return,
+        }),
+    }
+}
+
+/// Releases a task port returned by [`task_for_pid`].
+pub fn task_port_release(task: task_t) {
+    unsafe { mach_port_deallocate(mach_task_self(), task) };
+}
+
+/// Allocates `size` bytes anywhere in the address space of `task`.
+pub fn try_vm_alloc(task: task_t, size: usize) -> Result<libc::mach_vm_address_t, VmError> {
+    let mut address = 0;
+    let kern_return =
+        unsafe { mach_vm_allocate(task, &mut address, size as u64, VM_FLAGS_ANYWHERE) };
+    vm_trace!(
+        "mach_vm_allocate",
+        kern_return,
+        "task: {task}, size: {size:#x}, anywhere -> {address:#x}"
+    );
+    match kern_return {
+        KERN_SUCCESS => Ok(address),
+        kern_return => Err(VmError::Kern {
+            call: "mach_vm_allocate",
+            kern_return,
+        }),
+    }
+}
+
+/// Deallocates memory in the address space of `task`, errors are ignored.
+pub fn vm_dealloc(task: task_t, address: libc::mach_vm_address_t, size: usize) {
+    // only traced, the deallocation is best effort
+    let _kern_return = unsafe { mach_vm_deallocate(task, address, size as u64) };
+    vm_trace!(
+        "mach_vm_deallocate",
+        _kern_return,
+        "task: {task}, address: {address:#x}, size: {size:#x}"
+    );
+}
+
+/// Writes `data` at `address` in the address space of `task`.
+pub fn try_vm_write(
+    task: task_t,
+    address: libc::mach_vm_address_t,
+    data: &[u8],
+) -> Result<(), VmError> {
+    let kern_return = unsafe {
+        mach_vm_write(
+            task,
+            address,
+            data.as_ptr().addr() as libc::mach_vm_address_t,
+            data.len() as libc::mach_vm_size_t,
+        )
+    };
+    vm_trace!(
+        "mach_vm_write",
+        kern_return,
+        "task: {task}, address: {address:#x}, size: {:#x}",
+        data.len()
+    );
+    match kern_return {
+        KERN_SUCCESS => Ok(()),
+        kern_return => Err(VmError::Kern {
+            call: "mach_vm_write",
+            kern_return,
+        }),
     }
 }
 
+/// Same as [`try_vm_protect`], in the address space of `task`.
+pub fn try_vm_protect_task(
+    task: task_t,
+    address: libc::mach_vm_address_t,
+    size: usize,
+    set_maximum: libc::boolean_t,
+    protection: libc::vm_prot_t,
+) -> Result<(), VmError> {
+    let kern_return =
+        unsafe { mach_vm_protect(task, address, size as u64, set_maximum, protection) };
+    vm_trace!(
+        "mach_vm_protect",
+        kern_return,
+        "task: {task}, address: {address:#x}, size: {size:#x}, set_maximum: {set_maximum}, protection: {}",
+        crate::vm_prot_into_string(protection)
+    );
+    match kern_return {
+        KERN_SUCCESS => Ok(()),
+        kern_return => Err(VmError::Kern {
+            call: "mach_vm_protect",
+            kern_return,
+        }),
+    }
+}
+
+/// Same as [`vm_protect`], returning the error instead of panicking.
+pub fn try_vm_protect(
+    ptr: libc::mach_vm_address_t,
+    size: usize,
+    set_maximum: libc::boolean_t,
+    protection: libc::vm_prot_t,
+) -> Result<(), VmError> {
+    unsafe { vm_protect_internal(ptr, size as u64, set_maximum, protection) }.map_err(
+        |kern_return| VmError::Kern {
+            call: "mach_vm_protect",
+            kern_return,
+        },
+    )
+}
+
 /// Applies the protection to `ptr` -> `size`
 pub fn vm_protect(
     ptr: libc::mach_vm_address_t,
@@ -233,3 +852,30 @@ pub fn vm_protect(
             .unwrap_or_else(|kern_error| panic_kr_error(kern_error))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::VmError,
+        mach::{
+            try_copy_from_image, try_vm_alloc_self, try_vm_protect, vm_dealloc_self, vm_page_size,
+        },
+    };
+
+    #[test]
+    pub fn test_copy_to_protected() {
+        let size = vm_page_size();
+        let dst = try_vm_alloc_self(size).unwrap().as_ptr().addr() as u64;
+        let src = [0x42u8; 16];
+
+        try_vm_protect(dst, size, 0, libc::VM_PROT_READ).unwrap();
+
+        let err = try_copy_from_image(src.as_ptr().addr() as u64, dst, src.len()).unwrap_err();
+        assert!(
+            matches!(err, VmError::WriteToProtected { address, size: 16 } if address == dst),
+            "{err}"
+        );
+
+        vm_dealloc_self(dst, size);
+    }
+}
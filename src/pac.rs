@@ -0,0 +1,260 @@
+//! Pointer authentication (arm64e)
+//!
+//! A signed pointer carries a Pointer Authentication Code (PAC) in its
+//! unused upper bits, computed from the pointer, a per-process key and
+//! a 64-bit discriminator (the "context" or "modifier"). Authenticating
+//! with the same key and discriminator strips the PAC and returns the
+//! raw pointer, anything else yields a poisoned pointer that faults
+//! when used (or traps right away on CPUs with FEAT_FPAC).
+
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+/// Pointer Authenticate au random pointer
+///
+/// Signs the 'modifier' (`p`) with the key `context`.
+/// The instruction computes and inserting a Pointer Authentication Code for `p`
+/// and returns it signed.
+///
+/// https://developer.arm.com/documentation/ddi0602/2025-09/Base-Instructions/PACIA--PACIA1716--PACIASP--PACIAZ--PACIZA--Pointer-Authentication-Code-for-instruction-address--using-key-A-
+///
+/// # Safety
+///
+/// The CPU has to implement FEAT_PAuth, the instruction is undefined
+/// without it.
+pub unsafe extern "C" fn pacia(p: u64, context: u64) -> u64 {
+    core::arch::naked_asm!("pacia x0, x1", "ret")
+}
+
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+/// Authenticate a pointer previously signed with `context`
+///
+/// The pointer that is authenticated must have been previously signed.
+/// If the authentication passes, the upper bits of the address are restored and
+/// the pointer is returned.
+///
+/// https://developer.arm.com/documentation/ddi0602/2025-09/Base-Instructions/AUTIA--AUTIA1716--AUTIASP--AUTIAZ--AUTIZA--Authenticate-instruction-address--using-key-A-
+///
+/// # Safety
+///
+/// The CPU has to implement FEAT_PAuth, the instruction is undefined
+/// without it. On CPUs with FEAT_FPAC a failed authentication traps
+/// instead of returning a poisoned pointer, `p` has to have been signed
+/// with `context`.
+pub unsafe extern "C" fn autia(p: u64, context: u64) -> u64 {
+    core::arch::naked_asm!("autia x0, x1", "ret")
+}
+
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+/// Same as [`pacia`], for a data pointer
+///
+/// https://developer.arm.com/documentation/ddi0602/2025-09/Base-Instructions/PACDA--PACDZA--Pointer-Authentication-Code-for-data-address--using-key-A-
+///
+/// # Safety
+///
+/// The CPU has to implement FEAT_PAuth, the instruction is undefined
+/// without it.
+pub unsafe extern "C" fn pacda(p: u64, context: u64) -> u64 {
+    core::arch::naked_asm!("pacda x0, x1", "ret")
+}
+
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+/// Same as [`autia`], for a data pointer
+///
+/// https://developer.arm.com/documentation/ddi0602/2025-09/Base-Instructions/AUTDA--AUTDZA--Authenticate-data-address--using-key-A-
+///
+/// # Safety
+///
+/// The CPU has to implement FEAT_PAuth, the instruction is undefined
+/// without it. On CPUs with FEAT_FPAC a failed authentication traps
+/// instead of returning a poisoned pointer, `p` has to have been signed
+/// with `context`.
+pub unsafe extern "C" fn autda(p: u64, context: u64) -> u64 {
+    core::arch::naked_asm!("autda x0, x1", "ret")
+}
+
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+/// Same as [`pacia`], with the instruction key B
+///
+/// https://developer.arm.com/documentation/ddi0602/2025-09/Base-Instructions/PACIB--PACIB1716--PACIBSP--PACIBZ--PACIZB--Pointer-Authentication-Code-for-instruction-address--using-key-B-
+///
+/// # Safety
+///
+/// The CPU has to implement FEAT_PAuth, the instruction is undefined
+/// without it.
+pub unsafe extern "C" fn pacib(p: u64, context: u64) -> u64 {
+    core::arch::naked_asm!("pacib x0, x1", "ret")
+}
+
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+/// Same as [`autia`], with the instruction key B
+///
+/// https://developer.arm.com/documentation/ddi0602/2025-09/Base-Instructions/AUTIB--AUTIB1716--AUTIBSP--AUTIBZ--AUTIZB--Authenticate-instruction-address--using-key-B-
+///
+/// # Safety
+///
+/// Same as [`autia`].
+pub unsafe extern "C" fn autib(p: u64, context: u64) -> u64 {
+    core::arch::naked_asm!("autib x0, x1", "ret")
+}
+
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+/// Same as [`pacda`], with the data key B
+///
+/// https://developer.arm.com/documentation/ddi0602/2025-09/Base-Instructions/PACDB--PACDZB--Pointer-Authentication-Code-for-data-address--using-key-B-
+///
+/// # Safety
+///
+/// The CPU has to implement FEAT_PAuth, the instruction is undefined
+/// without it.
+pub unsafe extern "C" fn pacdb(p: u64, context: u64) -> u64 {
+    core::arch::naked_asm!("pacdb x0, x1", "ret")
+}
+
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+/// Same as [`autda`], with the data key B
+///
+/// https://developer.arm.com/documentation/ddi0602/2025-09/Base-Instructions/AUTDB--AUTDZB--Authenticate-data-address--using-key-B-
+///
+/// # Safety
+///
+/// Same as [`autda`].
+pub unsafe extern "C" fn autdb(p: u64, context: u64) -> u64 {
+    core::arch::naked_asm!("autdb x0, x1", "ret")
+}
+
+/// The number of virtual address bits of a user space pointer, the PAC
+/// lives above them
+pub const VA_BITS: u32 = 47;
+
+/// Returns true if `ptr` carries a PAC, that is has bits set above the
+/// virtual address.
+pub fn is_signed(ptr: u64) -> bool {
+    ptr >> VA_BITS != 0
+}
+
+/// Returns `ptr` without its PAC, whatever key and discriminator it was
+/// signed with.
+///
+/// Nothing is authenticated: this is for showing or looking up where a
+/// pointer goes, never for using it.
+pub fn strip(ptr: u64) -> u64 {
+    ptr & ((1 << VA_BITS) - 1)
+}
+
+/// The key a pointer is signed with
+///
+/// Code pointers (anything that is branched to) use an instruction
+/// key, pointers that are only loaded from or stored to use a data key.
+/// The variants are in the order "mach-o/fixup-chains.h" numbers them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacKey {
+    /// IA, the instruction key A
+    InstructionA,
+
+    /// IB, the instruction key B
+    InstructionB,
+
+    /// DA, the data key A
+    DataA,
+
+    /// DB, the data key B
+    DataB,
+}
+
+/// Returns the discriminator of a pointer stored at `address` and
+/// signed with the constant `diversity`, as
+/// `ptrauth_blend_discriminator` computes it.
+pub fn blend_discriminator(address: u64, diversity: u16) -> u64 {
+    (address & ((1 << 48) - 1)) | (diversity as u64) << 48
+}
+
+impl PacKey {
+    /// Signs `ptr` with this key and `discriminator`.
+    pub fn sign(self, ptr: u64, discriminator: u64) -> u64 {
+        // signing has no side effects, it only computes the PAC
+        unsafe {
+            match self {
+                PacKey::InstructionA => pacia(ptr, discriminator),
+                PacKey::InstructionB => pacib(ptr, discriminator),
+                PacKey::DataA => pacda(ptr, discriminator),
+                PacKey::DataB => pacdb(ptr, discriminator),
+            }
+        }
+    }
+
+    /// Authenticates `signed` with this key and `discriminator`,
+    /// returning the raw pointer.
+    ///
+    /// # Safety
+    ///
+    /// On CPUs with FEAT_FPAC a failed authentication traps instead
+    /// of returning a poisoned pointer, `signed` has to have been
+    /// signed with this key and `discriminator`.
+    pub unsafe fn authenticate(self, signed: u64, discriminator: u64) -> u64 {
+        unsafe {
+            match self {
+                PacKey::InstructionA => autia(signed, discriminator),
+                PacKey::InstructionB => autib(signed, discriminator),
+                PacKey::DataA => autda(signed, discriminator),
+                PacKey::DataB => autdb(signed, discriminator),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pac::{PacKey, blend_discriminator};
+
+    #[test]
+    pub fn test_pac() {
+        unsafe {
+            let ptr: u64 = 0x0000_1234_5678_9abc;
+            let ctx_ok: u64 = 666;
+            let ctx_bad: u64 = 777;
+
+            let signed = crate::pac::pacia(ptr, ctx_ok);
+            let auth_ok = crate::pac::autia(signed, ctx_ok);
+            let auth_bad = crate::pac::autia(signed, ctx_bad);
+
+            assert!(signed != ptr);
+            assert!(auth_ok == ptr);
+            assert!(auth_bad != ptr);
+        }
+    }
+
+    #[test]
+    pub fn test_pac_key() {
+        let ptr: u64 = 0x0000_1234_5678_9abc;
+
+        for key in [
+            PacKey::InstructionA,
+            PacKey::InstructionB,
+            PacKey::DataA,
+            PacKey::DataB,
+        ] {
+            let signed = key.sign(ptr, 666);
+            assert!(signed != ptr);
+            assert!(crate::pac::is_signed(signed));
+            assert!(!crate::pac::is_signed(ptr));
+            assert_eq!(crate::pac::strip(signed), ptr);
+            assert!(unsafe { key.authenticate(signed, 666) } == ptr);
+        }
+    }
+
+    #[test]
+    pub fn test_blend_discriminator() {
+        assert_eq!(
+            blend_discriminator(0xffff_0001_0000_4008, 0x6ae1),
+            0x6ae1_0001_0000_4008
+        );
+        assert_eq!(blend_discriminator(0x4008, 0), 0x4008);
+    }
+}
@@ -0,0 +1,209 @@
+use goblin::mach::{
+    MachO,
+    load_command::{CommandVariant, DyldInfoCommand},
+};
+
+use crate::{AddressSpace, Task, error::LoadError};
+
+// The rebase opcodes as defined in "mach-o/loader.h"
+//
+// A rebase stream is a sequence of bytes, each holding an opcode in the
+// high nibble and an immediate in the low nibble. Some opcodes are
+// followed by one or two ULEB128 operands.
+const REBASE_TYPE_POINTER: u8 = 1;
+
+const REBASE_OPCODE_MASK: u8 = 0xf0;
+const REBASE_IMMEDIATE_MASK: u8 = 0x0f;
+const REBASE_OPCODE_DONE: u8 = 0x00;
+const REBASE_OPCODE_SET_TYPE_IMM: u8 = 0x10;
+const REBASE_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB: u8 = 0x20;
+const REBASE_OPCODE_ADD_ADDR_ULEB: u8 = 0x30;
+const REBASE_OPCODE_ADD_ADDR_IMM_SCALED: u8 = 0x40;
+const REBASE_OPCODE_DO_REBASE_IMM_TIMES: u8 = 0x50;
+const REBASE_OPCODE_DO_REBASE_ULEB_TIMES: u8 = 0x60;
+const REBASE_OPCODE_DO_REBASE_ADD_ADDR_ULEB: u8 = 0x70;
+const REBASE_OPCODE_DO_REBASE_ULEB_TIMES_SKIPPING_ULEB: u8 = 0x80;
+
+const POINTER_SIZE: u64 = 8;
+
+/// Reads an unsigned LEB128 value from `data` at `*pos`, advancing `pos`.
+pub(crate) fn read_uleb128(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+
+    loop {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+
+        if shift >= 64 {
+            return None;
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+impl Task {
+    /// The difference between where the image was linked to and
+    /// where it actually lives.
+    ///
+    /// Segments are mapped at `memory + vmaddr`, so this is simply
+    /// the address of the tasks memory.
+    pub fn slide(&self) -> usize {
+        self.memory.as_ptr().addr()
+    }
+
+    /// Returns the link-time address of the runtime address `addr`, the
+    /// address `otool` or `objdump` print for it.
+    ///
+    /// That is `addr`, less where the image lives, plus where it was
+    /// linked to ([`Task::preferred_base`]): the slide taken off.
+    pub fn unslide(&self, addr: usize) -> usize {
+        addr.wrapping_sub(self.slide())
+    }
+
+    /// Returns the runtime address of the address `addr` of `space`.
+    pub fn runtime_address(&self, addr: usize, space: AddressSpace) -> usize {
+        match space {
+            AddressSpace::Runtime => addr,
+            AddressSpace::LinkTime => addr.wrapping_add(self.slide()),
+        }
+    }
+
+    /// Adds the slide to every absolute pointer listed in the
+    /// `LC_DYLD_INFO` rebase opcodes.
+    ///
+    /// Those are the internal pointers (vtables, function pointer
+    /// tables, ...) that are not covered by binding. Binaries using
+    /// chained fixups carry no rebase opcodes, their pointers are
+    /// rebased by [`Task::chained_fixups_init`].
+    ///
+    /// Pointers in read-only segments, like switch tables in
+    /// `__TEXT,__const`, are text relocations. They are rebased like the
+    /// others while the task is loading, when every segment is still
+    /// writable, but are an error once the task was sealed by
+    /// [`Task::segments_protect`].
+    pub fn rebase(&mut self, macho: &MachO, image: &[u8]) -> Result<(), LoadError> {
+        let Some(DyldInfoCommand {
+            rebase_off,
+            rebase_size,
+            ..
+        }) = macho.load_commands.iter().find_map(|lc| match &lc.command {
+            CommandVariant::DyldInfo(info) | CommandVariant::DyldInfoOnly(info) => Some(*info),
+            _ => None,
+        })
+        else {
+            return Ok(());
+        };
+
+        let start = rebase_off as usize;
+        let opcodes = start
+            .checked_add(rebase_size as usize)
+            .and_then(|end| image.get(start..end))
+            .ok_or(LoadError::MalformedRebase { offset: start })?;
+
+        let slide = self.slide() as u64;
+        let mut pos = 0;
+        let mut kind = REBASE_TYPE_POINTER;
+        let mut segment_index = 0;
+        let mut segment_offset = 0u64;
+
+        while pos < opcodes.len() {
+            let opcode_offset = start + pos;
+            let malformed = || LoadError::MalformedRebase {
+                offset: opcode_offset,
+            };
+
+            let byte = opcodes[pos];
+            pos += 1;
+            let immediate = byte & REBASE_IMMEDIATE_MASK;
+
+            let mut uleb = || read_uleb128(opcodes, &mut pos).ok_or_else(malformed);
+
+            // how many pointers to rebase, and how far to advance after each
+            let (count, skip) = match byte & REBASE_OPCODE_MASK {
+                REBASE_OPCODE_DONE => break,
+                REBASE_OPCODE_SET_TYPE_IMM => {
+                    kind = immediate;
+                    continue;
+                }
+                REBASE_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB => {
+                    segment_index = immediate as usize;
+                    segment_offset = uleb()?;
+                    continue;
+                }
+                REBASE_OPCODE_ADD_ADDR_ULEB => {
+                    segment_offset = segment_offset.wrapping_add(uleb()?);
+                    continue;
+                }
+                REBASE_OPCODE_ADD_ADDR_IMM_SCALED => {
+                    segment_offset = segment_offset.wrapping_add(immediate as u64 * POINTER_SIZE);
+                    continue;
+                }
+                REBASE_OPCODE_DO_REBASE_IMM_TIMES => (immediate as u64, 0),
+                REBASE_OPCODE_DO_REBASE_ULEB_TIMES => (uleb()?, 0),
+                REBASE_OPCODE_DO_REBASE_ADD_ADDR_ULEB => (1, uleb()?),
+                REBASE_OPCODE_DO_REBASE_ULEB_TIMES_SKIPPING_ULEB => {
+                    let count = uleb()?;
+                    (count, uleb()?)
+                }
+                _ => return Err(malformed()),
+            };
+
+            // only pointer sized rebases exist on 64 bit targets
+            if kind != REBASE_TYPE_POINTER {
+                return Err(malformed());
+            }
+
+            let segment = self.segments.get(segment_index).ok_or_else(malformed)?;
+
+            if self.sealed && segment.sealed_protection() & libc::VM_PROT_WRITE == 0 {
+                return Err(LoadError::TextRelocation {
+                    segment: segment.name.trim_end_matches('\0').to_string(),
+                    offset: opcode_offset,
+                });
+            }
+
+            for _ in 0..count {
+                if segment_offset
+                    .checked_add(POINTER_SIZE)
+                    .is_none_or(|end| end > segment.vm_size as u64)
+                {
+                    return Err(malformed());
+                }
+
+                unsafe {
+                    let slot = self
+                        .memory
+                        .add(segment.vm_addr + segment_offset as usize)
+                        .as_ptr() as *mut u64;
+                    slot.write_unaligned(slot.read_unaligned().wrapping_add(slide));
+                }
+
+                segment_offset = segment_offset.wrapping_add(skip + POINTER_SIZE);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rebase::read_uleb128;
+
+    #[test]
+    pub fn test_read_uleb128() {
+        let data = [0xe5, 0x8e, 0x26, 0x7f, 0x80];
+        let mut pos = 0;
+
+        assert_eq!(read_uleb128(&data, &mut pos), Some(624485));
+        assert_eq!(pos, 3);
+        assert_eq!(read_uleb128(&data, &mut pos), Some(0x7f));
+        assert_eq!(read_uleb128(&data, &mut pos), None);
+    }
+}
@@ -0,0 +1,263 @@
+//! Loading an image into another process.
+//!
+//! This is meant for debugging and injection tooling: the segments of the
+//! image are mapped into the address space of a target process through
+//! its task port, with the same layout a local [`Task`] would have.
+//!
+//! # Entitlements
+//!
+//! `task_for_pid` only hands out the task port of another process to
+//! root, or to a caller signed with `com.apple.security.cs.debugger`.
+//! Targets running under the hardened runtime (without the
+//! `com.apple.security.get-task-allow` entitlement) or protected by SIP
+//! refuse it either way.
+
+use goblin::mach::{Mach, MachO, cputype::CPU_TYPE_ARM64};
+
+use crate::{
+    TargetArch, Task, arch_not_found, entry_point_from_macho,
+    error::{LoadError, VmError},
+    image_check,
+    mach::{
+        task_for_pid, task_port_release, task_t, try_vm_alloc, try_vm_protect_task, try_vm_write,
+        vm_dealloc, vm_page_round,
+    },
+    segment_check_file_range, segment_check_file_size, segment_check_protection,
+    segments_check_overlap, segments_vm_size,
+};
+
+/// A segment mapped into another process
+#[derive(Debug, Clone)]
+pub struct RemoteSegment {
+    pub name: String,
+
+    /// address in the target process
+    pub address: u64,
+
+    /// memory size
+    pub size: u64,
+
+    /// the protection it was sealed with
+    pub initprot: libc::vm_prot_t,
+}
+
+/// An image mapped into another process, see [`Task::with_pointer_into`]
+///
+/// Dropping it unmaps the image from the target, [`RemoteTask::detach`]
+/// leaves it there.
+#[derive(Debug)]
+pub struct RemoteTask {
+    pid: libc::pid_t,
+    port: task_t,
+
+    /// start of the address space in the target
+    address: u64,
+    size: usize,
+
+    segments: Vec<RemoteSegment>,
+    entry_point: usize,
+}
+
+impl RemoteTask {
+    /// Returns the target process.
+    pub fn pid(&self) -> libc::pid_t {
+        self.pid
+    }
+
+    /// Returns the task port of the target process.
+    pub fn port(&self) -> task_t {
+        self.port
+    }
+
+    /// Returns where the address space of the image starts in the
+    /// target, its slide.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Returns the mapped segments.
+    pub fn segments(&self) -> &[RemoteSegment] {
+        &self.segments
+    }
+
+    /// Returns the entry point of the image, like [`Task::entry_point`].
+    pub fn entry_point(&self) -> usize {
+        self.entry_point
+    }
+
+    /// Writes `data` at `offset` from the start of the image in the target.
+    ///
+    /// The pages have to be writable, sealed segments are not.
+    pub fn write(&self, offset: u64, data: &[u8]) -> Result<(), VmError> {
+        try_vm_write(self.port, self.address + offset, data)
+    }
+
+    /// Changes the protection of `size` bytes at `offset` from the start
+    /// of the image in the target.
+    pub fn protect(
+        &self,
+        offset: u64,
+        size: usize,
+        protection: libc::vm_prot_t,
+    ) -> Result<(), VmError> {
+        try_vm_protect_task(self.port, self.address + offset, size, 0, protection)
+    }
+
+    /// Leaves the image mapped in the target and returns its address.
+    pub fn detach(self) -> u64 {
+        let address = self.address;
+        task_port_release(self.port);
+        std::mem::forget(self);
+        address
+    }
+}
+
+impl Drop for RemoteTask {
+    fn drop(&mut self) {
+        if self.size != 0 {
+            vm_dealloc(self.port, self.address, self.size);
+        }
+        task_port_release(self.port);
+    }
+}
+
+impl Task {
+    /// Maps the image at `ptr` into the process `pid`.
+    ///
+    /// The segments are copied into a fresh allocation of the target and
+    /// sealed to their `initprot`. Nothing runs in the target, and the
+    /// image is neither rebased nor bound: the loader would have to look
+    /// up the dylibs of the target, not its own.
+    ///
+    /// Fails with [`LoadError::TaskPort`] if the task port can not be
+    /// obtained, see the [entitlements](crate::remote#entitlements).
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Task::with_pointer`]. Writing into another process can
+    /// corrupt it.
+    pub unsafe fn with_pointer_into(
+        pid: libc::pid_t,
+        ptr: *const u8,
+        len: usize,
+    ) -> Result<RemoteTask, LoadError> {
+        image_check(ptr, len)?;
+
+        let image = unsafe { core::slice::from_raw_parts(ptr, len) };
+
+        match Mach::parse(image).map_err(LoadError::Parse)? {
+            Mach::Binary(macho) => remote_task_init(pid, &macho, image),
+            Mach::Fat(multi_arch) => {
+                let arches = multi_arch
+                    .iter_arches()
+                    .filter_map(|arch| arch.ok())
+                    .collect::<Vec<_>>();
+                let arch = arches
+                    .iter()
+                    .find(|arch| arch.cputype == CPU_TYPE_ARM64)
+                    .ok_or_else(|| {
+                        let found = arches.iter().map(|arch| arch.cputype).collect::<Vec<_>>();
+                        arch_not_found(TargetArch::Arm64, &found)
+                    })?;
+                let image = arch.slice(image);
+
+                unsafe { Self::with_pointer_into(pid, image.as_ptr(), image.len()) }
+            }
+        }
+    }
+}
+
+fn remote_task_init(
+    pid: libc::pid_t,
+    macho: &MachO<'_>,
+    image: &[u8],
+) -> Result<RemoteTask, LoadError> {
+    let vm_size = segments_vm_size(macho.segments.iter().map(|seg| {
+        (
+            seg.name().unwrap_or("<invalid utf8>").to_string(),
+            seg.vmaddr,
+            seg.vmsize,
+        )
+    }))?;
+
+    // the checks of a local load, a segment mapping more of the file
+    // than its vmsize would be written over the next one
+    segments_check_overlap(
+        macho
+            .segments
+            .iter()
+            .filter(|seg| !matches!(seg.name(), Ok("__PAGEZERO")))
+            .map(|seg| {
+                (
+                    seg.name().unwrap_or("<invalid utf8>").to_string(),
+                    seg.vmaddr,
+                    seg.vmaddr.saturating_add(seg.vmsize),
+                )
+            }),
+    )?;
+    for seg in macho.segments.iter() {
+        let segment = seg.name().unwrap_or("<invalid utf8>");
+        segment_check_protection(segment, seg.initprot as i32, seg.maxprot as i32)?;
+        segment_check_file_size(segment, seg.filesize, seg.vmsize)?;
+        segment_check_file_range(segment, seg.fileoff, seg.filesize, image.len())?;
+    }
+
+    let entry_point = entry_point_from_macho(macho)?;
+
+    let port = task_for_pid(pid).map_err(|err| LoadError::TaskPort { pid, err })?;
+
+    let size = vm_page_round(vm_size);
+    let address = match size {
+        0 => 0,
+        _ => try_vm_alloc(port, size).map_err(|err| {
+            task_port_release(port);
+            LoadError::Vm(err)
+        })?,
+    };
+
+    // owns the port and the allocation from here on
+    let mut task = RemoteTask {
+        pid,
+        port,
+        address,
+        size,
+        segments: Vec::new(),
+        entry_point,
+    };
+
+    for seg in macho.segments.iter().filter(|seg| seg.vmsize != 0) {
+        let data = (seg.fileoff as usize)
+            .checked_add(seg.filesize as usize)
+            .and_then(|end| image.get(seg.fileoff as usize..end))
+            .ok_or(LoadError::SegmentOutsideImage {
+                segment: seg.name().unwrap_or("<invalid utf8>").to_string(),
+                fileoff: seg.fileoff,
+                filesize: seg.filesize,
+            })?;
+
+        let segment = RemoteSegment {
+            name: seg.name().unwrap_or("<invalid utf8>").to_string(),
+            address: address + seg.vmaddr,
+            size: seg.vmsize,
+            initprot: seg.initprot as i32,
+        };
+
+        if !data.is_empty() {
+            try_vm_write(port, segment.address, data).map_err(LoadError::Vm)?;
+        }
+        for max in [false, true] {
+            try_vm_protect_task(
+                port,
+                segment.address,
+                vm_page_round(segment.size as usize),
+                max as i32,
+                segment.initprot,
+            )
+            .map_err(LoadError::Vm)?;
+        }
+
+        task.segments.push(segment);
+    }
+
+    Ok(task)
+}
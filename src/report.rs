@@ -0,0 +1,327 @@
+use std::fmt::Write;
+
+use goblin::mach::{
+    cputype::{CPU_TYPE_ARM, CPU_TYPE_ARM64, CPU_TYPE_ARM64_32, CPU_TYPE_I386, CPU_TYPE_X86_64},
+    load_command::cmd_to_str,
+};
+
+use crate::{
+    Task,
+    mach::{
+        MH_BUNDLE, MH_CORE, MH_DSYM, MH_DYLIB, MH_DYLIB_STUB, MH_DYLINKER, MH_EXECUTE, MH_FILESET,
+        MH_FLAG_NAMES, MH_FVMLIB, MH_KEXT_BUNDLE, MH_OBJECT, MH_PRELOAD,
+    },
+    rtld_flags_to_string, vm_prot_into_string,
+};
+
+/// Returns the name of a mach_header `cputype`.
+pub fn cputype_into_str(cputype: u32) -> &'static str {
+    match cputype {
+        CPU_TYPE_X86_64 => "x86_64",
+        CPU_TYPE_I386 => "i386",
+        CPU_TYPE_ARM64 => "arm64",
+        CPU_TYPE_ARM64_32 => "arm64_32",
+        CPU_TYPE_ARM => "arm",
+        _ => "unknown",
+    }
+}
+
+/// Returns the name of a mach_header `filetype`.
+pub fn filetype_into_str(filetype: u32) -> &'static str {
+    match filetype {
+        MH_OBJECT => "MH_OBJECT",
+        MH_EXECUTE => "MH_EXECUTE",
+        MH_FVMLIB => "MH_FVMLIB",
+        MH_CORE => "MH_CORE",
+        MH_PRELOAD => "MH_PRELOAD",
+        MH_DYLIB => "MH_DYLIB",
+        MH_DYLINKER => "MH_DYLINKER",
+        MH_BUNDLE => "MH_BUNDLE",
+        MH_DYLIB_STUB => "MH_DYLIB_STUB",
+        MH_DSYM => "MH_DSYM",
+        MH_KEXT_BUNDLE => "MH_KEXT_BUNDLE",
+        MH_FILESET => "MH_FILESET",
+        _ => "unknown",
+    }
+}
+
+/// Returns the name of a load command `cmd`.
+pub fn load_command_into_str(cmd: u32) -> &'static str {
+    cmd_to_str(cmd)
+}
+
+/// A section of [`SegmentSummary`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SectionSummary {
+    pub name: String,
+    pub vm_addr: u64,
+    pub vm_size: u64,
+    pub flags: u32,
+}
+
+/// A segment of [`LoadCommandSummary`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SegmentSummary {
+    pub name: String,
+    pub vm_addr: u64,
+    pub vm_size: u64,
+    pub file_offset: u64,
+    pub file_size: u64,
+
+    /// as `(rwx)`, see [`vm_prot_into_string`]
+    pub initprot: String,
+    pub maxprot: String,
+    pub sections: Vec<SectionSummary>,
+}
+
+/// A dylib of [`LoadCommandSummary`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DylibSummary {
+    pub name: String,
+
+    /// the `dlopen` flags, see [`rtld_flags_to_string`]
+    pub flags: String,
+}
+
+/// The loader's view of an image, see [`Task::load_commands_summary`]
+///
+/// This is what [`Task::report`] prints, as plain data. With the
+/// `serde` feature it is `Serialize`, see [`Task::load_commands_json`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LoadCommandSummary {
+    pub cputype: String,
+    pub filetype: String,
+    pub flags: String,
+    pub preferred_base: u64,
+
+    /// the entry point as a virtual address, 0 if none
+    pub entry_point: u64,
+
+    /// the `LC_UUID`, as `8-4-4-4-12` upper case hex
+    pub uuid: Option<String>,
+
+    /// the `LC_SOURCE_VERSION`, as `A.B.C.D.E`
+    pub source_version: Option<String>,
+
+    /// the `LC_ID_DYLIB` install name, and current and compatibility
+    /// versions as `X.Y.Z`
+    pub dylib_id: Option<(String, String, String)>,
+
+    /// every load command, in order
+    pub load_commands: Vec<String>,
+    pub segments: Vec<SegmentSummary>,
+    pub dylibs: Vec<DylibSummary>,
+}
+
+/// Returns an `LC_UUID` as `8-4-4-4-12` upper case hex, as printed by
+/// `dwarfdump --uuid`.
+pub fn uuid_into_string(uuid: &[u8; 16]) -> String {
+    let hex = uuid
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Returns the mach_header `flags` as a `|` separated list of names.
+pub fn mh_flags_into_string(flags: u32) -> String {
+    let mut names = MH_FLAG_NAMES
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| name.to_string())
+        .collect::<Vec<_>>();
+
+    let unknown = MH_FLAG_NAMES
+        .iter()
+        .fold(flags, |flags, (flag, _)| flags & !flag);
+    if unknown != 0 {
+        names.push(format!("{unknown:#x}"));
+    }
+
+    names.join("|")
+}
+
+/// Returns an `LC_SOURCE_VERSION` as `A.B.C.D.E`.
+pub fn source_version_into_string(version: u64) -> String {
+    format!(
+        "{}.{}.{}.{}.{}",
+        version >> 40,
+        (version >> 30) & 0x3ff,
+        (version >> 20) & 0x3ff,
+        (version >> 10) & 0x3ff,
+        version & 0x3ff
+    )
+}
+
+/// Returns a dylib version as `X.Y.Z`.
+pub fn dylib_version_into_string(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        version >> 16,
+        (version >> 8) & 0xff,
+        version & 0xff
+    )
+}
+
+impl Task {
+    /// Returns a human readable description of the loaded image.
+    ///
+    /// Everything the loader based its decisions on is in there,
+    /// which is the first thing to check when a load goes wrong.
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let header = &self.header;
+
+        let _ = writeln!(out, "header:");
+        let _ = writeln!(
+            out,
+            "  cputype     {} ({:#x})",
+            cputype_into_str(header.cputype),
+            header.cputype
+        );
+        let _ = writeln!(out, "  cpusubtype  {:#x}", header.cpusubtype);
+        let _ = writeln!(
+            out,
+            "  filetype    {} ({:#x})",
+            filetype_into_str(header.filetype),
+            header.filetype
+        );
+        let _ = writeln!(
+            out,
+            "  flags       {} ({:#x})",
+            mh_flags_into_string(header.flags),
+            header.flags
+        );
+        let _ = writeln!(out, "  base        {:#x}", self.preferred_base());
+        let _ = writeln!(out, "  entry       {:#x}", self.entry_point);
+
+        if let Some(version) = self.source_version {
+            let _ = writeln!(out, "  source      {}", source_version_into_string(version));
+        }
+
+        if let Some((name, current, compatibility)) = &self.dylib_id {
+            let _ = writeln!(
+                out,
+                "  id          {name} (current {}, compatibility {})",
+                dylib_version_into_string(*current),
+                dylib_version_into_string(*compatibility)
+            );
+        }
+
+        let unhandled = self.unhandled_load_commands();
+        if !unhandled.is_empty() {
+            let names = unhandled
+                .iter()
+                .map(|cmd| format!("{} ({cmd:#x})", load_command_into_str(*cmd)))
+                .collect::<Vec<_>>();
+            let _ = writeln!(out, "  unhandled   {}", names.join(", "));
+        }
+
+        let _ = writeln!(out, "segments:");
+        for segment in &self.segments {
+            let _ = writeln!(
+                out,
+                "  {:<16} {:#014x}-{:#014x} {}/{}",
+                segment.name.trim_end_matches('\0'),
+                segment.vm_addr,
+                segment.vm_addr + segment.vm_size,
+                vm_prot_into_string(segment.initprot),
+                vm_prot_into_string(segment.maxprot),
+            );
+        }
+
+        let _ = writeln!(out, "dylibs:");
+        for ((name, handle), flags) in self.dylibs.iter().zip(&self.dylib_flags) {
+            let _ = writeln!(
+                out,
+                "  {name} @ {handle:#x} ({})",
+                rtld_flags_to_string(*flags)
+            );
+        }
+
+        out
+    }
+
+    /// Returns what [`Task::report`] prints, as plain data.
+    pub fn load_commands_summary(&self) -> LoadCommandSummary {
+        let header = &self.header;
+
+        LoadCommandSummary {
+            cputype: cputype_into_str(header.cputype).to_string(),
+            filetype: filetype_into_str(header.filetype).to_string(),
+            flags: mh_flags_into_string(header.flags),
+            preferred_base: self.preferred_base(),
+            entry_point: self.entry_point as u64,
+            uuid: self.uuid.as_ref().map(uuid_into_string),
+            source_version: self.source_version.map(source_version_into_string),
+            dylib_id: self
+                .dylib_id
+                .as_ref()
+                .map(|(name, current, compatibility)| {
+                    (
+                        name.clone(),
+                        dylib_version_into_string(*current),
+                        dylib_version_into_string(*compatibility),
+                    )
+                }),
+            load_commands: self
+                .load_commands
+                .iter()
+                .map(|cmd| load_command_into_str(*cmd).to_string())
+                .collect(),
+            segments: self
+                .segments
+                .iter()
+                .map(|segment| SegmentSummary {
+                    name: segment.name.trim_end_matches('\0').to_string(),
+                    vm_addr: segment.vm_addr as u64,
+                    vm_size: segment.vm_size as u64,
+                    file_offset: segment.offset as u64,
+                    file_size: segment.size as u64,
+                    initprot: vm_prot_into_string(segment.initprot),
+                    maxprot: vm_prot_into_string(segment.maxprot),
+                    sections: segment
+                        .sections
+                        .iter()
+                        .map(|section| SectionSummary {
+                            name: section.name().to_string(),
+                            vm_addr: section.vm_addr as u64,
+                            vm_size: section.vm_size as u64,
+                            flags: section.flags,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            dylibs: self
+                .dylibs
+                .iter()
+                .zip(&self.dylib_flags)
+                .map(|((name, _), flags)| DylibSummary {
+                    name: name.clone(),
+                    flags: rtld_flags_to_string(*flags),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns [`Task::load_commands_summary`] as JSON.
+    ///
+    /// Dylib handles are left out, they only mean something in this
+    /// process.
+    #[cfg(feature = "serde")]
+    pub fn load_commands_json(&self) -> String {
+        serde_json::to_string_pretty(&self.load_commands_summary())
+            .expect("the summary only holds strings and integers")
+    }
+}
@@ -0,0 +1,205 @@
+//! The dyld search order for dylib install names.
+//!
+//! `dlopen` is handed a path the host can open: an install name starting
+//! with `@rpath/` is tried under every `LC_RPATH` of the image, in order,
+//! then under the fallback directories dyld uses when
+//! `DYLD_FALLBACK_FRAMEWORK_PATH` and `DYLD_FALLBACK_LIBRARY_PATH` are
+//! not set. `@loader_path` and `@executable_path` stand for the directory
+//! of the loaded image, both in install names and in rpaths, since the
+//! image is the main executable of the task.
+
+use std::path::Path;
+
+use goblin::mach::{
+    MachO,
+    load_command::{CommandVariant, LoadCommand, RpathCommand},
+};
+
+use crate::{dylib_name_from_command, error::LoadError};
+
+/// Where dyld looks for a framework after the rpaths
+pub const FALLBACK_FRAMEWORK_PATHS: &[&str] =
+    &["/Library/Frameworks", "/System/Library/Frameworks"];
+
+/// Where dyld looks for a dylib after the rpaths
+pub const FALLBACK_LIBRARY_PATHS: &[&str] = &["/usr/local/lib", "/usr/lib"];
+
+/// Replaces a leading `@loader_path` or `@executable_path` of `path`
+/// with `loader_dir`, `None` if `path` needs it and it is not known.
+///
+/// An empty `loader_dir`, the parent of a bare file name, is the
+/// current directory.
+fn expand_loader_path(path: &str, loader_dir: Option<&Path>) -> Option<String> {
+    for prefix in ["@loader_path", "@executable_path"] {
+        if let Some(rest) = path.strip_prefix(prefix) {
+            let loader_dir = match loader_dir? {
+                dir if dir.as_os_str().is_empty() => Path::new("."),
+                dir => dir,
+            };
+            return Some(format!("{}{rest}", loader_dir.display()));
+        }
+    }
+    Some(path.to_string())
+}
+
+/// Returns the `Foo.framework/...` part of a path inside a framework
+/// bundle, `None` if it is not in one.
+fn framework_partial_path(path: &str) -> Option<&str> {
+    let end = path.find(".framework/")?;
+    let start = path[..end].rfind('/').map_or(0, |slash| slash + 1);
+    Some(&path[start..])
+}
+
+/// Returns the paths to `dlopen`, in order, for the install name
+/// `install_name` of an image with the rpaths `rpaths`.
+///
+/// `loader_dir` is the directory of the loaded image, rpaths relative to
+/// it are skipped when it is not known. Install names without `@rpath/`
+/// are returned as they are, once expanded.
+pub fn dylib_search_paths(
+    install_name: &str,
+    rpaths: &[String],
+    loader_dir: Option<&Path>,
+) -> Vec<String> {
+    let Some(tail) = install_name.strip_prefix("@rpath/") else {
+        return expand_loader_path(install_name, loader_dir)
+            .into_iter()
+            .collect();
+    };
+
+    let mut paths = rpaths
+        .iter()
+        .filter_map(|rpath| expand_loader_path(rpath, loader_dir))
+        .map(|rpath| format!("{}/{tail}", rpath.trim_end_matches('/')))
+        .collect::<Vec<_>>();
+
+    if let Some(framework) = framework_partial_path(tail) {
+        paths.extend(
+            FALLBACK_FRAMEWORK_PATHS
+                .iter()
+                .map(|dir| format!("{dir}/{framework}")),
+        );
+    }
+
+    let leaf = tail.rsplit('/').next().unwrap_or(tail);
+    paths.extend(
+        FALLBACK_LIBRARY_PATHS
+            .iter()
+            .map(|dir| format!("{dir}/{leaf}")),
+    );
+
+    paths
+}
+
+/// Returns the path of every `LC_RPATH` of the image, in order.
+pub(crate) fn rpaths_from_macho(macho: &MachO, image: &[u8]) -> Result<Vec<String>, LoadError> {
+    let mut rpaths = Vec::new();
+
+    for LoadCommand {
+        offset, command, ..
+    } in &macho.load_commands
+    {
+        if let CommandVariant::Rpath(RpathCommand { cmdsize, path, .. }) = command {
+            let path = dylib_name_from_command(image, *offset, *cmdsize as usize, *path as usize)?;
+            rpaths.push(path.to_str().unwrap_or("<invalid utf8>").to_string());
+        }
+    }
+
+    Ok(rpaths)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::rpath::dylib_search_paths;
+
+    #[test]
+    pub fn test_dylib_search_paths() {
+        let rpaths = [
+            "/opt/a".to_string(),
+            "@loader_path/../Frameworks/".to_string(),
+        ];
+
+        assert_eq!(
+            dylib_search_paths(
+                "@rpath/Foo.framework/Versions/A/Foo",
+                &rpaths,
+                Some(Path::new("/app/bin"))
+            ),
+            [
+                "/opt/a/Foo.framework/Versions/A/Foo",
+                "/app/bin/../Frameworks/Foo.framework/Versions/A/Foo",
+                "/Library/Frameworks/Foo.framework/Versions/A/Foo",
+                "/System/Library/Frameworks/Foo.framework/Versions/A/Foo",
+                "/usr/local/lib/Foo",
+                "/usr/lib/Foo",
+            ]
+        );
+
+        // the loader relative rpath is skipped without a loader path
+        assert_eq!(
+            dylib_search_paths("@rpath/libfoo.dylib", &rpaths, None),
+            [
+                "/opt/a/libfoo.dylib",
+                "/usr/local/lib/libfoo.dylib",
+                "/usr/lib/libfoo.dylib",
+            ]
+        );
+
+        assert_eq!(
+            dylib_search_paths("/usr/lib/libSystem.B.dylib", &rpaths, None),
+            ["/usr/lib/libSystem.B.dylib"]
+        );
+        assert_eq!(
+            dylib_search_paths("@executable_path/libfoo.dylib", &rpaths, None),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    pub fn test_loader_path_of_bare_file_name() {
+        // the image was loaded as `hello_world`, from the current directory
+        let loader_dir = Path::new("hello_world").parent();
+
+        assert_eq!(
+            dylib_search_paths("@loader_path/libfoo.dylib", &[], loader_dir),
+            ["./libfoo.dylib"]
+        );
+        assert_eq!(
+            dylib_search_paths(
+                "@rpath/libfoo.dylib",
+                &["@executable_path/lib".to_string()],
+                loader_dir
+            ),
+            [
+                "./lib/libfoo.dylib",
+                "/usr/local/lib/libfoo.dylib",
+                "/usr/lib/libfoo.dylib",
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_rpath_framework_second_entry() {
+        let rpaths = ["/nonexistent".to_string(), "/System/Library".to_string()];
+        let paths = dylib_search_paths(
+            "@rpath/Frameworks/CoreFoundation.framework/CoreFoundation",
+            &rpaths,
+            None,
+        );
+
+        let (index, handle) = paths
+            .iter()
+            .enumerate()
+            .find_map(|(index, path)| {
+                let path = std::ffi::CString::new(path.as_str()).unwrap();
+                let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_LAZY) };
+                (!handle.is_null()).then_some((index, handle))
+            })
+            .expect("CoreFoundation was not found");
+
+        assert_eq!(index, 1);
+        unsafe { libc::dlclose(handle) };
+    }
+}
@@ -0,0 +1,711 @@
+use std::collections::{HashMap, HashSet};
+
+use goblin::mach::{MachO, load_command::CommandVariant};
+
+use crate::{
+    AddressSpace, Task,
+    error::LoadError,
+    mach::{
+        EXPORT_SYMBOL_FLAGS_KIND_ABSOLUTE, EXPORT_SYMBOL_FLAGS_KIND_MASK,
+        EXPORT_SYMBOL_FLAGS_REEXPORT, EXPORT_SYMBOL_FLAGS_STUB_AND_RESOLVER, N_ABS, N_EXT, N_SECT,
+        N_STAB, N_TYPE, N_WEAK_DEF,
+    },
+    rebase::read_uleb128,
+};
+
+/// A symbol defined by the loaded image
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    /// name, without the leading '_'
+    pub name: String,
+
+    /// address in memory
+    pub address: usize,
+
+    /// the raw n_type of the nlist
+    pub n_type: u8,
+
+    /// whether this is a weak definition (`N_WEAK_DEF`)
+    pub weak: bool,
+}
+
+/// Keeps a single definition of each external symbol defined more
+/// than once.
+///
+/// A strong definition wins over weak ones, between definitions of the
+/// same kind the first one wins. Local symbols are kept as they are,
+/// two files may each have their own `static` of the same name.
+pub fn definitions_coalesce(definitions: Vec<Symbol>) -> Vec<Symbol> {
+    let mut coalesced = Vec::<Symbol>::with_capacity(definitions.len());
+    let mut externals = HashMap::<String, usize>::new();
+
+    for symbol in definitions {
+        if symbol.n_type & N_EXT == 0 {
+            coalesced.push(symbol);
+            continue;
+        }
+
+        match externals.get(&symbol.name) {
+            None => {
+                externals.insert(symbol.name.clone(), coalesced.len());
+                coalesced.push(symbol);
+            }
+            Some(&index) => {
+                if coalesced[index].weak && !symbol.weak {
+                    coalesced[index] = symbol;
+                }
+            }
+        }
+    }
+    coalesced
+}
+
+/// An entry of the symbol table, as needed to bind imports
+#[derive(Debug, Clone)]
+pub(crate) struct Nlist {
+    /// name, with the leading '_'
+    pub name: String,
+
+    /// the raw n_type
+    pub n_type: u8,
+
+    /// the raw n_desc, holding the library ordinal of imports
+    pub n_desc: u16,
+
+    /// the raw n_value
+    pub n_value: u64,
+
+    /// the raw n_strx, when it lies outside of the string table
+    /// (`name` is empty then)
+    pub bad_strx: Option<u32>,
+}
+
+/// The size of an `nlist_64`
+const NLIST_SIZE: usize = 16;
+
+/// Returns the name at `n_strx` in the string table `strtab`, `None` if
+/// it does not start and end within it.
+fn nlist_name(strtab: &[u8], n_strx: u32) -> Option<String> {
+    let name = strtab.get(n_strx as usize..)?;
+    let end = name.iter().position(|byte| *byte == 0)?;
+    Some(String::from_utf8_lossy(&name[..end]).into_owned())
+}
+
+/// Reads the `nsyms` entries of the symbol table at `symoff`, their names
+/// from the string table at `stroff`.
+///
+/// Entries whose `n_strx` lies outside of the string table are kept, so
+/// that the indirect symbol table still indexes the right entries, and
+/// marked with [`Nlist::bad_strx`].
+pub(crate) fn nlists_read(
+    image: &[u8],
+    symoff: u32,
+    nsyms: u32,
+    stroff: u32,
+    strsize: u32,
+) -> Result<Vec<Nlist>, LoadError> {
+    let table = (nsyms as usize)
+        .checked_mul(NLIST_SIZE)
+        .and_then(|size| (symoff as usize).checked_add(size))
+        .and_then(|end| image.get(symoff as usize..end))
+        .ok_or(LoadError::MalformedSymbolTable {
+            offset: symoff as usize,
+        })?;
+    let strtab = (stroff as usize)
+        .checked_add(strsize as usize)
+        .and_then(|end| image.get(stroff as usize..end))
+        .ok_or(LoadError::MalformedSymbolTable {
+            offset: stroff as usize,
+        })?;
+
+    Ok(table
+        .chunks_exact(NLIST_SIZE)
+        .map(|entry| {
+            let n_strx = u32::from_le_bytes(entry[0..4].try_into().unwrap());
+            let name = nlist_name(strtab, n_strx);
+            Nlist {
+                bad_strx: name.is_none().then_some(n_strx),
+                name: name.unwrap_or_default(),
+                n_type: entry[4],
+                n_desc: u16::from_le_bytes(entry[6..8].try_into().unwrap()),
+                n_value: u64::from_le_bytes(entry[8..16].try_into().unwrap()),
+            }
+        })
+        .collect())
+}
+
+/// The symbol table of an image, kept across loads of that image.
+///
+/// Walking the symbol table of a large binary is most of the time it
+/// takes to load it. The cache holds everything the walk produces and
+/// is tied to the `LC_UUID` of the image it was built from, a cache
+/// given to a load of any other image is ignored.
+#[derive(Debug, Clone)]
+pub struct SymbolCache {
+    pub(crate) uuid: [u8; 16],
+    symtab: Vec<Nlist>,
+    indirect_symbols: Vec<u32>,
+}
+
+impl SymbolCache {
+    /// Returns the `LC_UUID` of the image the cache was built from.
+    pub fn uuid(&self) -> [u8; 16] {
+        self.uuid
+    }
+
+    /// Serializes the cache, to be stored between runs.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.uuid);
+
+        out.extend_from_slice(&(self.symtab.len() as u32).to_le_bytes());
+        for nlist in &self.symtab {
+            out.push(nlist.n_type);
+            out.extend_from_slice(&nlist.n_desc.to_le_bytes());
+            out.extend_from_slice(&nlist.n_value.to_le_bytes());
+            out.extend_from_slice(&(nlist.name.len() as u32).to_le_bytes());
+            out.extend_from_slice(nlist.name.as_bytes());
+        }
+
+        out.extend_from_slice(&(self.indirect_symbols.len() as u32).to_le_bytes());
+        for entry in &self.indirect_symbols {
+            out.extend_from_slice(&entry.to_le_bytes());
+        }
+        out
+    }
+
+    /// Reads back a cache serialized by [`SymbolCache::to_bytes`].
+    ///
+    /// Returns `None` if `bytes` is truncated or malformed.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut pos: usize = 0;
+        let mut take = |len: usize| {
+            let chunk = bytes.get(pos..pos.checked_add(len)?)?;
+            pos += len;
+            Some(chunk)
+        };
+
+        let uuid = take(16)?.try_into().ok()?;
+
+        let count = u32::from_le_bytes(take(4)?.try_into().ok()?);
+        let mut symtab = Vec::new();
+        for _ in 0..count {
+            let n_type = take(1)?[0];
+            let n_desc = u16::from_le_bytes(take(2)?.try_into().ok()?);
+            let n_value = u64::from_le_bytes(take(8)?.try_into().ok()?);
+            let name_len = u32::from_le_bytes(take(4)?.try_into().ok()?);
+            let name = String::from_utf8(take(name_len as usize)?.to_vec()).ok()?;
+            symtab.push(Nlist {
+                name,
+                n_type,
+                n_desc,
+                n_value,
+                bad_strx: None,
+            });
+        }
+
+        let count = u32::from_le_bytes(take(4)?.try_into().ok()?);
+        let indirect_symbols = (0..count)
+            .map(|_| Some(u32::from_le_bytes(take(4)?.try_into().ok()?)))
+            .collect::<Option<Vec<_>>>()?;
+
+        if pos != bytes.len() {
+            return None;
+        }
+
+        Some(SymbolCache {
+            uuid,
+            symtab,
+            indirect_symbols,
+        })
+    }
+}
+
+/// Returns the in-memory address of a defined symbol.
+///
+/// `N_SECT` symbols are relative to the image and get the slide added,
+/// `N_ABS` symbols are absolute and are returned as is. Undefined,
+/// indirect and debugging (`N_STAB`) symbols do not define anything.
+pub fn symbol_address_from_nlist(n_type: u8, n_value: u64, slide: usize) -> Option<usize> {
+    if n_type & N_STAB != 0 {
+        return None;
+    }
+
+    match n_type & N_TYPE {
+        N_SECT => Some(slide + n_value as usize),
+        N_ABS => Some(n_value as usize),
+        _ => None,
+    }
+}
+
+/// A symbol of the export trie with an address in the image
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TrieExport {
+    /// name, with the leading '_'
+    pub name: String,
+
+    /// offset from the mach header, or the address itself when absolute
+    pub value: u64,
+
+    /// whether `value` is an absolute address
+    pub absolute: bool,
+}
+
+/// Walks the export trie `trie`, as found in `LC_DYLD_EXPORTS_TRIE` or
+/// the export area of `LC_DYLD_INFO`.
+///
+/// Re-exports have no address in the image and are skipped, as are the
+/// resolvers of stub-and-resolver symbols, whose stub is what callers
+/// are given. On error, returns the offset of the faulty node: nodes
+/// have to lie within the trie and may not be reached twice.
+pub(crate) fn export_trie_read(trie: &[u8]) -> Result<Vec<TrieExport>, usize> {
+    let mut exports = Vec::new();
+    let mut visited = HashSet::new();
+    let mut nodes = vec![(0usize, Vec::<u8>::new())];
+
+    while let Some((node, prefix)) = nodes.pop() {
+        if !visited.insert(node) {
+            return Err(node);
+        }
+
+        let mut pos = node;
+        let terminal_size = read_uleb128(trie, &mut pos).ok_or(node)? as usize;
+        let children = pos.checked_add(terminal_size).ok_or(node)?;
+
+        if terminal_size != 0 {
+            let flags = read_uleb128(trie, &mut pos).ok_or(node)?;
+            if flags & EXPORT_SYMBOL_FLAGS_REEXPORT == 0 {
+                let value = read_uleb128(trie, &mut pos).ok_or(node)?;
+                if flags & EXPORT_SYMBOL_FLAGS_STUB_AND_RESOLVER != 0 {
+                    read_uleb128(trie, &mut pos).ok_or(node)?;
+                }
+                exports.push(TrieExport {
+                    name: String::from_utf8_lossy(&prefix).into_owned(),
+                    value,
+                    absolute: flags & EXPORT_SYMBOL_FLAGS_KIND_MASK
+                        == EXPORT_SYMBOL_FLAGS_KIND_ABSOLUTE,
+                });
+            }
+        }
+
+        pos = children;
+        let count = *trie.get(pos).ok_or(node)?;
+        pos += 1;
+
+        let mut edges = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let len = trie
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&byte| byte == 0))
+                .ok_or(node)?;
+            let edge = &trie[pos..pos + len];
+            pos += len + 1;
+
+            let child = read_uleb128(trie, &mut pos).ok_or(node)? as usize;
+            if child >= trie.len() {
+                return Err(node);
+            }
+            edges.push((child, [prefix.as_slice(), edge].concat()));
+        }
+
+        // the stack pops the last one first, keep the order of the trie
+        nodes.extend(edges.into_iter().rev());
+    }
+
+    Ok(exports)
+}
+
+impl Task {
+    /// Collects every symbol the image defines.
+    pub(crate) fn definitions_init(&mut self) {
+        let slide = self.slide();
+
+        let definitions = self
+            .symtab
+            .iter()
+            .filter(|nlist| nlist.bad_strx.is_none())
+            .filter_map(|nlist| {
+                let address = symbol_address_from_nlist(nlist.n_type, nlist.n_value, slide)?;
+                let name = &nlist.name;
+                Some(Symbol {
+                    name: name.strip_prefix('_').unwrap_or(name).to_string(),
+                    address,
+                    n_type: nlist.n_type,
+                    weak: nlist.n_desc & N_WEAK_DEF != 0,
+                })
+            })
+            .collect();
+
+        self.definitions = definitions_coalesce(definitions);
+    }
+
+    /// Collects the symbols of the export trie of the image, if it has
+    /// one.
+    pub(crate) fn exports_init(&mut self, macho: &MachO, image: &[u8]) -> Result<(), LoadError> {
+        let Some((start, size)) = macho.load_commands.iter().find_map(|lc| match &lc.command {
+            CommandVariant::DyldExportsTrie(trie) => {
+                Some((trie.dataoff as usize, trie.datasize as usize))
+            }
+            CommandVariant::DyldInfo(info) | CommandVariant::DyldInfoOnly(info) => {
+                Some((info.export_off as usize, info.export_size as usize))
+            }
+            _ => None,
+        }) else {
+            return Ok(());
+        };
+        if size == 0 {
+            return Ok(());
+        }
+
+        let trie = start
+            .checked_add(size)
+            .and_then(|end| image.get(start..end))
+            .ok_or(LoadError::MalformedExportTrie { offset: start })?;
+        let exports = export_trie_read(trie).map_err(|node| LoadError::MalformedExportTrie {
+            offset: start + node,
+        })?;
+
+        let header = self.slide() + self.preferred_base as usize;
+        self.trie_exports = exports
+            .into_iter()
+            .map(|export| {
+                let name = export.name.strip_prefix('_').unwrap_or(&export.name);
+                let address = match export.absolute {
+                    true => export.value as usize,
+                    false => header + export.value as usize,
+                };
+                (name.to_string(), address)
+            })
+            .collect();
+
+        Ok(())
+    }
+
+    /// Keeps the symbol table and the indirect symbol table of the image,
+    /// binding needs both after the image is gone.
+    ///
+    /// Names are checked against the string table, see [`nlists_read`].
+    pub(crate) fn symtab_init(&mut self, macho: &MachO, image: &[u8]) -> Result<(), LoadError> {
+        if let Some(symtab) = macho.load_commands.iter().find_map(|lc| match &lc.command {
+            CommandVariant::Symtab(symtab) => Some(symtab),
+            _ => None,
+        }) {
+            self.symtab = nlists_read(
+                image,
+                symtab.symoff,
+                symtab.nsyms,
+                symtab.stroff,
+                symtab.strsize,
+            )?;
+        }
+
+        let Some((indirectsymoff, nindirectsyms)) =
+            macho.load_commands.iter().find_map(|lc| match &lc.command {
+                CommandVariant::Dysymtab(dysymtab) => {
+                    Some((dysymtab.indirectsymoff, dysymtab.nindirectsyms))
+                }
+                _ => None,
+            })
+        else {
+            return Ok(());
+        };
+
+        let start = indirectsymoff as usize;
+        let table = (nindirectsyms as usize)
+            .checked_mul(size_of::<u32>())
+            .and_then(|size| start.checked_add(size))
+            .and_then(|end| image.get(start..end))
+            .ok_or(LoadError::MalformedIndirectSymbols { offset: start })?;
+
+        self.indirect_symbols = table
+            .chunks_exact(size_of::<u32>())
+            .map(|entry| u32::from_le_bytes(entry.try_into().unwrap()))
+            .collect();
+
+        Ok(())
+    }
+
+    /// Takes the symbol table and the indirect symbol table from `cache`
+    /// instead of reading them from the image.
+    pub(crate) fn symtab_from_cache(&mut self, cache: &SymbolCache) {
+        self.symtab = cache.symtab.clone();
+        self.indirect_symbols = cache.indirect_symbols.clone();
+    }
+
+    /// Returns a cache of the symbol table, to be given to later loads
+    /// of the same image through [`crate::TaskOptions::symbol_cache`].
+    ///
+    /// Images without an `LC_UUID` can not be told apart, and are not
+    /// cached, nor are those with names outside of their string table.
+    pub fn symbol_cache(&self) -> Option<SymbolCache> {
+        if self.symtab.iter().any(|nlist| nlist.bad_strx.is_some()) {
+            return None;
+        }
+
+        Some(SymbolCache {
+            uuid: self.uuid?,
+            symtab: self.symtab.clone(),
+            indirect_symbols: self.indirect_symbols.clone(),
+        })
+    }
+
+    /// Returns the in-memory address of the symbol `name`, defined
+    /// by the loaded image.
+    ///
+    /// `name` is the C name, without the leading '_'.
+    pub fn symbol_address(&self, name: &str) -> Option<usize> {
+        self.definitions
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .map(|symbol| symbol.address)
+    }
+
+    /// Returns every symbol the image exports, with its in-memory
+    /// address.
+    ///
+    /// These are the external symbols defined in a section of the symbol
+    /// table, followed by the symbols of the export trie it does not
+    /// list, a stripped image may only have the latter. Names are the C
+    /// names, without the leading '_'.
+    pub fn exports(&self) -> Vec<(String, usize)> {
+        let mut exports = self
+            .definitions
+            .iter()
+            .filter(|symbol| symbol.n_type & N_EXT != 0 && symbol.n_type & N_TYPE == N_SECT)
+            .map(|symbol| (symbol.name.clone(), symbol.address))
+            .collect::<Vec<_>>();
+
+        let listed = exports
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<HashSet<_>>();
+        exports.extend(
+            self.trie_exports
+                .iter()
+                .filter(|(name, _)| !listed.contains(name))
+                .cloned(),
+        );
+        exports
+    }
+
+    /// Returns the definition of `name`, if the image exports it.
+    pub(crate) fn exported_definition(&self, name: &str) -> Option<&Symbol> {
+        self.definitions
+            .iter()
+            .find(|symbol| symbol.name == name && symbol.n_type & N_EXT != 0)
+    }
+
+    /// Returns the definition of `name`, if the image defines it as an
+    /// external weak symbol.
+    pub(crate) fn weak_definition(&self, name: &str) -> Option<&Symbol> {
+        self.exported_definition(name).filter(|symbol| symbol.weak)
+    }
+
+    /// Returns the closest symbol at or before `addr`, and the offset
+    /// of `addr` from it.
+    ///
+    /// Only addresses in a section of the image symbolize, against the
+    /// symbols of that section: a pc in a dylib or past the image is
+    /// `None`, not an offset from the image's last symbol.
+    pub fn symbolize(&self, addr: usize) -> Option<(&str, usize)> {
+        self.symbolize_in(addr, AddressSpace::Runtime)
+    }
+
+    /// Same as [`Task::symbolize`], with `addr` given in `space`.
+    ///
+    /// With [`AddressSpace::LinkTime`], addresses copied from `otool` or
+    /// a disassembly of the file symbolize as they are.
+    pub fn symbolize_in(&self, addr: usize, space: AddressSpace) -> Option<(&str, usize)> {
+        let addr = self.runtime_address(addr, space);
+        if !(self.slide()..self.slide() + self.memory_size).contains(&addr) {
+            return None;
+        }
+
+        let section = self
+            .segments
+            .iter()
+            .flat_map(|segment| &segment.sections)
+            .map(|section| {
+                let start = self.slide() + section.vm_addr();
+                start..start + section.vm_size()
+            })
+            .find(|section| section.contains(&addr))?;
+
+        self.definitions
+            .iter()
+            .filter(|symbol| {
+                symbol.n_type & N_TYPE == N_SECT && (section.start..=addr).contains(&symbol.address)
+            })
+            .max_by_key(|symbol| symbol.address)
+            .map(|symbol| (symbol.name.as_str(), addr - symbol.address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        mach::{N_ABS, N_EXT, N_SECT, N_UNDF},
+        symbols::{
+            Nlist, Symbol, SymbolCache, TrieExport, definitions_coalesce, export_trie_read,
+            nlists_read, symbol_address_from_nlist,
+        },
+    };
+
+    #[test]
+    pub fn test_symbol_address_from_nlist() {
+        let slide = 0x1000;
+
+        assert_eq!(
+            symbol_address_from_nlist(N_SECT | N_EXT, 0x10, slide),
+            Some(0x1010)
+        );
+        assert_eq!(
+            symbol_address_from_nlist(N_ABS | N_EXT, 0x10, slide),
+            Some(0x10)
+        );
+        assert_eq!(symbol_address_from_nlist(N_UNDF | N_EXT, 0, slide), None);
+
+        // N_FUN stab
+        assert_eq!(symbol_address_from_nlist(0x24, 0x10, slide), None);
+    }
+
+    #[test]
+    pub fn test_definitions_coalesce() {
+        let symbol = |name: &str, address, n_type, weak| Symbol {
+            name: name.to_string(),
+            address,
+            n_type,
+            weak,
+        };
+
+        let definitions = definitions_coalesce(vec![
+            symbol("inline_fn", 0x10, N_SECT | N_EXT, true),
+            symbol("inline_fn", 0x20, N_SECT | N_EXT, true),
+            symbol("template_fn", 0x30, N_SECT | N_EXT, true),
+            symbol("template_fn", 0x40, N_SECT | N_EXT, false),
+            symbol("helper", 0x50, N_SECT, false),
+            symbol("helper", 0x60, N_SECT, false),
+        ]);
+
+        let addresses = |name: &str| {
+            definitions
+                .iter()
+                .filter(|symbol| symbol.name == name)
+                .map(|symbol| symbol.address)
+                .collect::<Vec<_>>()
+        };
+
+        // first weak definition wins
+        assert_eq!(addresses("inline_fn"), [0x10]);
+        // strong wins over weak
+        assert_eq!(addresses("template_fn"), [0x40]);
+        // locals are not coalesced
+        assert_eq!(addresses("helper"), [0x50, 0x60]);
+    }
+
+    #[test]
+    pub fn test_symbol_cache_bytes() {
+        let cache = SymbolCache {
+            uuid: [0x42; 16],
+            symtab: vec![
+                Nlist {
+                    name: "_main".to_string(),
+                    n_type: N_SECT | N_EXT,
+                    n_desc: 0,
+                    n_value: 0x3f40,
+                    bad_strx: None,
+                },
+                Nlist {
+                    name: "_printf".to_string(),
+                    n_type: N_UNDF | N_EXT,
+                    n_desc: 0x100,
+                    n_value: 0,
+                    bad_strx: None,
+                },
+            ],
+            indirect_symbols: vec![1, 0x80000000],
+        };
+
+        let bytes = cache.to_bytes();
+        let read = SymbolCache::from_bytes(&bytes).unwrap();
+
+        assert_eq!(read.uuid, cache.uuid);
+        assert_eq!(read.indirect_symbols, cache.indirect_symbols);
+        assert_eq!(read.symtab.len(), 2);
+        assert_eq!(read.symtab[1].name, "_printf");
+        assert_eq!(read.symtab[1].n_desc, 0x100);
+        assert_eq!(read.symtab[0].n_value, 0x3f40);
+
+        assert!(SymbolCache::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+    }
+
+    #[test]
+    pub fn test_nlists_bad_strx() {
+        let strtab = b"\0_main\0_printf\0";
+        let nlist = |n_strx: u32, n_type: u8| {
+            let mut entry = [0u8; 16];
+            entry[0..4].copy_from_slice(&n_strx.to_le_bytes());
+            entry[4] = n_type;
+            entry
+        };
+
+        let mut image = Vec::new();
+        image.extend_from_slice(&nlist(1, N_SECT | N_EXT));
+        image.extend_from_slice(&nlist(0x1000, N_UNDF | N_EXT));
+        image.extend_from_slice(&nlist(7, N_UNDF | N_EXT));
+        image.extend_from_slice(strtab);
+
+        let symtab = nlists_read(&image, 0, 3, 48, strtab.len() as u32).unwrap();
+        assert_eq!(symtab.len(), 3);
+        assert_eq!(symtab[0].name, "_main");
+        assert_eq!(symtab[1].bad_strx, Some(0x1000));
+        assert_eq!(symtab[1].name, "");
+        assert_eq!(symtab[2].name, "_printf");
+
+        // a name running off the end of the string table is out of it too
+        let symtab = nlists_read(&image, 0, 3, 48, strtab.len() as u32 - 1).unwrap();
+        assert_eq!(symtab[2].bad_strx, Some(7));
+
+        // the string table runs past the end of the image
+        assert!(nlists_read(&image, 0, 3, 48, 0x100).is_err());
+    }
+
+    #[test]
+    pub fn test_export_trie_read() {
+        #[rustfmt::skip]
+        let trie = [
+            // root: no terminal, one child
+            0x00, 0x01, b'_', 0x00, 5,
+            // "_": no terminal, two children
+            0x00, 0x02, b'm', b'a', b'i', b'n', 0x00, 18, b'f', b'o', b'o', 0x00, 23,
+            // "_main": regular, offset 0x3f0
+            0x03, 0x00, 0xf0, 0x07, 0x00,
+            // "_foo": absolute 0x10
+            0x02, 0x02, 0x10, 0x00,
+        ];
+
+        assert_eq!(
+            export_trie_read(&trie),
+            Ok(vec![
+                TrieExport {
+                    name: "_main".to_string(),
+                    value: 0x3f0,
+                    absolute: false,
+                },
+                TrieExport {
+                    name: "_foo".to_string(),
+                    value: 0x10,
+                    absolute: true,
+                },
+            ])
+        );
+
+        // a child pointing back at the root
+        let mut cycle = trie;
+        cycle[4] = 0;
+        assert_eq!(export_trie_read(&cycle), Err(0));
+
+        // truncated terminal of "_foo"
+        assert_eq!(export_trie_read(&trie[..25]), Err(23));
+    }
+}
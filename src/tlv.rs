@@ -0,0 +1,240 @@
+//! Thread local variables.
+//!
+//! Every `thread_local` of the image is reached through a descriptor in
+//! its `S_THREAD_LOCAL_VARIABLES` section (`__thread_vars`),
+//! `{ thunk, key, offset }`. Code loads the descriptor, then calls its
+//! thunk with it in `x0` and gets the address of the variable for the
+//! calling thread back.
+//!
+//! The storage of a thread is a copy of the template: the
+//! `S_THREAD_LOCAL_REGULAR` (`__thread_data`) and
+//! `S_THREAD_LOCAL_ZEROFILL` (`__thread_bss`) sections, which nothing
+//! accesses directly. `offset` is the place of the variable in it.
+//!
+//! dyld binds the thunks to `tlv_get_addr` of libSystem and gives the
+//! image a pthread key. That `tlv_get_addr` only allocates the storage
+//! of images dyld registered itself, which the loaded image is not, so
+//! the thunks are pointed at [`tlv_thunk`] instead. It does the same:
+//! the storage is kept under the key of the image, allocated on the
+//! first access of each thread, and freed when the thread exits.
+//!
+//! C++ `thread_local` objects with constructors are built by the code
+//! accessing them, which registers their destructors with
+//! `_tlv_atexit`, that part works for any image.
+
+use std::sync::Mutex;
+
+use crate::{
+    Task, abort_with,
+    error::{AbortReason, LinkError},
+    mach::{
+        S_THREAD_LOCAL_REGULAR, S_THREAD_LOCAL_VARIABLES, S_THREAD_LOCAL_ZEROFILL, SECTION_TYPE,
+    },
+};
+
+/// A `tlv_descriptor`, as defined in "mach-o/loader.h"
+#[repr(C)]
+struct TlvDescriptor {
+    thunk: usize,
+    key: libc::pthread_key_t,
+    offset: usize,
+}
+
+/// The template of an image, found by its key
+struct TlvTemplate {
+    key: libc::pthread_key_t,
+
+    /// address of the initial values
+    address: usize,
+    size: usize,
+}
+
+/// The templates of every task with thread local variables
+static TLV_TEMPLATES: Mutex<Vec<TlvTemplate>> = Mutex::new(Vec::new());
+
+#[unsafe(naked)]
+/// The thunk of every descriptor, returns the address of the variable
+/// of the descriptor in `x0`.
+///
+/// Compilers expect the thunk to only clobber `x0`, every other
+/// register that [`tlv_address`] may use is saved around it.
+unsafe extern "C" fn tlv_thunk() {
+    core::arch::naked_asm!(
+        "stp x29, x30, [sp, #-16]!",
+        "mov x29, sp",
+        "stp x1, x2, [sp, #-16]!",
+        "stp x3, x4, [sp, #-16]!",
+        "stp x5, x6, [sp, #-16]!",
+        "stp x7, x8, [sp, #-16]!",
+        "stp x9, x10, [sp, #-16]!",
+        "stp x11, x12, [sp, #-16]!",
+        "stp x13, x14, [sp, #-16]!",
+        "stp x15, x16, [sp, #-16]!",
+        "str x17, [sp, #-16]!",
+        "stp q0, q1, [sp, #-32]!",
+        "stp q2, q3, [sp, #-32]!",
+        "stp q4, q5, [sp, #-32]!",
+        "stp q6, q7, [sp, #-32]!",
+        "stp q16, q17, [sp, #-32]!",
+        "stp q18, q19, [sp, #-32]!",
+        "stp q20, q21, [sp, #-32]!",
+        "stp q22, q23, [sp, #-32]!",
+        "stp q24, q25, [sp, #-32]!",
+        "stp q26, q27, [sp, #-32]!",
+        "stp q28, q29, [sp, #-32]!",
+        "stp q30, q31, [sp, #-32]!",
+        "bl {address}",
+        "ldp q30, q31, [sp], #32",
+        "ldp q28, q29, [sp], #32",
+        "ldp q26, q27, [sp], #32",
+        "ldp q24, q25, [sp], #32",
+        "ldp q22, q23, [sp], #32",
+        "ldp q20, q21, [sp], #32",
+        "ldp q18, q19, [sp], #32",
+        "ldp q16, q17, [sp], #32",
+        "ldp q6, q7, [sp], #32",
+        "ldp q4, q5, [sp], #32",
+        "ldp q2, q3, [sp], #32",
+        "ldp q0, q1, [sp], #32",
+        "ldr x17, [sp], #16",
+        "ldp x15, x16, [sp], #16",
+        "ldp x13, x14, [sp], #16",
+        "ldp x11, x12, [sp], #16",
+        "ldp x9, x10, [sp], #16",
+        "ldp x7, x8, [sp], #16",
+        "ldp x5, x6, [sp], #16",
+        "ldp x3, x4, [sp], #16",
+        "ldp x1, x2, [sp], #16",
+        "ldp x29, x30, [sp], #16",
+        "ret",
+        address = sym tlv_address,
+    )
+}
+
+/// Returns the address of the variable of `descriptor` for the calling
+/// thread, allocating the storage of the thread on its first access.
+///
+/// There is no error to return to the program, running out of memory
+/// aborts, see [`crate::set_abort_hook`].
+extern "C" fn tlv_address(descriptor: &TlvDescriptor) -> usize {
+    let mut storage = unsafe { libc::pthread_getspecific(descriptor.key) };
+
+    if storage.is_null() {
+        let (address, size) = {
+            let templates = TLV_TEMPLATES.lock().unwrap_or_else(|err| err.into_inner());
+            match templates
+                .iter()
+                .find(|template| template.key == descriptor.key)
+            {
+                Some(template) => (template.address, template.size),
+                None => abort_with(AbortReason::TlvUnloaded),
+            }
+        };
+
+        storage = unsafe { libc::malloc(size.max(1)) };
+        if storage.is_null() {
+            abort_with(AbortReason::TlvAllocationFailed { size });
+        }
+        unsafe {
+            core::ptr::copy_nonoverlapping(address as *const u8, storage.cast(), size);
+            libc::pthread_setspecific(descriptor.key, storage);
+        }
+    }
+
+    storage.addr() + descriptor.offset
+}
+
+/// Frees the storage of an exiting thread.
+extern "C" fn tlv_free(storage: *mut libc::c_void) {
+    unsafe { libc::free(storage) };
+}
+
+impl Task {
+    /// Points the descriptors of the image at [`tlv_thunk`], under the
+    /// key of the task, creating it on first use.
+    ///
+    /// Binding writes the thunks dyld would use, this runs after it.
+    pub(crate) fn tlv_init(&mut self) -> Result<(), LinkError> {
+        if !self.header_flags().has_tlv_descriptors() {
+            return Ok(());
+        }
+
+        let sections = self
+            .segments
+            .iter()
+            .flat_map(|segment| &segment.sections)
+            .filter(|section| section.vm_size != 0);
+
+        let descriptors = sections
+            .clone()
+            .filter(|section| section.flags as i32 & SECTION_TYPE == S_THREAD_LOCAL_VARIABLES)
+            .collect::<Vec<_>>();
+        if descriptors.is_empty() {
+            return Ok(());
+        }
+
+        let key = match self.tlv_key {
+            Some(key) => key,
+            None => {
+                let template = sections
+                    .filter(|section| {
+                        matches!(
+                            section.flags as i32 & SECTION_TYPE,
+                            S_THREAD_LOCAL_REGULAR | S_THREAD_LOCAL_ZEROFILL
+                        )
+                    })
+                    .map(|section| (section.vm_addr, section.vm_addr + section.vm_size))
+                    .reduce(|(start, end), (other_start, other_end)| {
+                        (start.min(other_start), end.max(other_end))
+                    })
+                    .unwrap_or_default();
+
+                let mut key = 0;
+                let ret = unsafe { libc::pthread_key_create(&mut key, Some(tlv_free)) };
+                if ret != 0 {
+                    return Err(LinkError::ThreadLocalKey(
+                        std::io::Error::from_raw_os_error(ret),
+                    ));
+                }
+
+                TLV_TEMPLATES
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .push(TlvTemplate {
+                        key,
+                        address: self.slide() + template.0,
+                        size: template.1 - template.0,
+                    });
+                self.tlv_key = Some(key);
+                key
+            }
+        };
+
+        for section in descriptors {
+            let first = unsafe { self.memory.add(section.vm_addr).as_ptr() as *mut TlvDescriptor };
+            for index in 0..section.vm_size / size_of::<TlvDescriptor>() {
+                let descriptor = unsafe { &mut *first.add(index) };
+                descriptor.thunk = tlv_thunk as *const () as usize;
+                descriptor.key = key;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the key of the task.
+    ///
+    /// The storage of the threads still alive is not freed, pthread
+    /// only runs the destructor of a key for threads exiting.
+    pub(crate) fn tlv_shutdown(&mut self) {
+        let Some(key) = self.tlv_key.take() else {
+            return;
+        };
+
+        TLV_TEMPLATES
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .retain(|template| template.key != key);
+        unsafe { libc::pthread_key_delete(key) };
+    }
+}
@@ -0,0 +1,297 @@
+mod common;
+
+use std::{
+    ffi::CString,
+    fs,
+    sync::atomic::{AtomicBool, AtomicI32, Ordering},
+};
+
+use loader::{
+    Task, TaskOptions,
+    error::{ExecError, VmError},
+    jumper::ExecOptions,
+    linker::Linker,
+    mach,
+};
+
+#[test]
+#[ignore = "needs binaries/add/add, built from binaries/add/add.c"]
+pub fn test_call_symbol_repeatedly() {
+    let data = common::fixture("add/add");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.segments_protect_maxprot();
+    Linker::new().link_raw(&mut task);
+    task.segments_protect();
+
+    // the image is set up once, every call after that is just a call
+    for i in 0..1000u64 {
+        let sum = unsafe { task.call_symbol("add", &[i, 2 * i]) };
+        assert_eq!(sum.map(|sum| sum as u32), Some(3 * i as u32));
+    }
+
+    assert_eq!(unsafe { task.call_symbol("not_defined", &[]) }, None);
+}
+
+#[test]
+#[ignore = "needs binaries/add/add, built from binaries/add/add.c"]
+pub fn test_reload_segment() {
+    let data = common::fixture("add/add");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.prepare().expect("failed to prepare");
+    assert_eq!(
+        unsafe { task.call_symbol("add", &[5, 3]) }.map(|sum| sum as u32),
+        Some(8)
+    );
+
+    // __TEXT up to the end of the first two instructions of add, which
+    // become `sub w0, w0, w1; ret`
+    let text = task.slide() + task.preferred_base() as usize;
+    let add = task.symbol_address("add").expect("no add");
+    let mut patched =
+        unsafe { std::slice::from_raw_parts(text as *const u8, add - text + 8) }.to_vec();
+    let end = patched.len();
+    patched[end - 8..end - 4].copy_from_slice(&0x4b010000u32.to_le_bytes());
+    patched[end - 4..].copy_from_slice(&0xd65f03c0u32.to_le_bytes());
+
+    task.reload_segment("__TEXT", &patched)
+        .expect("failed to reload __TEXT");
+    assert_eq!(
+        unsafe { task.call_symbol("add", &[5, 3]) }.map(|sum| sum as u32),
+        Some(2)
+    );
+
+    // still sealed, and the rest of the task untouched
+    task.assert_protection().expect("__TEXT was not restored");
+
+    assert!(matches!(
+        task.reload_segment("__NOPE", &[0]),
+        Err(VmError::UnknownSegment { .. })
+    ));
+    assert!(matches!(
+        task.reload_segment("__TEXT", &vec![0; 1 << 30]),
+        Err(VmError::SegmentOverflow { .. })
+    ));
+}
+
+#[test]
+#[ignore = "needs binaries/tlv/tlv, built from binaries/tlv/tlv.cpp"]
+pub fn test_thread_local_variables() {
+    let data = common::fixture("tlv/tlv");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    assert!(task.header_flags().has_tlv_descriptors());
+    task.prepare().expect("failed to prepare");
+    unsafe { task.run_initializers() };
+
+    let next = |task: &Task, name| unsafe { task.call_symbol(name, &[]) }.map(|n| n as i32);
+    assert_eq!(next(&task, "counter_next"), Some(101));
+    assert_eq!(next(&task, "counter_next"), Some(102));
+    assert_eq!(next(&task, "plain_next"), Some(8));
+
+    // another thread gets its own, freshly constructed, copy
+    let counter_next = task
+        .symbol_address("counter_next")
+        .expect("no counter_next");
+    let other = std::thread::spawn(move || {
+        let counter_next =
+            unsafe { std::mem::transmute::<usize, extern "C" fn() -> i32>(counter_next) };
+        counter_next()
+    })
+    .join()
+    .expect("the thread panicked");
+    assert_eq!(other, 101);
+
+    assert_eq!(next(&task, "counter_next"), Some(103));
+
+    // the variables outlive the task once the image is taken raw
+    let counter_next =
+        unsafe { std::mem::transmute::<usize, extern "C" fn() -> i32>(counter_next) };
+    let (memory, size) = task.into_raw();
+    assert_eq!(counter_next(), 104);
+    mach::vm_dealloc_self(memory.as_ptr().addr() as u64, size);
+}
+
+#[test]
+#[ignore = "needs binaries/libadd/libadd.dylib, built from binaries/libadd/libadd.c"]
+pub fn test_call_dylib_export() {
+    let data = common::fixture("libadd/libadd.dylib");
+
+    // a dylib has no LC_MAIN, loading it is not an error
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    assert!(!task.has_entry_point());
+
+    task.prepare().expect("failed to prepare the dylib");
+    unsafe { task.run_initializers() };
+
+    assert!(task.symbol_address("add").is_some());
+    let initialized = unsafe { task.call_symbol("is_initialized", &[]) };
+    assert_eq!(initialized.map(|initialized| initialized as u32), Some(1));
+
+    let sum = unsafe { task.call_symbol("add", &[40, 2]) };
+    assert_eq!(sum.map(|sum| sum as u32), Some(42));
+}
+
+/// Whether libadd is loaded, `dlopen` does not load it with `RTLD_NOLOAD`
+fn libadd_loaded() -> bool {
+    let path = fs::canonicalize(common::fixture_path("libadd/libadd.dylib"))
+        .expect("failed to find libadd");
+    let path = CString::new(path.to_str().unwrap()).unwrap();
+
+    let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOLOAD) };
+    if handle.is_null() {
+        return false;
+    }
+    unsafe { libc::dlclose(handle) };
+    true
+}
+
+static TERMINATOR_SUM: AtomicI32 = AtomicI32::new(0);
+static TERMINATOR_LIBADD_LOADED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn terminator_hook(sum: i32) {
+    TERMINATOR_SUM.store(sum, Ordering::SeqCst);
+    TERMINATOR_LIBADD_LOADED.store(libadd_loaded(), Ordering::SeqCst);
+}
+
+#[test]
+#[ignore = "needs binaries/terminator/libterminator.dylib, built from binaries/terminator/terminator.c"]
+pub fn test_shutdown_terminators_before_dlclose() {
+    let data = common::fixture("terminator/libterminator.dylib");
+
+    let options = TaskOptions {
+        image_path: Some(common::fixture_path("terminator/libterminator.dylib")),
+        ..Default::default()
+    };
+    let mut task = unsafe { Task::with_options(data.as_ptr(), data.len(), &options) };
+    task.prepare().expect("failed to prepare the dylib");
+    unsafe { task.run_initializers() };
+    assert_eq!(task.terminators().len(), 1);
+
+    unsafe { task.call_symbol("set_hook", &[terminator_hook as *const () as u64]) };
+    assert!(task.dylib_handle("libadd.dylib").is_some());
+
+    // the terminator calls into libadd, which is only closed after it
+    task.shutdown();
+    assert_eq!(TERMINATOR_SUM.load(Ordering::SeqCst), 3);
+    assert!(TERMINATOR_LIBADD_LOADED.load(Ordering::SeqCst));
+
+    // the task gave its reference back, whether libadd is unloaded
+    // depends on who else holds one
+    assert!(task.dylib_handle("libadd.dylib").is_none());
+    assert_eq!(task.loaded_dylibs().count(), 0);
+
+    // shutting down again, or dropping, does nothing
+    task.shutdown();
+    assert!(task.terminators().is_empty());
+}
+
+#[test]
+#[ignore = "needs binaries/multicall/multicall, built from binaries/multicall/multicall.c"]
+pub fn test_multicall_argv0() {
+    let data = common::fixture("multicall/multicall");
+    let path = common::fixture_path("multicall/multicall");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.prepare().expect("failed to prepare");
+
+    // the program dispatches on argv[0] alone, whatever its real path
+    let run = |argv0: Option<&str>| {
+        let options = ExecOptions {
+            executable_path: Some(path.display().to_string()),
+            argv0: argv0.map(str::to_string),
+            ..Default::default()
+        };
+        task.run_forked(&options).expect("failed to run")
+    };
+    assert_eq!(run(Some("/usr/bin/true")), 0);
+    assert_eq!(run(Some("false")), 1);
+    assert_eq!(run(None), 2);
+}
+
+/// Returns hello_world with `main` replaced by `code`.
+fn hello_world_patched(code: &[u32]) -> Vec<u8> {
+    let mut data = common::fixture("hello_world/hello_world");
+    let nlist = common::symbol_nlist(&data, "_main");
+    let main = u64::from_le_bytes(data[nlist + 8..nlist + 16].try_into().unwrap());
+
+    // __TEXT starts the file, linked at 0x100000000
+    let offset = (main - 0x100000000) as usize;
+    for (index, instruction) in code.iter().enumerate() {
+        let at = offset + index * 4;
+        data[at..at + 4].copy_from_slice(&instruction.to_le_bytes());
+    }
+    data
+}
+
+#[test]
+pub fn test_crash_reported() {
+    // mov x0, #0; ldr x0, [x0]
+    let data = hello_world_patched(&[0xd2800000, 0xf9400000]);
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.prepare().expect("failed to prepare");
+
+    match task.run_forked(&ExecOptions::default()) {
+        Err(ExecError::Crashed {
+            signal,
+            address,
+            symbol,
+            ..
+        }) => {
+            assert!(matches!(signal, libc::SIGSEGV | libc::SIGBUS), "{signal}");
+            assert_eq!(address, 0);
+            assert_eq!(symbol.as_deref(), Some("main+0x4"));
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
+
+#[test]
+pub fn test_crash_in_dylib_reported() {
+    // movz/movk x16, strlen; mov x0, #0; br x16, strlen(NULL) faults
+    // in libSystem
+    let strlen = libc::strlen as *const () as u64;
+    let mut code = (0..4)
+        .map(|hw| {
+            let opcode = if hw == 0 { 0xd2800010 } else { 0xf2800010 };
+            opcode | (hw << 21) | (((strlen >> (hw * 16)) & 0xffff) as u32) << 5
+        })
+        .collect::<Vec<u32>>();
+    code.extend([0xd2800000, 0xd61f0200]);
+    let data = hello_world_patched(&code);
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.prepare().expect("failed to prepare");
+
+    // the pc is in no section of the image, it is not symbolized
+    match task.run_forked(&ExecOptions::default()) {
+        Err(ExecError::Crashed {
+            signal, pc, symbol, ..
+        }) => {
+            assert!(matches!(signal, libc::SIGSEGV | libc::SIGBUS), "{signal}");
+            assert_eq!(task.symbolize(pc as usize), None);
+            assert_eq!(symbol, None);
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
+
+#[test]
+pub fn test_stack_overflow_reported() {
+    // sub sp, sp, #0x1000; str xzr, [sp]; b .-8, until the guard page
+    let data = hello_world_patched(&[0xd14007ff, 0xf90003ff, 0x17fffffe]);
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.prepare().expect("failed to prepare");
+
+    // the handler runs on its alternate stack, the report still arrives
+    match task.run_forked(&ExecOptions::default()) {
+        Err(ExecError::Crashed { signal, symbol, .. }) => {
+            assert!(matches!(signal, libc::SIGSEGV | libc::SIGBUS), "{signal}");
+            assert_eq!(symbol.as_deref(), Some("main+0x4"));
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
@@ -0,0 +1,81 @@
+//! Helpers shared by the integration tests
+#![allow(dead_code)]
+
+use std::{fs, path::PathBuf};
+
+/// Returns the path of the fixture `name`, relative to `binaries/`.
+pub fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("binaries")
+        .join(name)
+}
+
+/// Reads the fixture `name`, relative to `binaries/`.
+///
+/// # Panics
+///
+/// If the fixture can not be read.
+pub fn fixture(name: &str) -> Vec<u8> {
+    let path = fixture_path(name);
+    fs::read(&path).unwrap_or_else(|err| panic!("failed to read {}: {err}", path.display()))
+}
+
+/// Returns the offset of the `LC_SEGMENT_64` of the segment `name`
+/// within the thin image `data`.
+///
+/// # Panics
+///
+/// If the image has no such segment.
+pub fn segment_command(data: &[u8], name: &str) -> usize {
+    let mut segname = [0u8; 16];
+    segname[..name.len()].copy_from_slice(name.as_bytes());
+
+    // the segname follows cmd and cmdsize
+    (0..data.len() - segname.len())
+        .find(|&index| data[index..index + segname.len()] == segname)
+        .map(|index| index - 8)
+        .unwrap_or_else(|| panic!("no {name} segment"))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Returns the offset of the first load command `cmd` within the thin
+/// image `data`.
+///
+/// # Panics
+///
+/// If the image has no such load command.
+pub fn load_command(data: &[u8], cmd: u32) -> usize {
+    let mut command = 32;
+    for _ in 0..u32_at(data, 16) {
+        if u32_at(data, command) == cmd {
+            return command;
+        }
+        command += u32_at(data, command + 4) as usize;
+    }
+    panic!("no load command {cmd:#x}");
+}
+
+/// Returns the offset of the `nlist_64` of the symbol `name`, with its
+/// leading '_', within the thin image `data`.
+///
+/// # Panics
+///
+/// If the image has no `LC_SYMTAB` or no such symbol.
+pub fn symbol_nlist(data: &[u8], name: &str) -> usize {
+    // LC_SYMTAB
+    let command = load_command(data, 0x2);
+    let symoff = u32_at(data, command + 8) as usize;
+    let nsyms = u32_at(data, command + 12) as usize;
+    let stroff = u32_at(data, command + 16) as usize;
+
+    (0..nsyms)
+        .map(|index| symoff + index * 16)
+        .find(|&nlist| {
+            let strx = stroff + u32_at(data, nlist) as usize;
+            data[strx..].split(|&byte| byte == 0).next() == Some(name.as_bytes())
+        })
+        .unwrap_or_else(|| panic!("no {name} symbol"))
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use std::process::Command;
+
+/// Runs the loader binary on `fixture` and returns what it wrote to stderr.
+fn run_fixture(fixture: &str, flags: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_loader"))
+        .args(flags)
+        .arg(common::fixture_path(fixture))
+        .output()
+        .expect("failed to run the loader");
+
+    assert!(
+        output.status.success(),
+        "loader exited with {}",
+        output.status
+    );
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+pub fn test_hello_world_fprintf() {
+    // goes through dylib loading, GOT binding, protection and the jump
+    let stderr = run_fixture("hello_world_fprintf/hello_world", &[]);
+    assert!(
+        stderr.contains("Hello, World"),
+        "unexpected output: {stderr}"
+    );
+}
+
+#[test]
+pub fn test_hello_world_no_linkedit() {
+    let stderr = run_fixture("hello_world_fprintf/hello_world", &["--no-linkedit"]);
+    assert!(
+        stderr.contains("Hello, World"),
+        "unexpected output: {stderr}"
+    );
+}
@@ -0,0 +1,969 @@
+mod common;
+
+use std::{
+    error::Error as _,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::fd::AsRawFd,
+};
+
+use loader::{
+    FlatLookup, LoadMode, Task, TaskOptions,
+    error::{DlError, Error, ExecError, LinkError, LoadError, SecurityError},
+    linker::{FLAT_LOOKUP, GotStatus, Linker, SELF_IMAGE},
+    mach,
+    pac::{self, PacKey},
+    symbols::SymbolCache,
+};
+
+#[test]
+pub fn test_symbols_init() {
+    let data = common::fixture("hello_world/hello_world");
+
+    // loading resolves nothing
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    assert!(task.symbols.is_empty());
+
+    task.symbols_init().expect("failed to resolve the imports");
+    assert_eq!(
+        task.symbols,
+        [("write".to_string(), libc::write as *const () as u64)]
+    );
+}
+
+#[test]
+pub fn test_resolve_all_imports() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.segments_protect_maxprot();
+
+    let bindings = task.resolve_all_imports().expect("failed to link");
+
+    let fprintf = bindings
+        .iter()
+        .find(|binding| binding.symbol == "fprintf")
+        .expect("fprintf was not bound");
+    assert_eq!(fprintf.dylib, "/usr/lib/libSystem.B.dylib");
+    assert_eq!(fprintf.target, libc::fprintf as *const () as usize);
+
+    // every slot now holds what it was bound to
+    for binding in &bindings {
+        let slot = unsafe { (binding.slot_addr as *const u64).read() };
+        assert_eq!(slot as usize, binding.target, "{}", binding.symbol);
+    }
+
+    // and the dump names each slot after the symbol bound to it
+    let got = task.dump_got();
+    for binding in &bindings {
+        let entry = got
+            .iter()
+            .find(|entry| entry.slot_addr == binding.slot_addr)
+            .unwrap_or_else(|| panic!("{} is not in the GOT dump", binding.symbol));
+        assert_eq!(entry.symbol.as_deref(), Some(binding.symbol.as_str()));
+        assert_eq!(entry.resolved_addr, binding.target);
+        assert_eq!(entry.status, GotStatus::Bound);
+    }
+}
+
+#[test]
+pub fn test_symbol_cache_load() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let mut walked = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    let cache = walked.symbol_cache().expect("no symbol cache");
+
+    // the cache as stored between runs
+    let options = TaskOptions {
+        symbol_cache: Some(SymbolCache::from_bytes(&cache.to_bytes()).expect("bad cache")),
+        ..Default::default()
+    };
+    let mut cached = unsafe { Task::with_options(data.as_ptr(), data.len(), &options) };
+
+    // the same symbols, each in its own task
+    let relative = |task: &Task| {
+        let mut exports = task
+            .exports()
+            .into_iter()
+            .map(|(name, address)| (name, address - task.slide()))
+            .collect::<Vec<_>>();
+        exports.sort();
+        exports
+    };
+    assert_eq!(relative(&walked), relative(&cached));
+
+    let main = cached.symbol_address("main").expect("no main");
+    assert_eq!(cached.symbolize(main + 4), Some(("main", 4)));
+
+    // and the same imports, bound to the same targets
+    let bindings = |task: &mut Task| {
+        task.segments_protect_maxprot();
+        task.resolve_all_imports()
+            .expect("failed to link")
+            .into_iter()
+            .map(|binding| (binding.symbol, binding.dylib, binding.target))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(bindings(&mut walked), bindings(&mut cached));
+}
+
+#[test]
+pub fn test_shared_cache_imports() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.segments_protect_maxprot();
+
+    let bindings = task.resolve_all_imports().expect("failed to link");
+    assert!(bindings.len() >= 2, "{bindings:?}");
+
+    for binding in &bindings {
+        // libSystem only re-exports, the symbols live in the cache
+        // resident dylibs underneath it
+        let name = std::ffi::CString::new(binding.symbol.as_str()).unwrap();
+        let flat = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()) };
+        assert_eq!(binding.target, flat.addr(), "{}", binding.symbol);
+
+        let mut info = std::mem::MaybeUninit::<libc::Dl_info>::uninit();
+        assert_ne!(
+            unsafe { libc::dladdr(binding.target as *const libc::c_void, info.as_mut_ptr()) },
+            0,
+            "{} does not point into a loaded image",
+            binding.symbol
+        );
+
+        let image = unsafe { std::ffi::CStr::from_ptr(info.assume_init().dli_fname) };
+        assert!(
+            image.to_bytes().starts_with(b"/usr/lib/"),
+            "{} resolved into {image:?}",
+            binding.symbol
+        );
+    }
+}
+
+static MALLOC_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+extern "C" fn malloc_replacement(_size: libc::size_t) -> *mut libc::c_void {
+    MALLOC_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    std::ptr::null_mut()
+}
+
+#[test]
+pub fn test_override_import() {
+    let mut data = common::fixture("hello_world_fprintf/hello_world");
+
+    // rename the fprintf import to malloc, main calls it once
+    let (from, to) = (b"_fprintf\0", b"_malloc\0\0");
+    for index in 0..data.len() - from.len() {
+        if &data[index..index + from.len()] == from {
+            data[index..index + to.len()].copy_from_slice(to);
+        }
+    }
+
+    // overridden once the task is bound and sealed
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.prepare().expect("failed to prepare");
+    task.override_import("malloc", malloc_replacement as *const () as usize)
+        .expect("failed to override malloc");
+    assert!(task.override_import("not_imported", 0).is_err());
+
+    // the slot holds the replacement and is sealed again
+    let slot = task
+        .dump_got()
+        .into_iter()
+        .find(|entry| entry.symbol.as_deref() == Some("malloc"))
+        .expect("no malloc slot");
+    assert_eq!(slot.resolved_addr, malloc_replacement as *const () as usize);
+    task.assert_protection().expect("not sealed again");
+
+    assert_eq!(unsafe { task.call_symbol("main", &[0, 0]) }, Some(0));
+    assert_eq!(MALLOC_CALLS.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[test]
+pub fn test_report_protections() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.prepare().expect("failed to prepare");
+
+    let protections = task.report_protections().expect("failed to query");
+    let (_, text, text_max) = protections
+        .iter()
+        .find(|(name, _, _)| name == "__TEXT")
+        .expect("no __TEXT");
+    assert_eq!(*text, libc::VM_PROT_READ | libc::VM_PROT_EXECUTE);
+    assert_eq!(*text_max, *text);
+
+    // querying does not change anything
+    assert_eq!(task.report_protections().unwrap(), protections);
+}
+
+#[test]
+pub fn test_wx_invariant() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.prepare().expect("failed to prepare");
+    task.assert_wx_invariant().expect("sealed task is w+x");
+
+    // a page of __TEXT made writable, where the kernel allows it
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    let main = task.symbol_address("main").expect("no main");
+    let page = main & !(mach::vm_page_size() - 1);
+    let rwx = libc::VM_PROT_READ | libc::VM_PROT_WRITE | libc::VM_PROT_EXECUTE;
+
+    if mach::try_vm_protect(page as u64, mach::vm_page_size(), 0, rwx).is_ok() {
+        match task.assert_wx_invariant() {
+            Err(SecurityError::WritableAndExecutable {
+                segment, address, ..
+            }) => {
+                assert_eq!(segment, "__TEXT");
+                assert_eq!(address, page as u64);
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+}
+
+#[test]
+pub fn test_into_raw() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    let main = task.symbol_address("main").expect("no main");
+    let (memory, size) = (task.memory(), task.memory_size());
+    assert_eq!(memory.as_ptr().addr(), task.slide());
+
+    assert_eq!(task.into_raw(), (memory, size));
+
+    // the task is gone, its image is not
+    let region = mach::vm_region_containing_self(main as u64).expect("the image was unmapped");
+    assert!(region.address >= memory.as_ptr().addr() as u64);
+
+    mach::vm_dealloc_self(memory.as_ptr().addr() as u64, size);
+}
+
+#[test]
+pub fn test_from_fd() {
+    let path = common::fixture_path("hello_world/hello_world");
+    let file = fs::File::open(&path).expect("failed to open the fixture");
+
+    let task = Task::from_fd(file.as_raw_fd()).expect("failed to load from the fd");
+    assert!(task.symbol_address("main").is_some());
+}
+
+#[test]
+pub fn test_from_fd_unlinked() {
+    // an anonymous file, as a host receiving the image over a socket has:
+    // there is no path left to load from
+    let path = std::env::temp_dir().join(format!("execvm-from-fd-{}", std::process::id()));
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .expect("failed to create the file");
+    fs::remove_file(&path).expect("failed to unlink the file");
+
+    match Task::from_fd(file.as_raw_fd()) {
+        Err(LoadError::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof),
+        other => panic!("unexpected result: {other:?}"),
+    }
+
+    file.write_all(&common::fixture("hello_world/hello_world"))
+        .expect("failed to write the image");
+    let task = Task::from_fd(file.as_raw_fd()).expect("failed to load from the fd");
+    assert!(task.symbol_address("main").is_some());
+}
+
+#[test]
+pub fn test_two_tasks() {
+    let data = common::fixture("hello_world/hello_world");
+
+    // a task loading while another one lives, as a loaded program
+    // using the loader would
+    let mut outer = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    outer.prepare().expect("failed to prepare the outer task");
+    let mut inner = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    inner.prepare().expect("failed to prepare the inner task");
+
+    assert_ne!(outer.slide(), inner.slide());
+    let outer_main = outer.symbol_address("main").expect("no main");
+    let inner_main = inner.symbol_address("main").expect("no main");
+    assert_eq!(outer_main - outer.slide(), inner_main - inner.slide());
+
+    // dropping one leaves the other mapped and intact
+    drop(inner);
+    let text = unsafe { std::slice::from_raw_parts(outer_main as *const u8, 16) };
+    let offset = outer.unslide(outer_main) - outer.preferred_base() as usize;
+    assert_eq!(text, &data[offset..offset + 16]);
+}
+
+#[test]
+pub fn test_base_address_unavailable() {
+    let mut data = common::fixture("hello_world_fprintf/hello_world");
+
+    // clear MH_PIE in the header of the arm64 slice
+    let magic = [0xcf, 0xfa, 0xed, 0xfe, 0x0c, 0x00, 0x00, 0x01];
+    let header = (0..data.len() - magic.len())
+        .find(|&index| data[index..index + magic.len()] == magic)
+        .expect("no arm64 slice");
+    let flags = header + 24;
+    let pie = u32::from_le_bytes(data[flags..flags + 4].try_into().unwrap()) & !0x200000;
+    data[flags..flags + 4].copy_from_slice(&pie.to_le_bytes());
+
+    // take the preferred base, unless something else already has
+    let base = 0x100000000;
+    let page = mach::vm_alloc_fixed_self(base, mach::vm_page_size());
+
+    let err = unsafe { Task::try_with_pointer(data.as_ptr(), data.len()) }.unwrap_err();
+    match err {
+        LoadError::BaseAddressUnavailable { required, .. } => assert_eq!(required, base),
+        err => panic!("unexpected error: {err}"),
+    }
+
+    if let Ok(page) = page {
+        mach::vm_dealloc_self(page.as_ptr().addr() as u64, mach::vm_page_size());
+    }
+}
+
+#[test]
+pub fn test_slide_hint() {
+    let data = common::fixture("hello_world/hello_world");
+    let with_hint = |slide_hint| TaskOptions {
+        slide_hint: Some(slide_hint),
+        ..Default::default()
+    };
+
+    let first = unsafe { Task::try_with_pointer(data.as_ptr(), data.len()) }.expect("load failed");
+    let slide = first.slide();
+
+    // taken by the first task, the image lands elsewhere
+    let second = unsafe { Task::try_with_options(data.as_ptr(), data.len(), &with_hint(slide)) }
+        .expect("a taken hint failed the load");
+    assert_ne!(second.slide(), slide);
+
+    // free again, the image lands right there
+    drop(first);
+    let third = unsafe { Task::try_with_options(data.as_ptr(), data.len(), &with_hint(slide)) }
+        .expect("load failed");
+    assert_eq!(third.slide(), slide);
+}
+
+#[test]
+pub fn test_protection_inconsistent() {
+    let mut data = common::fixture("hello_world/hello_world");
+
+    // leave __TEXT r-x initially, but at most r--
+    let name = b"__TEXT\0\0\0\0\0\0\0\0\0\0";
+    let segname = (0..data.len() - name.len())
+        .find(|&index| data[index..index + name.len()] == *name)
+        .expect("no __TEXT segment");
+    let maxprot = segname + 16 + 32;
+    assert_eq!(
+        data[maxprot + 4..maxprot + 8],
+        (libc::VM_PROT_READ | libc::VM_PROT_EXECUTE).to_le_bytes()
+    );
+    data[maxprot..maxprot + 4].copy_from_slice(&libc::VM_PROT_READ.to_le_bytes());
+
+    let err = unsafe { Task::try_with_pointer(data.as_ptr(), data.len()) }.unwrap_err();
+    match err {
+        LoadError::ProtectionInconsistent {
+            segment,
+            initprot,
+            maxprot,
+        } => {
+            assert_eq!(segment, "__TEXT");
+            assert_eq!(initprot, libc::VM_PROT_READ | libc::VM_PROT_EXECUTE);
+            assert_eq!(maxprot, libc::VM_PROT_READ);
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+pub fn test_file_size_exceeds_vm_size() {
+    let mut data = common::fixture("hello_world/hello_world");
+
+    // __DATA_CONST maps a page more of the file than it has memory for
+    let command = common::segment_command(&data, "__DATA_CONST");
+    let vmsize = u64::from_le_bytes(data[command + 32..command + 40].try_into().unwrap());
+    data[command + 48..command + 56].copy_from_slice(&(vmsize + 0x4000).to_le_bytes());
+
+    let err = unsafe { Task::try_with_pointer(data.as_ptr(), data.len()) }.unwrap_err();
+    match err {
+        LoadError::FileSizeExceedsVmSize {
+            segment,
+            filesize,
+            vmsize: size,
+        } => {
+            assert_eq!(segment, "__DATA_CONST");
+            assert_eq!((filesize, size), (vmsize + 0x4000, vmsize));
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+
+    // the same check before writing into another process, it fails
+    // before asking for the task port
+    let err =
+        unsafe { Task::with_pointer_into(libc::getpid(), data.as_ptr(), data.len()) }.unwrap_err();
+    assert!(
+        matches!(err, LoadError::FileSizeExceedsVmSize { .. }),
+        "{err}"
+    );
+}
+
+#[test]
+pub fn test_invalid_utf8_section_name() {
+    let mut data = common::fixture("hello_world/hello_world");
+
+    // the first section of __TEXT, __text, follows its segment_command_64
+    let command = common::segment_command(&data, "__TEXT");
+    assert_eq!(&data[command + 72..command + 79], b"__text\0");
+    data[command + 78] = 0xff;
+
+    let task = unsafe { Task::try_with_pointer(data.as_ptr(), data.len()) }
+        .expect("failed to load a non UTF-8 section name");
+    let names = task
+        .executable_sections()
+        .iter()
+        .map(|section| section.name().to_string())
+        .collect::<Vec<_>>();
+    assert!(
+        names.iter().any(|name| name == "<invalid utf8>"),
+        "{names:?}"
+    );
+}
+
+#[test]
+pub fn test_zero_vm_size_segment() {
+    let mut data = common::fixture("hello_world/hello_world");
+
+    // __PAGEZERO shrunk to nothing, like linker padding: it is skipped
+    let command = common::segment_command(&data, "__PAGEZERO");
+    data[command + 32..command + 40].copy_from_slice(&0u64.to_le_bytes());
+
+    let mut task = unsafe { Task::try_with_pointer(data.as_ptr(), data.len()) }
+        .expect("failed to load a zero sized segment");
+    task.prepare().expect("failed to prepare");
+
+    // __LINKEDIT without a vm range, but still mapping the file
+    let command = common::segment_command(&data, "__LINKEDIT");
+    data[command + 32..command + 40].copy_from_slice(&0u64.to_le_bytes());
+
+    let err = unsafe { Task::try_with_pointer(data.as_ptr(), data.len()) }.unwrap_err();
+    match err {
+        LoadError::FileSizeExceedsVmSize {
+            segment, vmsize, ..
+        } => {
+            assert_eq!(segment, "__LINKEDIT");
+            assert_eq!(vmsize, 0);
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+pub fn test_bad_entry_point() {
+    let mut data = common::fixture("hello_world/hello_world");
+
+    // LC_MAIN pointing into __LINKEDIT
+    let linkedit = common::segment_command(&data, "__LINKEDIT");
+    let fileoff = data[linkedit + 40..linkedit + 48].to_vec();
+    let main = common::load_command(&data, 0x80000028);
+    data[main + 8..main + 16].copy_from_slice(&fileoff);
+
+    let err = unsafe { Task::try_with_pointer(data.as_ptr(), data.len()) }.unwrap_err();
+    match err {
+        LoadError::BadEntryPoint { entry, segment } => {
+            assert_eq!(entry.to_le_bytes(), fileoff[..]);
+            assert_eq!(segment.as_deref(), Some("__LINKEDIT"));
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+pub fn test_segment_outside_image() {
+    let data = common::fixture("hello_world/hello_world");
+    let macho = goblin::mach::MachO::parse(&data, 0).expect("failed to parse");
+
+    // the image handed over ends where __DATA_CONST starts
+    let command = common::segment_command(&data, "__DATA_CONST");
+    let fileoff = u64::from_le_bytes(data[command + 40..command + 48].try_into().unwrap());
+    let image = &data[..fileoff as usize];
+
+    let err = Task::from_macho(&macho, image).unwrap_err();
+    match err {
+        LoadError::SegmentOutsideImage {
+            segment,
+            fileoff: offset,
+            ..
+        } => {
+            assert_eq!(segment, "__DATA_CONST");
+            assert_eq!(offset, fileoff);
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+#[cfg(feature = "jit")]
+#[test]
+pub fn test_jit_prepare() {
+    let data = common::fixture("hello_world/hello_world");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.prepare().expect("failed to prepare");
+
+    // __TEXT is MAP_JIT memory, rwx with W^X enforced per thread, every
+    // other segment is sealed as usual
+    let rwx = libc::VM_PROT_READ | libc::VM_PROT_WRITE | libc::VM_PROT_EXECUTE;
+    let protections = task
+        .report_protections()
+        .expect("failed to read protections");
+    for (segment, protection, _) in &protections {
+        match segment.as_str() {
+            "__TEXT" => assert_eq!(*protection, rwx),
+            "__DATA_CONST" => assert_eq!(*protection, libc::VM_PROT_READ),
+            _ => {}
+        }
+    }
+    task.assert_wx_invariant().expect("W^X violated");
+
+    // this thread may execute it
+    assert_eq!(unsafe { task.call_symbol("main", &[]) }, Some(0));
+}
+
+#[test]
+pub fn test_link_after_protect() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.segments_protect_maxprot();
+    task.segments_protect();
+
+    // the GOT is read-only now, binding used to fault on it
+    let got = task
+        .section_data("__DATA_CONST", "__got")
+        .expect("no __got section");
+    let region =
+        mach::vm_region_containing_self(got.as_ptr().addr() as u64).expect("the GOT is not mapped");
+    assert_eq!(region.protection, libc::VM_PROT_READ);
+
+    Linker::new().link_raw(&mut task);
+
+    task.assert_protection()
+        .expect("link_raw did not seal the task again");
+}
+
+#[test]
+pub fn test_entry_point_prologue() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    let entry = task.entry_address().expect("no entry point");
+
+    // LC_MAIN points at main
+    assert_eq!(task.symbol_address("main"), Some(entry));
+
+    // and main starts with a prologue
+    let instruction = unsafe { (entry as *const u32).read() };
+    let pacibsp = instruction == 0xd503237f;
+    let sub_sp = instruction & 0xff8003ff == 0xd10003ff; // sub sp, sp, #imm
+    let stp_fp_lr = instruction & 0xffc07fff == 0xa9807bfd; // stp x29, x30, [sp, #-imm]!
+    assert!(
+        pacibsp || sub_sp || stp_fp_lr,
+        "{instruction:#010x} at the entry point is not a prologue"
+    );
+}
+
+#[test]
+pub fn test_dylib_handle() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+
+    let dylibs = task.loaded_dylibs().collect::<Vec<_>>();
+    assert_eq!(dylibs.len(), 1);
+    assert_eq!(dylibs[0].0, "/usr/lib/libSystem.B.dylib");
+
+    let handle = task
+        .dylib_handle("libSystem.B.dylib")
+        .expect("libSystem was not found by file name");
+    assert_eq!(handle.addr(), dylibs[0].1);
+    assert_eq!(
+        task.dylib_handle("/usr/lib/libSystem.B.dylib"),
+        Some(handle)
+    );
+    assert_eq!(task.dylib_handle("libc++.1.dylib"), None);
+
+    let fprintf = unsafe { libc::dlsym(handle, c"fprintf".as_ptr()) };
+    assert_eq!(fprintf.addr(), libc::fprintf as *const () as usize);
+}
+
+#[test]
+pub fn test_on_missing_dylib() {
+    let mut data = common::fixture("hello_world_fprintf/hello_world");
+
+    // rename the libSystem dependency to one that does not exist
+    let (from, to) = (b"/usr/lib/libSystem.B.dylib", b"/usr/lib/libNoSuch.B.dylib");
+    for index in 0..data.len() - from.len() {
+        if &data[index..index + from.len()] == from {
+            data[index..index + to.len()].copy_from_slice(to);
+        }
+    }
+
+    let err = unsafe { Task::try_with_options(data.as_ptr(), data.len(), &TaskOptions::default()) }
+        .unwrap_err();
+    match err {
+        LoadError::DylibNotFound { name, .. } => assert_eq!(name, "/usr/lib/libNoSuch.B.dylib"),
+        err => panic!("unexpected error: {err}"),
+    }
+
+    // libSystem stands in for the missing dylib
+    let options = TaskOptions {
+        on_missing_dylib: Some(Box::new(|name| {
+            assert_eq!(name, "/usr/lib/libNoSuch.B.dylib");
+            let handle =
+                unsafe { libc::dlopen(c"/usr/lib/libSystem.B.dylib".as_ptr(), libc::RTLD_NOW) };
+            Some(handle)
+        })),
+        ..Default::default()
+    };
+    let task = unsafe { Task::try_with_options(data.as_ptr(), data.len(), &options) }
+        .expect("the substitute was not used");
+    assert!(task.dylib_handle("libNoSuch.B.dylib").is_some());
+}
+
+#[test]
+pub fn test_missing_weak_dylib() {
+    let mut data = common::fixture("hello_world/hello_world");
+
+    // libSystem weak linked, and renamed to a dylib that does not exist
+    let command = common::load_command(&data, 0xc);
+    data[command..command + 4].copy_from_slice(&0x80000018_u32.to_le_bytes());
+    let (from, to) = (b"/usr/lib/libSystem.B.dylib", b"/usr/lib/libNoSuch.B.dylib");
+    let name = (command..data.len() - from.len())
+        .find(|&index| &data[index..index + from.len()] == from)
+        .expect("no libSystem install name");
+    data[name..name + to.len()].copy_from_slice(to);
+
+    // skipped without asking the hook
+    let options = TaskOptions {
+        on_missing_dylib: Some(Box::new(|name| panic!("asked for weak dylib {name}"))),
+        ..Default::default()
+    };
+    let mut task = unsafe { Task::try_with_options(data.as_ptr(), data.len(), &options) }
+        .expect("failed to load without the weak dylib");
+    assert!(task.dylib_handle("libNoSuch.B.dylib").is_none());
+
+    // write is not a weak import
+    task.segments_protect_maxprot();
+    match task.resolve_all_imports().unwrap_err() {
+        LinkError::UnresolvedSymbol { symbol, dylib, .. } => {
+            assert_eq!(symbol, "write");
+            assert_eq!(dylib, "/usr/lib/libNoSuch.B.dylib");
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+
+    // the only chained import (DYLD_CHAINED_IMPORT), made weak
+    let chained_fixups = common::load_command(&data, 0x80000034);
+    let dataoff = u32::from_le_bytes(data[chained_fixups + 8..][..4].try_into().unwrap()) as usize;
+    let imports_offset = u32::from_le_bytes(data[dataoff + 8..][..4].try_into().unwrap()) as usize;
+    data[dataoff + imports_offset + 1] |= 0x01;
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.segments_protect_maxprot();
+    let bindings = task.resolve_all_imports().expect("failed to link");
+    assert!(bindings.iter().all(|binding| binding.symbol != "write"));
+}
+
+#[test]
+pub fn test_error_source_chain() {
+    let mut data = common::fixture("hello_world_fprintf/hello_world");
+
+    // rename the fprintf import to a symbol libSystem does not define
+    let (from, to) = (b"_fprintf\0", b"_fprintX\0");
+    for index in 0..data.len() - from.len() {
+        if &data[index..index + from.len()] == from {
+            data[index..index + to.len()].copy_from_slice(to);
+        }
+    }
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    let err = task.prepare().unwrap_err();
+    match &err {
+        ExecError::Link(LinkError::UnresolvedSymbol { symbol, .. }) => {
+            assert_eq!(symbol, "fprintX")
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+
+    // the link step, then the dlsym failure
+    let link = err.source().expect("no link error");
+    assert!(link.downcast_ref::<LinkError>().is_some());
+    let dlerror = link.source().expect("no dlerror");
+    assert!(dlerror.downcast_ref::<DlError>().is_some());
+    assert!(dlerror.to_string().contains("fprintX"), "{dlerror}");
+    assert!(dlerror.source().is_none());
+
+    // the unified error is the one it holds
+    let message = err.to_string();
+    let err = Error::from(err);
+    assert_eq!(err.to_string(), message);
+    assert!(err.source().unwrap().downcast_ref::<LinkError>().is_some());
+}
+
+#[test]
+#[ignore = "needs binaries/flat/flat, built from binaries/flat/flat.c"]
+pub fn test_flat_namespace_imports() {
+    let data = common::fixture("flat/flat");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.segments_protect_maxprot();
+
+    let bindings = task.resolve_all_imports().expect("failed to link");
+
+    let strlen = bindings
+        .iter()
+        .find(|binding| binding.symbol == "strlen")
+        .expect("strlen was not bound");
+    assert_eq!(strlen.dylib, FLAT_LOOKUP);
+    assert_eq!(strlen.target, libc::strlen as *const () as usize);
+
+    // the missing weak import is left null
+    assert!(
+        bindings
+            .iter()
+            .all(|binding| binding.symbol != "flat_missing")
+    );
+}
+
+#[test]
+#[ignore = "needs binaries/preload/preload and libmymalloc.dylib, built from binaries/preload/*.c"]
+pub fn test_preload_dylibs() {
+    let data = common::fixture("preload/preload");
+    let library = common::fixture_path("preload/libmymalloc.dylib")
+        .display()
+        .to_string();
+
+    let options = TaskOptions {
+        preload_dylibs: vec![library.clone().into()],
+        ..Default::default()
+    };
+    let mut task = unsafe { Task::try_with_options(data.as_ptr(), data.len(), &options) }
+        .expect("failed to load");
+    task.segments_protect_maxprot();
+
+    let handle = unsafe {
+        let path = std::ffi::CString::new(library.as_str()).unwrap();
+        libc::dlopen(path.as_ptr(), libc::RTLD_NOLOAD)
+    };
+    assert!(!handle.is_null(), "the library was not preloaded");
+
+    let bindings = task.resolve_all_imports().expect("failed to link");
+
+    // malloc comes from the preloaded library, not libsystem_malloc
+    let malloc = bindings
+        .iter()
+        .find(|binding| binding.symbol == "malloc")
+        .expect("malloc was not bound");
+    assert_eq!(malloc.dylib, library);
+    assert_eq!(malloc.target, unsafe {
+        libc::dlsym(handle, c"malloc".as_ptr()).addr()
+    });
+
+    // what it does not define is looked up as usual
+    let free = bindings
+        .iter()
+        .find(|binding| binding.symbol == "free")
+        .expect("free was not bound");
+    assert_eq!(free.dylib, FLAT_LOOKUP);
+
+    unsafe { libc::dlclose(handle) };
+}
+
+#[test]
+pub fn test_link_only() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let options = TaskOptions {
+        load_mode: LoadMode::LinkOnly,
+        ..Default::default()
+    };
+    let task = unsafe { Task::try_with_options(data.as_ptr(), data.len(), &options) }
+        .expect("failed to link");
+
+    // bound and sealed, without a single instruction of the image run
+    task.assert_protection().expect("the image was not sealed");
+    assert!(
+        task.dump_got()
+            .iter()
+            .all(|entry| entry.status == GotStatus::Bound),
+        "{:#?}",
+        task.dump_got()
+    );
+}
+
+#[test]
+pub fn test_dylib_version_issues() {
+    let mut data = common::fixture("hello_world_fprintf/hello_world");
+
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    assert_eq!(task.dylib_version_issues(), []);
+    drop(task);
+
+    // ask for a libSystem compatibility version no system has, it sits
+    // right before the name in the dylib command of the arm64 slice
+    let magic = [0xcf, 0xfa, 0xed, 0xfe, 0x0c, 0x00, 0x00, 0x01];
+    let header = (0..data.len() - magic.len())
+        .find(|&index| data[index..index + magic.len()] == magic)
+        .expect("no arm64 slice");
+    let name = b"/usr/lib/libSystem.B.dylib";
+    let index = header
+        + data[header..]
+            .windows(name.len())
+            .position(|window| window == name)
+            .expect("no libSystem dependency");
+    data[index - 4..index].copy_from_slice(&0xffff0000u32.to_le_bytes());
+
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    let issues = task.dylib_version_issues();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].name, "/usr/lib/libSystem.B.dylib");
+    assert_eq!(issues[0].required, 0xffff0000);
+    drop(task);
+
+    let options = TaskOptions {
+        strict_dylib_versions: true,
+        ..Default::default()
+    };
+    let err = unsafe { Task::try_with_options(data.as_ptr(), data.len(), &options) }.unwrap_err();
+    assert!(matches!(err, LoadError::DylibTooOld { .. }), "{err}");
+}
+
+unsafe extern "C" {
+    // the libc crate does not declare it for Apple targets
+    #[link_name = "optind"]
+    static OPTIND: libc::c_int;
+}
+
+#[test]
+#[ignore = "needs binaries/auth_got/auth_got, built from binaries/auth_got/auth_got.c"]
+pub fn test_auth_got_signed() {
+    let data = common::fixture("auth_got/auth_got");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    assert!(task.is_arm64e());
+    task.segments_protect_maxprot();
+
+    let bindings = task.resolve_all_imports().expect("failed to link");
+    let strlen = bindings
+        .iter()
+        .find(|binding| binding.symbol == "strlen")
+        .expect("strlen was not bound");
+    assert_eq!(strlen.target, libc::strlen as *const () as usize);
+
+    // the slot holds the target signed with its own address
+    let slot = unsafe { (strlen.slot_addr as *const u64).read() };
+    assert_ne!(slot as usize, strlen.target);
+    assert!(pac::is_signed(slot));
+    let authenticated = unsafe { PacKey::InstructionA.authenticate(slot, strlen.slot_addr as u64) };
+    assert_eq!(authenticated as usize, strlen.target);
+
+    // a variable goes to the plain __got, its fixup is not authenticated
+    let optind = bindings
+        .iter()
+        .find(|binding| binding.symbol == "optind")
+        .expect("optind was not bound");
+    let slot = unsafe { (optind.slot_addr as *const u64).read() };
+    assert!(!pac::is_signed(slot));
+    assert_eq!(slot as usize, optind.target);
+
+    // and the stub calling through it authenticates it
+    task.segments_protect();
+    let len = unsafe { task.call_symbol("call_strlen", &[c"auth".as_ptr() as u64]) };
+    assert_eq!(len, Some(4));
+
+    // an int, only the low half of x0 is set
+    let optind = unsafe { task.call_symbol("read_optind", &[]) };
+    assert_eq!(optind.map(|value| value as i32), Some(unsafe { OPTIND }));
+}
+
+#[test]
+#[ignore = "needs binaries/local_got/local_got, built from binaries/local_got/local_got.c"]
+pub fn test_local_got_entries() {
+    let data = common::fixture("local_got/local_got");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.segments_protect_maxprot();
+
+    let bindings = task.resolve_all_imports().expect("failed to link");
+
+    // the image's own function is bound without a lookup
+    let local = bindings
+        .iter()
+        .find(|binding| binding.symbol == "local_fn")
+        .expect("local_fn has no symbol pointer");
+    assert_eq!(local.dylib, SELF_IMAGE);
+    assert_eq!(Some(local.target), task.symbol_address("local_fn"));
+
+    // next to a real import
+    let strlen = bindings
+        .iter()
+        .find(|binding| binding.symbol == "strlen")
+        .expect("strlen was not bound");
+    assert_eq!(strlen.target, libc::strlen as *const () as usize);
+}
+
+/// Loads `binaries/duplicate/<name>` with libfoo_a, which also exports
+/// `foo`, loaded first.
+fn duplicate_task(name: &str, flat_lookup: FlatLookup) -> (Vec<u8>, Task) {
+    let libfoo_a = common::fixture_path("duplicate/libfoo_a.dylib");
+    let libfoo_a = std::ffi::CString::new(libfoo_a.to_str().unwrap()).unwrap();
+    let handle = unsafe { libc::dlopen(libfoo_a.as_ptr(), libc::RTLD_NOW | libc::RTLD_GLOBAL) };
+    assert!(!handle.is_null(), "failed to load libfoo_a");
+
+    let path = common::fixture_path(&format!("duplicate/{name}"));
+    let data = common::fixture(&format!("duplicate/{name}"));
+    let options = TaskOptions {
+        image_path: Some(path),
+        flat_lookup,
+        ..Default::default()
+    };
+    let task = unsafe { Task::with_options(data.as_ptr(), data.len(), &options) };
+    (data, task)
+}
+
+#[test]
+#[ignore = "needs binaries/duplicate/*, built from binaries/duplicate/*.c"]
+pub fn test_duplicate_symbol_two_level() {
+    let (_data, mut task) = duplicate_task("duplicate", FlatLookup::Strict);
+    task.prepare().expect("failed to prepare");
+
+    // libfoo_a came first, but the image was linked against libfoo_b
+    let foo = unsafe { task.call_symbol("call_foo", &[]) };
+    assert_eq!(foo.map(|foo| foo as u32), Some(2));
+}
+
+#[test]
+#[ignore = "needs binaries/duplicate/*, built from binaries/duplicate/*.c"]
+pub fn test_duplicate_symbol_flat() {
+    let (_data, mut task) = duplicate_task("duplicate_flat", FlatLookup::Strict);
+    task.segments_protect_maxprot();
+    match task.resolve_all_imports() {
+        Err(LinkError::AmbiguousSymbol { symbol, images }) => {
+            assert_eq!(symbol, "foo");
+            assert_eq!(images.len(), 2, "{images:?}");
+        }
+        result => panic!("foo was not ambiguous: {result:?}"),
+    }
+
+    // the first loaded definition wins
+    let (_data, mut task) = duplicate_task("duplicate_flat", FlatLookup::FirstWins);
+    task.prepare().expect("failed to prepare");
+    let foo = unsafe { task.call_symbol("call_foo", &[]) };
+    assert_eq!(foo.map(|foo| foo as u32), Some(1));
+}
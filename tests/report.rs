@@ -0,0 +1,191 @@
+mod common;
+
+use loader::{
+    AddressSpace, TargetArch, Task, TaskOptions, error::LoadError, report::load_command_into_str,
+};
+
+#[test]
+pub fn test_unhandled_load_commands() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    let unhandled = task
+        .unhandled_load_commands()
+        .into_iter()
+        .map(load_command_into_str)
+        .collect::<Vec<_>>();
+
+    // nothing needs the dylinker path, the uuid keys the symbol cache
+    assert!(unhandled.contains(&"LC_LOAD_DYLINKER"), "{unhandled:?}");
+    assert!(!unhandled.contains(&"LC_UUID"), "{unhandled:?}");
+    assert!(!unhandled.contains(&"LC_SEGMENT_64"), "{unhandled:?}");
+    assert!(!unhandled.contains(&"LC_LOAD_DYLIB"), "{unhandled:?}");
+}
+
+#[test]
+pub fn test_preferred_base() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+
+    // __TEXT of an executable, right after the 4GB __PAGEZERO
+    assert_eq!(task.preferred_base(), 0x100000000);
+}
+
+#[test]
+pub fn test_unwind_info() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    let unwind_info = task.unwind_info().expect("no __unwind_info");
+
+    // UNWIND_SECTION_VERSION
+    assert_eq!(u32::from_le_bytes(unwind_info[..4].try_into().unwrap()), 1);
+}
+
+#[test]
+pub fn test_cstrings() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    assert_eq!(task.cstrings(), ["Hello, World\n"]);
+}
+
+#[test]
+pub fn test_link_time_addresses() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    let main = task.symbol_address("main").expect("no main");
+
+    // main as otool shows it, in the __TEXT linked at 0x100000000
+    let linked = task.unslide(main);
+    assert!(linked > task.preferred_base() as usize, "{linked:#x}");
+    assert_eq!(linked, task.entry_point);
+
+    assert_eq!(
+        task.symbolize_in(linked + 4, AddressSpace::LinkTime),
+        Some(("main", 4))
+    );
+    assert_eq!(task.symbolize(main + 4), Some(("main", 4)));
+}
+
+#[test]
+pub fn test_exports() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    let exports = task.exports();
+
+    let main = task.symbol_address("main").expect("no main");
+    assert!(exports.contains(&("main".to_string(), main)), "{exports:?}");
+
+    // imports are not exports
+    assert!(!exports.iter().any(|(name, _)| name == "fprintf"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+pub fn test_load_commands_json() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    let json: serde_json::Value =
+        serde_json::from_str(&task.load_commands_json()).expect("invalid json");
+
+    assert_eq!(json["cputype"], "arm64");
+    assert_eq!(json["preferred_base"], 0x100000000u64);
+    assert!(json["uuid"].is_string(), "{json}");
+
+    let segments = json["segments"].as_array().unwrap();
+    let text = segments
+        .iter()
+        .find(|segment| segment["name"] == "__TEXT")
+        .expect("no __TEXT segment");
+    assert!(
+        text["sections"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|section| section["name"] == "__text")
+    );
+
+    let dylibs = json["dylibs"].as_array().unwrap();
+    assert!(
+        dylibs
+            .iter()
+            .any(|dylib| dylib["name"] == "/usr/lib/libSystem.B.dylib"),
+        "{json}"
+    );
+}
+
+#[test]
+pub fn test_prefer_arch() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    assert_eq!(task.load_commands_summary().cputype, "arm64");
+
+    // the x86_64 slice is mapped for inspection, never run
+    let options = TaskOptions {
+        prefer_arch: Some(TargetArch::X86_64),
+        ..Default::default()
+    };
+    let task = unsafe { Task::try_with_options(data.as_ptr(), data.len(), &options) }
+        .expect("failed to load the x86_64 slice");
+    assert_eq!(task.load_commands_summary().cputype, "x86_64");
+
+    // a thin image only has its own architecture
+    let data = common::fixture("hello_world/hello_world");
+
+    let err = unsafe { Task::try_with_options(data.as_ptr(), data.len(), &options) }.unwrap_err();
+    match err {
+        LoadError::ArchNotFound { requested, found } => {
+            assert_eq!(requested, "x86_64");
+            assert_eq!(found, ["arm64"]);
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+pub fn test_text_exec_segment() {
+    // newer arm64 binaries keep their code in __TEXT_EXEC, rename __TEXT
+    // to it: the entry point has to be found by protection, not by name
+    let mut data = common::fixture("hello_world/hello_world");
+    let segname = common::segment_command(&data, "__TEXT") + 8;
+    data[segname..segname + 16].copy_from_slice(b"__TEXT_EXEC\0\0\0\0\0");
+
+    let task = unsafe { Task::try_with_pointer(data.as_ptr(), data.len()) }
+        .expect("failed to load __TEXT_EXEC");
+    let segment = task
+        .segment_at(task.entry_point)
+        .expect("the entry point is outside of any segment");
+    assert_eq!(segment.name(), "__TEXT_EXEC");
+    assert!(segment.is_executable());
+
+    let main = task.symbol_address("main").expect("no main");
+    assert_eq!(task.symbolize(main + 4), Some(("main", 4)));
+}
+
+#[test]
+pub fn test_absolute_symbol() {
+    // turn main into an N_ABS symbol: its n_value is an address as is,
+    // the slide is not added and it is no section symbol to symbolize
+    let mut data = common::fixture("hello_world/hello_world");
+    let nlist = common::symbol_nlist(&data, "_main");
+    data[nlist + 4] = 0x3 | 0x1; // N_ABS | N_EXT
+    data[nlist + 5] = 0; // NO_SECT
+    data[nlist + 8..nlist + 16].copy_from_slice(&0x1234u64.to_le_bytes());
+
+    let task = unsafe { Task::try_with_pointer(data.as_ptr(), data.len()) }
+        .expect("failed to load an absolute main");
+    assert_eq!(task.symbol_address("main"), Some(0x1234));
+
+    let entry_point = task.entry_point;
+    assert_ne!(
+        task.symbolize_in(entry_point + 4, AddressSpace::LinkTime)
+            .map(|(name, _)| name),
+        Some("main")
+    );
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use loader::{Task, TaskOptions, linker::Linker};
+
+#[test]
+#[ignore = "needs binaries/common/common, built from binaries/common/common.c"]
+pub fn test_common_is_zeroed() {
+    let data = common::fixture("common/common");
+
+    let mut task = unsafe { Task::with_pointer(data.as_ptr(), data.len()) };
+    task.segments_protect_maxprot();
+    Linker::new().link_raw(&mut task);
+    task.segments_protect();
+
+    // sums the 1MiB __common buffer
+    assert_eq!(unsafe { task.call_symbol("sum", &[]) }, Some(0));
+}
+
+#[test]
+pub fn test_verify_copy() {
+    let data = common::fixture("hello_world_fprintf/hello_world");
+
+    let options = TaskOptions {
+        verify_copy: true,
+        ..Default::default()
+    };
+    unsafe { Task::try_with_options(data.as_ptr(), data.len(), &options) }
+        .expect("a segment did not survive the copy");
+}